@@ -1,6 +1,7 @@
+use std::fmt;
 use std::fs;
 use std::path::PathBuf;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
 use griff::*;
 
@@ -9,22 +10,108 @@ use crate::rela;
 use crate::refs;
 use crate::srcs;
 use crate::cmdl;
+use crate::lookup::PositionIndex;
+use crate::query::QueryIndex;
+use crate::uri::FileUri;
 
 #[derive(Debug)]
 pub enum ParseError {
     CannotReadFile,
     RiffError(ChunkError),
+    // a chunk this parser doesn't know, or data where a stream was expected
+    UnexpectedChunk,
+    // a compressed string table that doesn't inflate, i.e. a shard caught mid-write
+    Decompress(String),
 }
 type ParseReturn = Result<ClangdFile, ParseError>;
 
+#[derive(Debug)]
+pub enum LoadError {
+    // no .cache directory under the root
+    NoCache(PathBuf),
+    // .cache exists but clangd hasn't written an index into it
+    NoIndex(PathBuf),
+    CannotReadIndex(std::io::Error),
+}
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::NoCache(p) => write!(f, "Unable to find .cache in {}!", p.display()),
+            LoadError::NoIndex(p) => write!(f, "No index in {}, has clangd been run?", p.display()),
+            LoadError::CannotReadIndex(e) => write!(f, "Unable to read the index: {}", e),
+        }
+    }
+}
+impl std::error::Error for LoadError {}
+
+// Keyed by IDX file name, hash included, since sources in different directories share a basename;
+// `ClangdDatabase::files_named` and `file_of` look shards up by source
 pub type ClangdFileMap = BTreeMap<String, ClangdFile>;
 pub type ClangdIdMap = BTreeMap<symbols::SymbolId, symbols::Symbol>;
 pub type ClangdNameMap = BTreeMap<String, symbols::Symbol>;
+pub type ClangdRefMap = BTreeMap<symbols::SymbolId, Vec<refs::RefReferences>>;
+
 #[derive(Debug, Clone)]
+pub struct ClangdParseFailure {
+    pub path: PathBuf,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Default)]
 pub struct ClangdDatabase {
     pub file: ClangdFileMap,
     pub id: ClangdIdMap,
     pub name: ClangdNameMap,
+    // references to each symbol, gathered from every file
+    pub refs: ClangdRefMap,
+    // IDX files that could not be parsed
+    pub failures: Vec<ClangdParseFailure>,
+    // symbol ranges per file, for position lookups
+    pub positions: PositionIndex,
+    // calls and class hierarchy, for queries
+    pub queries: QueryIndex,
+}
+
+impl ClangdDatabase {
+    /// Every relation across all files, deduplicated
+    pub fn relations(&self) -> BTreeSet<rela::Rela> {
+        self.file.values()
+            .flat_map(|f| f.relations.data.iter().cloned())
+            .collect()
+    }
+
+    /// Every (includer, included) URI pair across all files, deduplicated
+    pub fn include_edges(&self) -> BTreeSet<(FileUri, FileUri)> {
+        self.file.values()
+            .flat_map(|f| f.sources.data.iter())
+            .flat_map(|s| s.direct_includes.iter().map(|i| (s.uri.clone(), i.clone())))
+            .collect()
+    }
+
+    /// Shards of the sources named `name`, i.e. foo.h; `file` is keyed by IDX file name,
+    /// so this is the lookup by source name, with one shard per directory holding a `name`
+    pub fn files_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = (&'a String, &'a ClangdFile)> {
+        self.file.iter().filter(move |(k, _)| crate::shard_name(k) == Some(name))
+    }
+
+    /// The shard of `uri`: named after it and listing it among its sources
+    pub fn file_of(&self, uri: &FileUri) -> Option<&ClangdFile> {
+        let key = self.file_key(uri)?;
+        self.file.get(&key)
+    }
+
+    pub fn file_of_mut(&mut self, uri: &FileUri) -> Option<&mut ClangdFile> {
+        let key = self.file_key(uri)?;
+        self.file.get_mut(&key)
+    }
+
+    fn file_key(&self, uri: &FileUri) -> Option<String> {
+        let name = uri.file_name()?;
+        let key = self.files_named(&name)
+            .find(|(_, f)| f.sources.data.iter().any(|s| s.uri == *uri))
+            .map(|(k, _)| k.clone());
+        key
+    }
 }
 
 pub trait ClangdUtility {
@@ -41,18 +128,16 @@ pub trait ClangdUtility {
             bytes_read += 1;
             shift += 7;
             if cont != 1
-            || !(shift < 32) {
+            || shift >= 32 {
                 break;
             }
         }
         (bytes_read, varint)
     }
 
-    fn get_string(buf: &[u8], string_table: &Vec<String>) -> (usize, String) {
-        let bytes_read: usize;
+    fn get_string(buf: &[u8], string_table: &[String]) -> (usize, String) {
         let mut s: String = String::new();
-        let (sz, idx) = Self::get_varint(buf);
-        bytes_read = sz;
+        let (bytes_read, idx) = Self::get_varint(buf);
         if (idx as usize) < string_table.len() {
             s = string_table[ idx as usize ].clone();
         }
@@ -63,21 +148,21 @@ pub trait ClangdUtility {
 
     fn get_u32(buf: &[u8]) -> (usize, u32) {
         let mut ret: u32 = 0;
-        for i in 0..4 {
-            ret |= (buf[i] as u32) << i*8;
+        for (i, b) in buf.iter().enumerate().take(4) {
+            ret |= (*b as u32) << (i*8);
         }
         (4, ret)
     }
 
-    fn decompress(buf: &[u8]) -> Vec<u8> {
+    fn decompress(buf: &[u8]) -> std::io::Result<Vec<u8>> {
         use std::io::{BufReader, Read};
         use libflate::zlib::Decoder;
 
         let reader = BufReader::new(buf);
         let mut v: Vec<u8> = vec![];
-        let mut decoder = Decoder::new(reader).unwrap();
-        decoder.read_to_end(&mut v).unwrap();
-        v
+        let mut decoder = Decoder::new(reader)?;
+        decoder.read_to_end(&mut v)?;
+        Ok(v)
     }
 }
 
@@ -103,6 +188,7 @@ pub struct ClangdSources {
 
 #[derive(Debug, Clone, Default)]
 pub struct ClangdMetaData {
+    #[allow(dead_code)]
     version: [u8;4],
 }
 
@@ -150,15 +236,14 @@ impl ClangdFile {
     
         let contents = _contents.unwrap();
         let data = contents.as_slice();
-        let riff = Riff::parse(data).await;
-        if riff.is_err() {
-            return Err(ParseError::RiffError(riff.unwrap_err()));
-        }
-        let cd: ClangdFile = ClangdFile::consume_riff(&riff.unwrap());
-        Ok(cd)
+        let riff = match Riff::parse(data).await {
+            Ok(riff) => riff,
+            Err(e) => return Err(ParseError::RiffError(e)),
+        };
+        ClangdFile::consume_riff(&riff)
     }
     
-    fn consume_riff(riff: &Riff) -> ClangdFile {
+    fn consume_riff(riff: &Riff) -> ParseReturn {
         let mut cd: ClangdFile = Default::default();
         if let Some(r) = &riff.chunk {
             match r.id {
@@ -169,71 +254,72 @@ impl ClangdFile {
                                 for child in &x.data {
                                     match child.id {
                                         ChunkId::Stri => {
-                                            let data: ChunkStream = ClangdFile::get_stream(&child.data);
-                                            cd.string = ClangdFile::consume_string(&data);
+                                            let data: ChunkStream = ClangdFile::get_stream(&child.data)?;
+                                            cd.string = ClangdFile::consume_string(&data)?;
                                         },
                                         ChunkId::Symb => {
-                                            let data: ChunkStream = ClangdFile::get_stream(&child.data);
+                                            let data: ChunkStream = ClangdFile::get_stream(&child.data)?;
                                             cd.symbols = ClangdFile::consume_symbols(&data, &cd.string);
                                         },
                                         ChunkId::Srcs => {
-                                            let data: ChunkStream = ClangdFile::get_stream(&child.data);
+                                            let data: ChunkStream = ClangdFile::get_stream(&child.data)?;
                                             cd.sources = ClangdFile::consume_sources(&data, &cd.string);
                                         },
                                         ChunkId::Rela => {
-                                            let data: ChunkStream = ClangdFile::get_stream(&child.data);
+                                            let data: ChunkStream = ClangdFile::get_stream(&child.data)?;
                                             cd.relations = ClangdFile::consume_relations(&data);
                                         },
                                         ChunkId::Refs => {
-                                            let data: ChunkStream = ClangdFile::get_stream(&child.data);
+                                            let data: ChunkStream = ClangdFile::get_stream(&child.data)?;
                                             cd.references = ClangdFile::consume_references(&data, &cd.string);
                                         },
                                         ChunkId::Cmdl => {
-                                            let data: ChunkStream = ClangdFile::get_stream(&child.data);
+                                            let data: ChunkStream = ClangdFile::get_stream(&child.data)?;
                                             cd.cmdline = ClangdFile::consume_cmdline(&data, &cd.string);
                                         },
                                         ChunkId::Meta => {
-                                            let data: ChunkStream = ClangdFile::get_stream(&child.data);
-                                            cd.meta = ClangdFile::consume_metadata(&data);
+                                            let data: ChunkStream = ClangdFile::get_stream(&child.data)?;
+                                            cd.meta = ClangdFile::consume_metadata(&data)?;
                                         },                                        
-                                        _ => todo!()
+                                        _ => return Err(ParseError::UnexpectedChunk),
                                     }
                                 }
                             }
                         },
-                        _ => todo!()
+                        _ => return Err(ParseError::UnexpectedChunk),
                     }
                 },
-                _ => todo!()
+                _ => return Err(ParseError::UnexpectedChunk),
             }
         }
     
-        cd
+        Ok(cd)
     }
 
-    fn consume_string(data: &ChunkStream) -> Vec<String> {
-        let v: Vec<String>;
+    fn consume_string(data: &ChunkStream) -> Result<Vec<String>, ParseError> {
         let buf: &[u8] = data.data.as_slice();
+        if buf.len() < 4 {
+            return Err(ParseError::UnexpectedChunk);
+        }
         let (sz, compr_sz) = Self::get_u32(buf);
         if compr_sz == 0 {
             // uncompressed
-            v = Self::get_strings(buf.get(sz..).unwrap());
+            Ok(Self::get_strings(&buf[sz..]))
         }
         else {
             // compressed
-            let decomp = Self::decompress(buf.get(sz..).unwrap());
+            let decomp = Self::decompress(&buf[sz..]).map_err(|e| ParseError::Decompress(e.to_string()))?;
             let buf = decomp.as_slice();
-            v = Self::get_strings(buf);
+            Ok(Self::get_strings(buf))
         }
-        v
     }
 
     fn get_strings(buf: &[u8]) -> Vec<String> {
         let mut v: Vec<String> = vec![];
         let mut s: String = String::new();
-        for i in 0..buf.len() {
-            s.push(buf[i] as char);
-            if buf[i] == b'\0' {
+        for b in buf {
+            s.push(*b as char);
+            if *b == b'\0' {
                 v.push(s.clone());
                 s.clear();
             }
@@ -241,46 +327,35 @@ impl ClangdFile {
         v
     }
 
-    fn consume_symbols(data: &ChunkStream, string_table: &Vec<String>) -> ClangdSymbols {
-        let mut cs: ClangdSymbols = Default::default();
-        cs.data = symbols::Symbol::parse(data, string_table);
-        cs
+    fn consume_symbols(data: &ChunkStream, string_table: &[String]) -> ClangdSymbols {
+        ClangdSymbols { data: symbols::Symbol::parse(data, string_table) }
     }
 
-    fn consume_sources(data: &ChunkStream, string_table: &Vec<String>) -> ClangdSources {
-        let mut cs: ClangdSources = Default::default();
-        cs.data = srcs::Srcs::parse(data, string_table);
-        cs
+    fn consume_sources(data: &ChunkStream, string_table: &[String]) -> ClangdSources {
+        ClangdSources { data: srcs::Srcs::parse(data, string_table) }
     }
 
     fn consume_relations(data: &ChunkStream) -> ClangdRelations {
-        let mut cr: ClangdRelations = Default::default();
-        cr.data = rela::Rela::parse(data);
-        cr
+        ClangdRelations { data: rela::Rela::parse(data) }
     }
 
-    fn consume_references(data: &ChunkStream, string_table: &Vec<String>) -> ClangdReferences {
-        let mut cr: ClangdReferences = Default::default();
-        cr.data = refs::Refs::parse(data, string_table);
-        cr
+    fn consume_references(data: &ChunkStream, string_table: &[String]) -> ClangdReferences {
+        ClangdReferences { data: refs::Refs::parse(data, string_table) }
     }
 
-    fn consume_cmdline(data: &ChunkStream, string_table: &Vec<String>) -> ClangdCmdLine {
-        let mut ccl: ClangdCmdLine = Default::default();
-        ccl.data = cmdl::Cmdl::parse(data, string_table);
-        ccl
+    fn consume_cmdline(data: &ChunkStream, string_table: &[String]) -> ClangdCmdLine {
+        ClangdCmdLine { data: cmdl::Cmdl::parse(data, string_table) }
     }
 
-    fn consume_metadata(data: &ChunkStream) -> ClangdMetaData {
-        let mut cm: ClangdMetaData = Default::default();
-        cm.version = data.data.clone().as_slice().try_into().unwrap();
-        cm
+    fn consume_metadata(data: &ChunkStream) -> Result<ClangdMetaData, ParseError> {
+        let version = data.data.as_slice().try_into().map_err(|_| ParseError::UnexpectedChunk)?;
+        Ok(ClangdMetaData { version })
     }
 
-    fn get_stream(cd: &ChunkData) -> ChunkStream {
+    fn get_stream(cd: &ChunkData) -> Result<ChunkStream, ParseError> {
         match cd {
-            ChunkData::StreamData(x) => x.clone(),
-            _ => todo!()
+            ChunkData::StreamData(x) => Ok(x.clone()),
+            _ => Err(ParseError::UnexpectedChunk),
         }
     }
 }
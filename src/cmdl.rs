@@ -1,7 +1,7 @@
 use griff::ChunkStream;
 use crate::clangd::ClangdUtility;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct Cmdl {
     pub directory: String,
     pub cmdl: Vec<String>,
@@ -10,10 +10,10 @@ impl ClangdUtility for Cmdl{}
 
 impl Cmdl {
     #[allow(dead_code)]
-    pub fn parse(stream: &ChunkStream, string_tables: &Vec<String>) -> Vec<Self> {
+    pub fn parse(stream: &ChunkStream, string_tables: &[String]) -> Vec<Self> {
         let mut cmdlines: Vec<Cmdl> = vec![];
         let data: &[u8] = stream.data.as_slice();
-        if data.len() == 0 {
+        if data.is_empty() {
             return cmdlines;
         }
         let mut cursor: usize = 0;
@@ -0,0 +1,158 @@
+//! Classify changes to symbols declared in public headers as breaking or non-breaking.
+
+use serde::Serialize;
+
+use std::path::{Path, PathBuf};
+use std::collections::BTreeSet;
+
+use crate::clangd::ClangdDatabase;
+use crate::diff::SymbolChange;
+use crate::symbols::{Symbol, SymbolKind, SymbolFlags, symbol_id_str};
+use crate::uri::FileUri;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Breaking,
+    NonBreaking,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CompatChangeKind {
+    Added,
+    Removed,
+    ParameterTypes,
+    ReturnType,
+    Type,
+    Signature,
+    DeclarationMoved,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CompatChange {
+    pub severity: Severity,
+    pub change: CompatChangeKind,
+    pub id: String,
+    pub name: String,
+    pub kind: String,
+    pub header: FileUri,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CompatReport {
+    pub breaking: usize,
+    pub non_breaking: usize,
+    pub changes: Vec<CompatChange>,
+}
+impl CompatReport {
+    pub fn is_breaking(&self) -> bool {
+        self.breaking > 0
+    }
+}
+
+/// Compare the public API of `old` against `new`, each indexed from the given project root.
+/// Only symbols declared in a header under one of `public` (any header if empty) are considered;
+/// relative paths are taken from the root.
+pub fn check(old: &ClangdDatabase, old_root: &Path, new: &ClangdDatabase, new_root: &Path, public: &[String]) -> CompatReport {
+    let mut report: CompatReport = Default::default();
+    let diff = old.diff(new);
+    let old_public = resolve(old_root, public);
+    let new_public = resolve(new_root, public);
+    let old_names: BTreeSet<String> = old.id.values().map(|s| s.qualified_name()).collect();
+
+    for s in diff.removed.iter().filter(|s| is_public(s, &old_public)) {
+        report.changes.push(change(Severity::Breaking, CompatChangeKind::Removed, s, None, None));
+    }
+    for s in diff.added.iter().filter(|s| is_public(s, &new_public)) {
+        // New members change the layout of a public struct, but not of one that is itself new
+        let severity = match s.syminfo.kind {
+            SymbolKind::Field if old_names.contains(s.scope.trim_end_matches("::")) => Severity::Breaking,
+            _ => Severity::NonBreaking,
+        };
+        report.changes.push(change(severity, CompatChangeKind::Added, s, None, None));
+    }
+    for d in diff.changed.iter().filter(|d| is_public(&d.old, &old_public)) {
+        let function = d.new.is_function();
+        let mut typed = false;
+        for c in d.changes.iter() {
+            let (severity, kind, o, n) = match c {
+                SymbolChange::Signature { old, new } if function => {
+                    if parameter_types(&d.old) != parameter_types(&d.new) {
+                        typed = true;
+                        (Severity::Breaking, CompatChangeKind::ParameterTypes, old.clone(), new.clone())
+                    }
+                    else {
+                        // parameter renames only
+                        (Severity::NonBreaking, CompatChangeKind::Signature, old.clone(), new.clone())
+                    }
+                },
+                SymbolChange::Signature { .. } => continue,
+                // ReturnType also holds the type of variables and fields
+                SymbolChange::ReturnType { old, new } if function => {
+                    typed = true;
+                    (Severity::Breaking, CompatChangeKind::ReturnType, old.clone(), new.clone())
+                },
+                SymbolChange::ReturnType { old, new } => {
+                    typed = true;
+                    (Severity::Breaking, CompatChangeKind::Type, old.clone(), new.clone())
+                },
+                // Type is opaque, only worth reporting when nothing more precise was found
+                SymbolChange::Type { .. } => continue,
+                SymbolChange::DeclarationMoved { old, new } =>
+                    (Severity::NonBreaking, CompatChangeKind::DeclarationMoved, old.file_uri.to_string(), new.file_uri.to_string()),
+                SymbolChange::DefinitionMoved { .. } => continue,
+            };
+            report.changes.push(change(severity, kind, &d.new, Some(o), Some(n)));
+        }
+        if !typed {
+            if let Some(SymbolChange::Type { old, new }) = d.changes.iter().find(|c| matches!(c, SymbolChange::Type { .. })) {
+                report.changes.push(change(Severity::Breaking, CompatChangeKind::Type, &d.new, Some(old.clone()), Some(new.clone())));
+            }
+        }
+    }
+
+    report.breaking = report.changes.iter().filter(|c| c.severity == Severity::Breaking).count();
+    report.non_breaking = report.changes.len() - report.breaking;
+    report
+}
+
+fn change(severity: Severity, kind: CompatChangeKind, s: &Symbol, old: Option<String>, new: Option<String>) -> CompatChange {
+    CompatChange {
+        severity,
+        change: kind,
+        id: symbol_id_str(&s.id),
+        name: s.qualified_name(),
+        kind: format!("{:?}", s.syminfo.kind),
+        header: s.canonical_declaration.file_uri.clone(),
+        old,
+        new,
+    }
+}
+
+// `public` as absolute paths, relative ones taken from `root`
+fn resolve(root: &Path, public: &[String]) -> Vec<PathBuf> {
+    let root = std::path::absolute(root).unwrap_or_else(|_| root.to_path_buf());
+    public.iter().map(|p| root.join(p)).collect()
+}
+
+fn is_public(s: &Symbol, public: &[PathBuf]) -> bool {
+    let uri = &s.canonical_declaration.file_uri;
+    let path = uri.to_path().unwrap_or_else(|| PathBuf::from(uri.path()));
+    uri.is_header()
+    && s.has_flag(SymbolFlags::VisibleOutsideFile)
+    && (public.is_empty() || public.iter().any(|p| path.starts_with(p)))
+}
+
+/// Parameter types of a function such as `(const char *name, int len)`, with names dropped and spacing normalised
+pub fn parameter_types(s: &Symbol) -> Vec<String> {
+    let mut types: Vec<String> = s.params().iter()
+        .map(|p| p.t.split_whitespace().collect::<Vec<&str>>().join(" ").replace(" *", "*").replace(" &", "&"))
+        .collect();
+    if s.signature.contains("...") {
+        types.push("...".to_string());
+    }
+    types
+}
@@ -0,0 +1,140 @@
+//! Recover each translation unit's compile command, i.e. to regenerate compile_commands.json.
+
+use serde::Serialize;
+
+use std::path::{Path, PathBuf};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::clangd::ClangdDatabase;
+use crate::cmdl::Cmdl;
+use crate::srcs::SourceFlags;
+use crate::uri::FileUri;
+
+// Flags clangd adds to every command on top of what the build system gave it
+const INJECTED_FLAGS: [&str; 2] = ["-resource-dir", "-fsyntax-only"];
+
+// Flags whose value may be given as the next argument
+const JOINABLE_FLAGS: [&str; 5] = ["-I", "-D", "-U", "-isystem", "-iquote"];
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CompileCommand {
+    pub directory: String,
+    pub file: String,
+    pub arguments: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CompileCommandOptions {
+    // drop flags clangd injected, like -resource-dir
+    pub strip_injected: bool,
+    // join split flags, make include paths absolute and drop duplicates
+    pub normalize: bool,
+}
+
+impl ClangdDatabase {
+    /// Translation unit URI -> the command clangd compiled it with
+    pub fn compile_commands(&self) -> BTreeMap<FileUri, Cmdl> {
+        let mut m: BTreeMap<FileUri, Cmdl> = BTreeMap::new();
+        for file in self.file.values() {
+            let tu = file.sources.data.iter().find(|s| s.has_flag(SourceFlags::IsTU));
+            if let (Some(tu), Some(cmd)) = (tu, file.cmdline.data.first()) {
+                m.entry(tu.uri.clone()).or_insert_with(|| cmd.clone());
+            }
+        }
+        m
+    }
+
+    /// The command `tu` was compiled with, the same one `compile_commands` picks
+    pub fn compile_command(&self, tu: &FileUri) -> Option<&Cmdl> {
+        self.file.values()
+            .find(|f| f.sources.data.iter().find(|s| s.has_flag(SourceFlags::IsTU)).is_some_and(|s| s.uri == *tu))
+            .and_then(|f| f.cmdline.data.first())
+    }
+
+    /// Directories `tu` searches for quoted includes, in command-line order
+    pub fn include_dirs(&self, tu: &FileUri) -> Vec<PathBuf> {
+        let Some(cmd) = self.compile_command(tu) else { return vec![] };
+        normalize_arguments(&cmd.cmdl, &cmd.directory).iter()
+            .filter_map(|a| a.strip_prefix("-iquote").or_else(|| a.strip_prefix("-I")))
+            .filter(|d| !d.is_empty())
+            .map(PathBuf::from)
+            .collect()
+    }
+}
+
+/// Entries for a compile_commands.json
+pub fn compile_database(db: &ClangdDatabase, opts: &CompileCommandOptions) -> Vec<CompileCommand> {
+    db.compile_commands().iter()
+        .map(|(uri, cmd)| {
+            let mut arguments = cmd.cmdl.clone();
+            if opts.strip_injected {
+                arguments = strip_injected(&arguments);
+            }
+            if opts.normalize {
+                arguments = normalize_arguments(&arguments, &cmd.directory);
+            }
+            CompileCommand { directory: cmd.directory.clone(), file: uri.path(), arguments }
+        })
+        .collect()
+}
+
+pub fn strip_injected(args: &[String]) -> Vec<String> {
+    let mut out: Vec<String> = vec![];
+    let mut skip = false;
+    for a in args {
+        if skip {
+            skip = false;
+            continue;
+        }
+        if INJECTED_FLAGS.contains(&a.as_str()) {
+            // -resource-dir <dir>
+            skip = a == "-resource-dir";
+            continue;
+        }
+        if INJECTED_FLAGS.iter().any(|f| a.starts_with(&format!("{}=", f))) {
+            continue;
+        }
+        out.push(a.clone());
+    }
+    out
+}
+
+/// Join `-I dir` into `-Idir`, make relative include paths absolute and drop repeated flags:
+/// include directories seen before, and -D/-U repeating what the macro was last set to, so `-DX -UX -DX` stays
+pub fn normalize_arguments(args: &[String], directory: &str) -> Vec<String> {
+    let mut out: Vec<String> = vec![];
+    let mut dirs: BTreeSet<String> = BTreeSet::new();
+    // macro name -> the last -D or -U of it
+    let mut macros: BTreeMap<String, String> = BTreeMap::new();
+    let mut iter = args.iter().peekable();
+    while let Some(a) = iter.next() {
+        let mut arg = a.clone();
+        if JOINABLE_FLAGS.contains(&a.as_str()) {
+            if let Some(v) = iter.next() {
+                arg = format!("{}{}", a, v);
+            }
+        }
+        for f in ["-I", "-isystem", "-iquote"] {
+            if let Some(dir) = arg.strip_prefix(f) {
+                if !dir.is_empty() && Path::new(dir).is_relative() {
+                    arg = format!("{}{}", f, Path::new(directory).join(dir).display());
+                }
+                break;
+            }
+        }
+        // only flags carrying their own value can be dropped safely, i.e. not -Xclang <arg>
+        let joined = JOINABLE_FLAGS.iter().any(|f| arg.len() > f.len() && arg.starts_with(f));
+        let repeated = match arg.strip_prefix("-D").or_else(|| arg.strip_prefix("-U")) {
+            Some(m) if joined => {
+                let name = m.split_once('=').map_or(m, |(n, _)| n).to_string();
+                macros.insert(name, arg.clone()).as_ref() == Some(&arg)
+            },
+            _ => joined && !dirs.insert(arg.clone()),
+        };
+        if repeated {
+            continue;
+        }
+        out.push(arg);
+    }
+    out
+}
@@ -0,0 +1,96 @@
+//! Find functions, globals, macros and types that are defined but never used.
+
+use std::fmt;
+use std::collections::BTreeMap;
+
+use crate::clangd::ClangdDatabase;
+use crate::refs::RefKind;
+use crate::sarif::{Finding, Level, Rule};
+use crate::symbols::{Symbol, SymbolKind, SymbolFlags};
+use crate::uri::FileUri;
+
+pub const DEAD_CODE_RULE: Rule = Rule {
+    id: "dead-code",
+    description: "Symbol is defined but never referenced outside its own definition",
+};
+
+// Entry points that are never referenced from the index
+const ENTRY_POINTS: [&str; 2] = ["main", "wmain"];
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DeadCodeReport {
+    // definition file URI -> unused symbols defined there
+    pub files: BTreeMap<FileUri, Vec<Symbol>>,
+}
+impl DeadCodeReport {
+    pub fn len(&self) -> usize {
+        self.files.values().map(|v| v.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    pub fn findings(&self) -> Vec<Finding> {
+        self.files.values()
+            .flatten()
+            .map(|s| Finding {
+                rule: DEAD_CODE_RULE.id,
+                level: Level::Warning,
+                message: format!("{:?} '{}' is never used", s.syminfo.kind, s.qualified_name()),
+                location: s.definition.clone(),
+            })
+            .collect()
+    }
+}
+impl fmt::Display for DeadCodeReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (file, syms) in self.files.iter() {
+            writeln!(f, "{}", file)?;
+            for s in syms.iter() {
+                writeln!(f, "    {}:{} {:?} {}", s.definition.start.line + 1, s.definition.start.column + 1,
+                    s.syminfo.kind, s.qualified_name())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Symbols defined in the index but never referenced outside their own definition.
+/// Names matching any of the `allow` patterns (`*` wildcards) are skipped.
+pub fn find(db: &ClangdDatabase, allow: &[String]) -> DeadCodeReport {
+    let mut report: DeadCodeReport = Default::default();
+
+    for sym in db.id.values() {
+        if !is_candidate(sym)
+        || ENTRY_POINTS.contains(&sym.name.as_str())
+        || allow.iter().any(|p| crate::wildcard_match(p, &sym.name) || crate::wildcard_match(p, &sym.qualified_name())) {
+            continue;
+        }
+        // Part of a library's public interface
+        if sym.has_flag(SymbolFlags::VisibleOutsideFile)
+        && sym.canonical_declaration.file_uri.is_header() {
+            continue;
+        }
+        let used = match db.refs.get(&sym.id) {
+            // Recursion and other self references don't count
+            Some(refs) => refs.iter().any(|r| r.has_kind(RefKind::Reference) && r.container_id != sym.id),
+            // No refs were recorded, trust clangd's count
+            None => sym.references > 0,
+        };
+        if !used {
+            report.files.entry(sym.definition.file_uri.clone()).or_default().push(sym.clone());
+        }
+    }
+
+    for syms in report.files.values_mut() {
+        syms.sort_by_key(|s| (s.definition.start.line, s.definition.start.column));
+    }
+    report
+}
+
+fn is_candidate(sym: &Symbol) -> bool {
+    use SymbolKind::*;
+    !sym.definition.file_uri.is_empty()
+    && matches!(sym.syminfo.kind, Function | Variable | Macro | Struct | Class | Union | Enum | TypeAlias)
+}
@@ -0,0 +1,150 @@
+//! Compare two parsed indexes, i.e. built from two different commits.
+
+use std::fmt;
+
+use crate::clangd::ClangdDatabase;
+use crate::rela::Rela;
+use crate::symbols::{Symbol, SymbolLocation, symbol_id_str};
+use crate::uri::FileUri;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SymbolChange {
+    Signature { old: String, new: String },
+    ReturnType { old: String, new: String },
+    Type { old: String, new: String },
+    DefinitionMoved { old: SymbolLocation, new: SymbolLocation },
+    DeclarationMoved { old: SymbolLocation, new: SymbolLocation },
+}
+impl fmt::Display for SymbolChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use SymbolChange::*;
+        match self {
+            Signature { old, new } => write!(f, "signature '{}' -> '{}'", old, new),
+            ReturnType { old, new } => write!(f, "return type '{}' -> '{}'", old, new),
+            Type { old, new } => write!(f, "type '{}' -> '{}'", old, new),
+            DefinitionMoved { old, new } => write!(f, "definition {}:{} -> {}:{}",
+                old.file_uri, old.start.line + 1, new.file_uri, new.start.line + 1),
+            DeclarationMoved { old, new } => write!(f, "declaration {}:{} -> {}:{}",
+                old.file_uri, old.start.line + 1, new.file_uri, new.start.line + 1),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SymbolDiff {
+    pub old: Symbol,
+    pub new: Symbol,
+    pub changes: Vec<SymbolChange>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DatabaseDiff {
+    pub added: Vec<Symbol>,
+    pub removed: Vec<Symbol>,
+    pub changed: Vec<SymbolDiff>,
+    pub relations_added: Vec<Rela>,
+    pub relations_removed: Vec<Rela>,
+    // (includer, included)
+    pub includes_added: Vec<(FileUri, FileUri)>,
+    pub includes_removed: Vec<(FileUri, FileUri)>,
+}
+impl DatabaseDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+        && self.removed.is_empty()
+        && self.changed.is_empty()
+        && self.relations_added.is_empty()
+        && self.relations_removed.is_empty()
+        && self.includes_added.is_empty()
+        && self.includes_removed.is_empty()
+    }
+}
+impl fmt::Display for DatabaseDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for s in self.added.iter() {
+            writeln!(f, "+ {} {}", symbol_id_str(&s.id), s.qualified_name())?;
+        }
+        for s in self.removed.iter() {
+            writeln!(f, "- {} {}", symbol_id_str(&s.id), s.qualified_name())?;
+        }
+        for d in self.changed.iter() {
+            writeln!(f, "~ {} {}", symbol_id_str(&d.new.id), d.new.qualified_name())?;
+            for c in d.changes.iter() {
+                writeln!(f, "    {}", c)?;
+            }
+        }
+        for r in self.relations_added.iter() {
+            writeln!(f, "+ {} {:?} {}", symbol_id_str(&r.subject), r.predicate, symbol_id_str(&r.object))?;
+        }
+        for r in self.relations_removed.iter() {
+            writeln!(f, "- {} {:?} {}", symbol_id_str(&r.subject), r.predicate, symbol_id_str(&r.object))?;
+        }
+        for (from, to) in self.includes_added.iter() {
+            writeln!(f, "+ {} -> {}", from, to)?;
+        }
+        for (from, to) in self.includes_removed.iter() {
+            writeln!(f, "- {} -> {}", from, to)?;
+        }
+        Ok(())
+    }
+}
+
+impl ClangdDatabase {
+    /// Everything that changed going from `self` to `other`
+    pub fn diff(&self, other: &ClangdDatabase) -> DatabaseDiff {
+        let mut d: DatabaseDiff = Default::default();
+
+        for (id, sym) in other.id.iter() {
+            match self.id.get(id) {
+                None => d.added.push(sym.clone()),
+                Some(old) => {
+                    let changes = diff_symbol(old, sym);
+                    if !changes.is_empty() {
+                        d.changed.push(SymbolDiff { old: old.clone(), new: sym.clone(), changes });
+                    }
+                },
+            }
+        }
+        d.removed = self.id.iter()
+            .filter(|(id, _)| !other.id.contains_key(*id))
+            .map(|(_, s)| s.clone())
+            .collect();
+
+        let (old, new) = (self.relations(), other.relations());
+        d.relations_added = new.difference(&old).cloned().collect();
+        d.relations_removed = old.difference(&new).cloned().collect();
+
+        let (old, new) = (self.include_edges(), other.include_edges());
+        d.includes_added = new.difference(&old).cloned().collect();
+        d.includes_removed = old.difference(&new).cloned().collect();
+
+        d
+    }
+}
+
+fn diff_symbol(old: &Symbol, new: &Symbol) -> Vec<SymbolChange> {
+    let mut changes: Vec<SymbolChange> = vec![];
+    if old.signature != new.signature {
+        changes.push(SymbolChange::Signature { old: old.signature.clone(), new: new.signature.clone() });
+    }
+    if old.return_t != new.return_t {
+        changes.push(SymbolChange::ReturnType { old: old.return_t.clone(), new: new.return_t.clone() });
+    }
+    if old.t != new.t {
+        changes.push(SymbolChange::Type { old: old.t.clone(), new: new.t.clone() });
+    }
+    if moved(&old.definition, &new.definition) {
+        changes.push(SymbolChange::DefinitionMoved { old: old.definition.clone(), new: new.definition.clone() });
+    }
+    if moved(&old.canonical_declaration, &new.canonical_declaration) {
+        changes.push(SymbolChange::DeclarationMoved {
+            old: old.canonical_declaration.clone(), new: new.canonical_declaration.clone()
+        });
+    }
+    changes
+}
+
+// Only a change of file counts; an edit above a symbol shifts its line without moving it
+fn moved(old: &SymbolLocation, new: &SymbolLocation) -> bool {
+    old.file_uri != new.file_uri
+}
@@ -0,0 +1,180 @@
+//! Compare the compile flags of every translation unit and point out the odd ones out.
+
+use serde::Serialize;
+
+use std::fmt;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::clangd::ClangdDatabase;
+use crate::compdb;
+use crate::uri::FileUri;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FlagCategory {
+    Define,
+    IncludePath,
+    Standard,
+    Optimization,
+    Warning,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct FlagProfile {
+    pub uri: FileUri,
+    pub defines: BTreeSet<String>,
+    pub include_paths: BTreeSet<String>,
+    pub standard: Option<String>,
+    pub optimization: Option<String>,
+    pub warnings: BTreeSet<String>,
+}
+impl FlagProfile {
+    pub fn from_arguments(uri: &FileUri, args: &[String]) -> Self {
+        let mut p = FlagProfile { uri: uri.clone(), ..Default::default() };
+        for a in args {
+            if let Some(v) = a.strip_prefix("-D") {
+                p.defines.insert(v.to_string());
+            }
+            else if let Some(v) = a.strip_prefix("-I") {
+                p.include_paths.insert(v.to_string());
+            }
+            else if let Some(v) = a.strip_prefix("-std=") {
+                // the last one wins, as it does for the compiler
+                p.standard = Some(v.to_string());
+            }
+            else if a.starts_with("-O") {
+                p.optimization = Some(a.clone());
+            }
+            else if a.starts_with("-W") && !a.starts_with("-Wl,") && !a.starts_with("-Wa,") && !a.starts_with("-Wp,") {
+                p.warnings.insert(a.clone());
+            }
+        }
+        p
+    }
+
+    fn set(&self, c: FlagCategory) -> BTreeSet<String> {
+        match c {
+            FlagCategory::Define => self.defines.clone(),
+            FlagCategory::IncludePath => self.include_paths.clone(),
+            FlagCategory::Warning => self.warnings.clone(),
+            FlagCategory::Standard => self.standard.iter().cloned().collect(),
+            FlagCategory::Optimization => self.optimization.iter().cloned().collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviationKind {
+    // most translation units have the flag, this one doesn't
+    Missing,
+    // few translation units have the flag, this one does
+    Extra,
+    // a single-valued flag differs from the most common value
+    Different { majority: Option<String> },
+}
+
+/// One flag, or one value of a single-valued flag, and every translation unit deviating with it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Deviation {
+    pub category: FlagCategory,
+    pub flag: Option<String>,
+    pub kind: DeviationKind,
+    pub uris: Vec<FileUri>,
+}
+impl fmt::Display for Deviation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let flag = self.flag.clone().unwrap_or("(none)".to_string());
+        let n = self.uris.len();
+        match &self.kind {
+            DeviationKind::Missing => write!(f, "{:?} {} missing from {} translation units", self.category, flag, n)?,
+            DeviationKind::Extra => write!(f, "{:?} {} only in {} translation units", self.category, flag, n)?,
+            DeviationKind::Different { majority } => write!(f, "{:?} is {} in {} translation units, most use {}",
+                self.category, flag, n, majority.clone().unwrap_or("(none)".to_string()))?,
+        }
+        for uri in self.uris.iter() {
+            write!(f, "\n    {}", uri)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct FlagReport {
+    pub profiles: Vec<FlagProfile>,
+    pub deviations: Vec<Deviation>,
+}
+impl fmt::Display for FlagReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for p in self.profiles.iter() {
+            writeln!(f, "{}", p.uri)?;
+            writeln!(f, "    std: {}", p.standard.clone().unwrap_or_default())?;
+            writeln!(f, "    optimization: {}", p.optimization.clone().unwrap_or_default())?;
+            writeln!(f, "    defines: {}", p.defines.iter().cloned().collect::<Vec<String>>().join(" "))?;
+            writeln!(f, "    include paths: {}", p.include_paths.iter().cloned().collect::<Vec<String>>().join(" "))?;
+            writeln!(f, "    warnings: {}", p.warnings.iter().cloned().collect::<Vec<String>>().join(" "))?;
+        }
+        if !self.deviations.is_empty() {
+            writeln!(f)?;
+            writeln!(f, "{} inconsistent flags", self.deviations.len())?;
+        }
+        for d in self.deviations.iter() {
+            writeln!(f, "{}", d)?;
+        }
+        Ok(())
+    }
+}
+
+/// Flags of every translation unit, and where they differ from the majority, grouped by flag
+pub fn report(db: &ClangdDatabase) -> FlagReport {
+    let mut r: FlagReport = Default::default();
+    for (uri, cmd) in db.compile_commands().iter() {
+        let args = compdb::normalize_arguments(&compdb::strip_injected(&cmd.cmdl), &cmd.directory);
+        r.profiles.push(FlagProfile::from_arguments(uri, &args));
+    }
+
+    let total = r.profiles.len();
+    for c in [FlagCategory::Define, FlagCategory::IncludePath, FlagCategory::Warning] {
+        let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+        for p in r.profiles.iter() {
+            for flag in p.set(c) {
+                *counts.entry(flag).or_default() += 1;
+            }
+        }
+        for (flag, n) in counts.iter().filter(|(_, n)| **n < total) {
+            let majority = *n * 2 > total;
+            // the units without a flag most have, or with one few have
+            let uris: Vec<FileUri> = r.profiles.iter()
+                .filter(|p| p.set(c).contains(flag) != majority)
+                .map(|p| p.uri.clone())
+                .collect();
+            let kind = if majority { DeviationKind::Missing } else { DeviationKind::Extra };
+            r.deviations.push(Deviation { category: c, flag: Some(flag.clone()), kind, uris });
+        }
+    }
+
+    for c in [FlagCategory::Standard, FlagCategory::Optimization] {
+        let mut counts: BTreeMap<Option<String>, usize> = BTreeMap::new();
+        for p in r.profiles.iter() {
+            *counts.entry(p.set(c).into_iter().next()).or_default() += 1;
+        }
+        if counts.len() < 2 {
+            continue;
+        }
+        // ties go to the first value in sort order so the output is stable
+        let majority = counts.iter().max_by(|a, b| a.1.cmp(b.1).then(b.0.cmp(a.0))).unwrap().0.clone();
+        let mut others: BTreeMap<Option<String>, Vec<FileUri>> = BTreeMap::new();
+        for p in r.profiles.iter() {
+            let value = p.set(c).into_iter().next();
+            if value != majority {
+                others.entry(value).or_default().push(p.uri.clone());
+            }
+        }
+        for (flag, uris) in others {
+            r.deviations.push(Deviation { category: c, flag, kind: DeviationKind::Different { majority: majority.clone() }, uris });
+        }
+    }
+
+    r.deviations.sort_by(|a, b| (a.category, &a.flag).cmp(&(b.category, &b.flag)));
+    r
+}
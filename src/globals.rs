@@ -0,0 +1,210 @@
+//! Global state: variables with external linkage, where they are defined and declared, and the functions using them.
+
+use serde::Serialize;
+
+use std::fmt;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::clangd::ClangdDatabase;
+use crate::query::NO_CONTAINER;
+use crate::refs::RefKind;
+use crate::symbols::{Symbol, SymbolFlags, SymbolId, SymbolKind, SymbolLocation, SymbolProperty};
+use crate::tags::{Sources, byte_column};
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct GlobalVariable {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub t: String,
+    /// Empty when only declared, i.e. defined in a library
+    pub definition: Option<SymbolLocation>,
+    pub declarations: Vec<SymbolLocation>,
+    /// Qualified names of the functions reading it
+    pub readers: Vec<String>,
+    /// Qualified names of the functions assigning it, changing it or taking its address
+    pub writers: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct FunctionGlobals {
+    pub name: String,
+    pub location: SymbolLocation,
+    /// Globals it reads or writes
+    pub globals: Vec<String>,
+    /// Those it writes
+    pub writes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct GlobalsReport {
+    /// Sorted by name
+    pub globals: Vec<GlobalVariable>,
+    /// Sorted by the number of globals touched, most first
+    pub functions: Vec<FunctionGlobals>,
+}
+impl fmt::Display for GlobalsReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let at = |l: &SymbolLocation| format!("{}:{}", l.file_uri, l.start.line + 1);
+        for g in self.globals.iter() {
+            writeln!(f, "{} {}", g.t, g.name)?;
+            match g.definition.as_ref() {
+                Some(l) => writeln!(f, "    defined {}", at(l))?,
+                None => writeln!(f, "    not defined in the index")?,
+            }
+            for l in g.declarations.iter() {
+                writeln!(f, "    declared {}", at(l))?;
+            }
+            for n in g.readers.iter() {
+                writeln!(f, "    read by {}", n)?;
+            }
+            for n in g.writers.iter() {
+                writeln!(f, "    written by {}", n)?;
+            }
+        }
+        if !self.functions.is_empty() {
+            writeln!(f, "Functions by globals touched:")?;
+        }
+        for func in self.functions.iter() {
+            writeln!(f, "    {:>4} {} ({} written) {}", func.globals.len(), func.name, func.writes.len(), at(&func.location))?;
+        }
+        Ok(())
+    }
+}
+
+/// Namespace-scope variables visible to other translation units; `static` ones and locals are left out
+pub fn is_global(s: &Symbol) -> bool {
+    s.syminfo.kind == SymbolKind::Variable
+        && s.has_flag(SymbolFlags::VisibleOutsideFile)
+        && !s.has_property(SymbolProperty::Local)
+}
+
+// Keywords that end like an operand but leave a following & unary
+const UNARY_CONTEXT: [&str; 3] = ["return", "sizeof", "case"];
+
+// Whether `s` ends with the word `w`, not just with its letters
+fn ends_with_word(s: &str, w: &str) -> bool {
+    s.strip_suffix(w).is_some_and(|p| !p.ends_with(|c: char| c.is_alphanumeric() || c == '_'))
+}
+
+// Whether `s` ends with a cast, i.e. `(struct s *)`: parentheses holding only a type, that aren't a call's
+fn ends_with_cast(s: &str) -> bool {
+    let Some((callee, t)) = s.strip_suffix(')').and_then(|s| s.rsplit_once('(')) else { return false };
+    let callee = callee.trim_end();
+    let call = callee.ends_with(|c: char| c.is_alphanumeric() || matches!(c, '_' | ')' | ']'))
+        && !UNARY_CONTEXT.iter().any(|k| ends_with_word(callee, k));
+    !call && !t.trim().is_empty() && t.chars().all(|c| c.is_alphanumeric() || " _*&:<>,".contains(c))
+}
+
+/// Whether the reference between clangd columns `start` and `end` of `line` changes the variable: an assignment,
+/// increment or decrement, also through a subscript or member, or taking its address.
+/// Only the reference's own line is looked at, so a statement split across lines, e.g. `g\n    = 1;`, counts as a read,
+/// and a parenthesised name before `&`, as in `(mask) & g`, is taken for a cast.
+pub fn is_write(line: &str, start: u32, end: u32) -> bool {
+    let (start, end) = (byte_column(line, start), byte_column(line, end));
+    let before = line[..start].trim_end();
+    if before.ends_with("++") || before.ends_with("--") {
+        return true;
+    }
+    if let Some(b) = before.strip_suffix('&') {
+        // unary &, not a bitwise and or && after an operand
+        let b = b.trim_end();
+        let operand = b.ends_with(|c: char| c.is_alphanumeric() || matches!(c, '_' | ')' | ']' | '&'));
+        if !operand || UNARY_CONTEXT.iter().any(|k| ends_with_word(b, k)) || ends_with_cast(b) {
+            return true;
+        }
+    }
+
+    let mut after = line[end..].trim_start();
+    loop {
+        if after.starts_with('[') {
+            let mut depth = 0;
+            let close = after.char_indices().find(|(_, c)| {
+                match c {
+                    '[' => depth += 1,
+                    ']' => depth -= 1,
+                    _ => (),
+                }
+                depth == 0
+            });
+            let Some((i, _)) = close else { return false };
+            after = after[i + 1..].trim_start();
+        }
+        else if let Some(rest) = after.strip_prefix("->").or_else(|| after.strip_prefix('.')) {
+            let rest = rest.trim_start();
+            after = rest.trim_start_matches(|c: char| c.is_alphanumeric() || c == '_').trim_start();
+        }
+        else {
+            break;
+        }
+    }
+    if after.starts_with("++") || after.starts_with("--") {
+        return true;
+    }
+    ["=", "+=", "-=", "*=", "/=", "%=", "&=", "|=", "^=", "<<=", ">>="].iter()
+        .any(|op| after.strip_prefix(op).is_some_and(|rest| *op != "=" || !rest.starts_with('=')))
+}
+
+/// Every global variable, the functions reading and writing it, and those functions ranked by how many globals they touch
+pub fn analyze(db: &ClangdDatabase) -> GlobalsReport {
+    let mut sources: Sources = Default::default();
+    let mut report: GlobalsReport = Default::default();
+    // function -> (globals touched, globals written)
+    let mut by_function: BTreeMap<SymbolId, (BTreeSet<String>, BTreeSet<String>)> = BTreeMap::new();
+
+    let mut globals: Vec<&Symbol> = db.id.values().filter(|s| is_global(s)).collect();
+    globals.sort_by_key(|s| s.qualified_name());
+    for s in globals {
+        let mut g = GlobalVariable {
+            name: s.qualified_name(),
+            t: s.t.clone(),
+            definition: Some(s.definition.clone()).filter(|l| !l.file_uri.is_empty()),
+            ..Default::default()
+        };
+        // the canonical declaration is the definition when nothing else declares it
+        if !s.canonical_declaration.file_uri.is_empty() && g.definition.as_ref() != Some(&s.canonical_declaration) {
+            g.declarations.push(s.canonical_declaration.clone());
+        }
+
+        let mut readers: BTreeSet<String> = BTreeSet::new();
+        let mut writers: BTreeSet<String> = BTreeSet::new();
+        for r in db.references(&s.id).iter() {
+            if r.has_kind(RefKind::Declaration) && !r.has_kind(RefKind::Definition)
+                && Some(&r.location) != g.definition.as_ref() && !g.declarations.contains(&r.location) {
+                g.declarations.push(r.location.clone());
+            }
+            if !r.has_kind(RefKind::Reference) || r.container_id == NO_CONTAINER {
+                continue;
+            }
+            let Some(func) = db.id.get(&r.container_id).filter(|c| c.is_function()) else { continue };
+            let entry = by_function.entry(func.id).or_default();
+            entry.0.insert(g.name.clone());
+            // without the source every reference is a read
+            let line = sources.line(&r.location.file_uri, r.location.start.line).map(|(l, _)| l);
+            if line.is_some_and(|l| is_write(l, r.location.start.column, r.location.end.column)) {
+                writers.insert(func.qualified_name());
+                entry.1.insert(g.name.clone());
+            }
+            else {
+                readers.insert(func.qualified_name());
+            }
+        }
+        g.declarations.sort_by(|a, b| (&a.file_uri, &a.start).cmp(&(&b.file_uri, &b.start)));
+        g.readers = readers.into_iter().collect();
+        g.writers = writers.into_iter().collect();
+        report.globals.push(g);
+    }
+
+    for (id, (touched, written)) in by_function {
+        let Some(func) = db.id.get(&id) else { continue };
+        report.functions.push(FunctionGlobals {
+            name: func.qualified_name(),
+            location: func.location().clone(),
+            globals: touched.into_iter().collect(),
+            writes: written.into_iter().collect(),
+        });
+    }
+    report.functions.sort_by(|a, b| b.globals.len().cmp(&a.globals.len())
+        .then(b.writes.len().cmp(&a.writes.len()))
+        .then(a.name.cmp(&b.name)));
+    report
+}
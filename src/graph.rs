@@ -0,0 +1,296 @@
+//! Call, include and class-hierarchy graphs, written as Graphviz DOT, GraphML or Mermaid.
+//! Edges point from caller to callee, includer to included and base to derived class.
+
+use std::io::{self, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use crate::clangd::ClangdDatabase;
+use crate::rela::RelationKind;
+use crate::symbols::{SymbolId, parse_symbol_id, symbol_id_str};
+use crate::uri::FileUri;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GraphKind {
+    #[default]
+    Calls,
+    Includes,
+    Types,
+}
+impl FromStr for GraphKind {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "calls" => Ok(GraphKind::Calls),
+            "includes" => Ok(GraphKind::Includes),
+            "types" => Ok(GraphKind::Types),
+            _ => Err(format!("Unknown graph '{}', expected calls, includes or types", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphFormat {
+    Dot,
+    GraphMl,
+    Mermaid,
+}
+impl FromStr for GraphFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dot" => Ok(GraphFormat::Dot),
+            "graphml" => Ok(GraphFormat::GraphMl),
+            "mermaid" => Ok(GraphFormat::Mermaid),
+            _ => Err(format!("Unknown format '{}', expected dot, graphml or mermaid", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct GraphOptions {
+    /// Only what is reachable from this symbol (qualified name or hex id) or file
+    pub root: Option<String>,
+    /// How many edges away from the root to go; unlimited if not given
+    pub depth: Option<usize>,
+    /// Group nodes by the directory they are defined in
+    pub cluster: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Node {
+    pub label: String,
+    /// Directory relative to the project root, empty when outside it
+    pub dir: String,
+}
+
+/// Nodes by key (hex symbol id or file URI) and the edges between those keys
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Graph {
+    pub kind: GraphKind,
+    pub nodes: BTreeMap<String, Node>,
+    pub edges: BTreeSet<(String, String)>,
+    pub cluster: bool,
+}
+
+// Directory of `uri` relative to `root`, or the absolute one outside it
+fn directory(uri: &FileUri, root: &Path) -> String {
+    let path = match uri.relative_to(root).or_else(|| uri.to_path()) {
+        Some(p) => p,
+        None => return String::new(),
+    };
+    path.parent().map(|d| d.to_string_lossy().replace('\\', "/")).unwrap_or_default()
+}
+
+/// Build the `kind` graph of `db`; file labels and directories are relative to `root`.
+/// Fails when `opts.root` names nothing in the graph.
+pub fn build(db: &ClangdDatabase, kind: GraphKind, root: &Path, opts: &GraphOptions) -> Result<Graph, String> {
+    let mut g = Graph { kind, cluster: opts.cluster, ..Default::default() };
+    let sym_node = |id: &SymbolId| -> Option<(String, Node)> {
+        let s = db.id.get(id)?;
+        let node = Node { label: s.qualified_name(), dir: directory(&s.location().file_uri, root) };
+        Some((symbol_id_str(id), node))
+    };
+
+    match kind {
+        GraphKind::Calls => {
+            for (callee, refs) in db.refs.iter() {
+                if !db.id.get(callee).is_some_and(|s| s.is_function()) {
+                    continue;
+                }
+                for r in refs.iter().filter(|r| db.queries.is_call(r)) {
+                    let (Some(from), Some(to)) = (sym_node(&r.container_id), sym_node(callee)) else { continue };
+                    g.edges.insert((from.0.clone(), to.0.clone()));
+                    g.nodes.extend([from, to]);
+                }
+            }
+        },
+        GraphKind::Includes => {
+            for (from, to) in db.include_edges() {
+                for uri in [&from, &to] {
+                    let label = uri.relative_to(root).map(|p| p.to_string_lossy().replace('\\', "/")).unwrap_or(uri.path());
+                    g.nodes.insert(uri.to_string(), Node { label, dir: directory(uri, root) });
+                }
+                g.edges.insert((from.to_string(), to.to_string()));
+            }
+        },
+        GraphKind::Types => {
+            for r in db.relations().iter().filter(|r| r.predicate == RelationKind::BaseOf) {
+                let (Some(base), Some(derived)) = (sym_node(&r.subject), sym_node(&r.object)) else { continue };
+                g.edges.insert((base.0.clone(), derived.0.clone()));
+                g.nodes.extend([base, derived]);
+            }
+        },
+    }
+
+    if let Some(r) = opts.root.as_deref() {
+        let roots = roots(db, &g, kind, r);
+        if roots.is_empty() {
+            return Err(format!("No node '{}' in the {} graph", r, g.name()));
+        }
+        g.retain_reachable(&roots, opts.depth);
+    }
+    Ok(g)
+}
+
+// Node keys `root` names: a file for includes, a qualified name or hex id otherwise
+fn roots(db: &ClangdDatabase, g: &Graph, kind: GraphKind, root: &str) -> Vec<String> {
+    if kind == GraphKind::Includes {
+        let uri = match root.contains("://") {
+            true => FileUri::new(root),
+            false => FileUri::from_path(&std::path::absolute(root).unwrap_or_else(|_| root.into())),
+        };
+        return g.nodes.keys().filter(|k| FileUri::new(k) == uri).cloned().collect();
+    }
+    if let Some(id) = parse_symbol_id(root) {
+        return Some(symbol_id_str(&id)).into_iter().filter(|k| g.nodes.contains_key(k)).collect();
+    }
+    db.id.values()
+        .filter(|s| s.qualified_name() == root)
+        .map(|s| symbol_id_str(&s.id))
+        .filter(|k| g.nodes.contains_key(k))
+        .collect()
+}
+
+impl Graph {
+    /// Keep what `roots` reach within `depth` edges
+    pub fn retain_reachable(&mut self, roots: &[String], depth: Option<usize>) {
+        let mut successors: BTreeMap<&String, Vec<&String>> = BTreeMap::new();
+        for (from, to) in self.edges.iter() {
+            successors.entry(from).or_default().push(to);
+        }
+        let mut seen: BTreeMap<&String, usize> = BTreeMap::new();
+        let mut queue: VecDeque<(&String, usize)> = VecDeque::new();
+        for r in roots.iter().filter(|r| self.nodes.contains_key(*r)) {
+            seen.insert(r, 0);
+            queue.push_back((r, 0));
+        }
+        while let Some((key, d)) = queue.pop_front() {
+            if depth.is_some_and(|max| d >= max) {
+                continue;
+            }
+            for to in successors.get(key).into_iter().flatten().copied() {
+                if !seen.contains_key(to) {
+                    seen.insert(to, d + 1);
+                    queue.push_back((to, d + 1));
+                }
+            }
+        }
+        let keep: BTreeSet<String> = seen.into_keys().cloned().collect();
+        self.nodes.retain(|k, _| keep.contains(k));
+        self.edges.retain(|(from, to)| keep.contains(from) && keep.contains(to));
+    }
+
+    fn name(&self) -> &'static str {
+        match self.kind {
+            GraphKind::Calls => "calls",
+            GraphKind::Includes => "includes",
+            GraphKind::Types => "types",
+        }
+    }
+
+    // Short ids n0, n1, ... in key order, since keys are URIs or hex ids
+    fn ids(&self) -> BTreeMap<&String, String> {
+        self.nodes.keys().enumerate().map(|(i, k)| (k, format!("n{}", i))).collect()
+    }
+
+    // Node keys by directory; a single group without clustering
+    fn clusters(&self) -> BTreeMap<&str, Vec<&String>> {
+        let mut m: BTreeMap<&str, Vec<&String>> = BTreeMap::new();
+        for (k, n) in self.nodes.iter() {
+            let dir = if self.cluster { n.dir.as_str() } else { "" };
+            m.entry(dir).or_default().push(k);
+        }
+        m
+    }
+
+    pub fn write<W: Write>(&self, format: GraphFormat, out: &mut W) -> io::Result<()> {
+        match format {
+            GraphFormat::Dot => self.write_dot(out),
+            GraphFormat::GraphMl => self.write_graphml(out),
+            GraphFormat::Mermaid => self.write_mermaid(out),
+        }
+    }
+
+    pub fn write_dot<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+        let ids = self.ids();
+        writeln!(out, "digraph {} {{", self.name())?;
+        writeln!(out, "  rankdir={};", if self.kind == GraphKind::Types { "TB" } else { "LR" })?;
+        writeln!(out, "  node [shape=box];")?;
+        for (i, (dir, keys)) in self.clusters().iter().enumerate() {
+            let indent = if dir.is_empty() { "  " } else { "    " };
+            if !dir.is_empty() {
+                writeln!(out, "  subgraph cluster_{} {{", i)?;
+                writeln!(out, "    label={};", quote(dir))?;
+            }
+            for k in keys.iter() {
+                writeln!(out, "{}{} [label={}];", indent, ids[k], quote(&self.nodes[*k].label))?;
+            }
+            if !dir.is_empty() {
+                writeln!(out, "  }}")?;
+            }
+        }
+        for (from, to) in self.edges.iter() {
+            writeln!(out, "  {} -> {};", ids[from], ids[to])?;
+        }
+        writeln!(out, "}}")
+    }
+
+    /// GraphML with the label and directory as node data; clusters become nested graphs
+    pub fn write_graphml<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let escape = |s: &str| s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;");
+        let ids = self.ids();
+        writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+        writeln!(out, "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">")?;
+        writeln!(out, "  <key id=\"label\" for=\"node\" attr.name=\"label\" attr.type=\"string\"/>")?;
+        writeln!(out, "  <key id=\"dir\" for=\"node\" attr.name=\"directory\" attr.type=\"string\"/>")?;
+        writeln!(out, "  <graph id=\"{}\" edgedefault=\"directed\">", self.name())?;
+        for (i, (dir, keys)) in self.clusters().iter().enumerate() {
+            let indent = if dir.is_empty() { "    " } else { "        " };
+            if !dir.is_empty() {
+                writeln!(out, "    <node id=\"cluster_{}\">", i)?;
+                writeln!(out, "      <data key=\"label\">{}</data>", escape(dir))?;
+                writeln!(out, "      <graph id=\"cluster_{}:\" edgedefault=\"directed\">", i)?;
+            }
+            for k in keys.iter() {
+                let n = &self.nodes[*k];
+                writeln!(out, "{}<node id=\"{}\"><data key=\"label\">{}</data><data key=\"dir\">{}</data></node>",
+                    indent, ids[k], escape(&n.label), escape(&n.dir))?;
+            }
+            if !dir.is_empty() {
+                writeln!(out, "      </graph>")?;
+                writeln!(out, "    </node>")?;
+            }
+        }
+        for (from, to) in self.edges.iter() {
+            writeln!(out, "    <edge source=\"{}\" target=\"{}\"/>", ids[from], ids[to])?;
+        }
+        writeln!(out, "  </graph>")?;
+        writeln!(out, "</graphml>")
+    }
+
+    /// A Mermaid flowchart, for Markdown documents
+    pub fn write_mermaid<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let quote = |s: &str| format!("\"{}\"", s.replace('"', "#quot;"));
+        let ids = self.ids();
+        writeln!(out, "flowchart {}", if self.kind == GraphKind::Types { "TB" } else { "LR" })?;
+        for (i, (dir, keys)) in self.clusters().iter().enumerate() {
+            let indent = if dir.is_empty() { "  " } else { "    " };
+            if !dir.is_empty() {
+                writeln!(out, "  subgraph cluster_{} [{}]", i, quote(dir))?;
+            }
+            for k in keys.iter() {
+                writeln!(out, "{}{}[{}]", indent, ids[k], quote(&self.nodes[*k].label))?;
+            }
+            if !dir.is_empty() {
+                writeln!(out, "  end")?;
+            }
+        }
+        for (from, to) in self.edges.iter() {
+            writeln!(out, "  {} --> {}", ids[from], ids[to])?;
+        }
+        Ok(())
+    }
+}
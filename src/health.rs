@@ -0,0 +1,165 @@
+//! Problems with the index itself: unreadable shards and translation units that failed to compile.
+
+use std::fmt;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::clangd::{ClangdDatabase, ClangdFile, ClangdParseFailure};
+use crate::cmdl::Cmdl;
+use crate::sarif::{Finding, Level, Rule};
+use crate::srcs::{Srcs, SourceFlags};
+use crate::symbols::{SymbolId, SymbolLocation};
+use crate::uri::FileUri;
+
+pub const PARSE_FAILURE_RULE: Rule = Rule {
+    id: "parse-failure",
+    description: "Index file could not be parsed",
+};
+
+pub const INDEX_ERROR_RULE: Rule = Rule {
+    id: "index-error",
+    description: "Translation unit had errors when clangd indexed it",
+};
+
+/// Every source clangd recorded as having compile errors, deduplicated by URI
+pub fn sources_with_errors(db: &ClangdDatabase) -> Vec<&Srcs> {
+    let mut v: Vec<&Srcs> = db.file.values()
+        .flat_map(|f| f.sources.data.iter())
+        .filter(|s| s.has_flag(SourceFlags::HadErrors))
+        .collect();
+    v.sort_by(|a, b| a.uri.cmp(&b.uri));
+    v.dedup_by(|a, b| a.uri == b.uri);
+    v
+}
+
+pub fn findings(db: &ClangdDatabase) -> Vec<Finding> {
+    let mut findings: Vec<Finding> = db.failures.iter()
+        .map(|f| Finding {
+            rule: PARSE_FAILURE_RULE.id,
+            level: Level::Error,
+            message: format!("Unable to parse index file: {}", f.error),
+            location: SymbolLocation { file_uri: FileUri::from_path(&f.path), ..Default::default() },
+        })
+        .collect();
+    for s in sources_with_errors(db) {
+        findings.push(Finding {
+            rule: INDEX_ERROR_RULE.id,
+            level: Level::Warning,
+            message: "clangd hit errors compiling this file, its symbols may be incomplete".to_string(),
+            location: SymbolLocation { file_uri: s.uri.clone(), ..Default::default() },
+        });
+    }
+    findings
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BrokenUnit {
+    pub uri: FileUri,
+    pub shard: String,
+    // how clangd compiled the file, if it was recorded
+    pub command: Option<Cmdl>,
+    // from its own shard and those of the headers it includes that clangd also marked as having errors
+    pub symbols: usize,
+    // symbols not seen in any shard that was indexed cleanly
+    pub only_here: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct HealthReport {
+    pub units: usize,
+    pub broken: Vec<BrokenUnit>,
+    pub failures: Vec<ClangdParseFailure>,
+    pub total_symbols: usize,
+    pub affected_symbols: usize,
+}
+impl HealthReport {
+    /// Fraction of the symbol table that only comes from translation units with errors
+    pub fn incomplete_ratio(&self) -> f32 {
+        if self.total_symbols == 0 {
+            return 0.0;
+        }
+        self.affected_symbols as f32 / self.total_symbols as f32
+    }
+}
+impl fmt::Display for HealthReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} translation units, {} indexed with errors", self.units, self.broken.len())?;
+        for b in self.broken.iter() {
+            writeln!(f, "{}", b.uri)?;
+            writeln!(f, "    symbols: {} ({} not seen elsewhere)", b.symbols, b.only_here)?;
+            match &b.command {
+                Some(c) => {
+                    writeln!(f, "    directory: {}", c.directory)?;
+                    writeln!(f, "    command: {}", c.cmdl.join(" "))?;
+                },
+                None => writeln!(f, "    command: unknown")?,
+            }
+        }
+        for e in self.failures.iter() {
+            writeln!(f, "unreadable: {} ({})", e.path.display(), e.error)?;
+        }
+        writeln!(f, "{} of {} symbols ({:.1}%) may be incomplete",
+            self.affected_symbols, self.total_symbols, self.incomplete_ratio() * 100.0)
+    }
+}
+
+// Whether clangd hit errors in the translation unit the shard came from; header shards carry the flag too
+fn had_errors(file: &ClangdFile) -> bool {
+    file.sources.data.iter().any(|s| s.has_flag(SourceFlags::HadErrors))
+}
+
+/// Translation units clangd indexed with errors, and how much of the symbol table they may be hiding.
+/// A header shard only says it had errors, not in which unit, so a broken unit is blamed for every header it
+/// includes, directly or not, that has them.
+pub fn report(db: &ClangdDatabase) -> HealthReport {
+    let mut r: HealthReport = Default::default();
+    let healthy: BTreeSet<SymbolId> = db.file.values()
+        .filter(|f| !had_errors(f))
+        .flat_map(|f| f.symbols.data.iter().map(|s| s.id))
+        .collect();
+    let mut includes: BTreeMap<FileUri, Vec<FileUri>> = BTreeMap::new();
+    for (from, to) in db.include_edges() {
+        includes.entry(from).or_default().push(to);
+    }
+
+    for (shard, file) in db.file.iter() {
+        let tu = match file.sources.data.iter().find(|s| s.has_flag(SourceFlags::IsTU)) {
+            Some(tu) => tu,
+            None => continue,
+        };
+        r.units += 1;
+        if !tu.has_flag(SourceFlags::HadErrors) {
+            continue;
+        }
+        let mut symbols: BTreeSet<SymbolId> = file.symbols.data.iter().map(|s| s.id).collect();
+        let mut seen: BTreeSet<&FileUri> = BTreeSet::from([&tu.uri]);
+        let mut queue: Vec<&FileUri> = vec![&tu.uri];
+        while let Some(uri) = queue.pop() {
+            for header in includes.get(uri).into_iter().flatten() {
+                if !seen.insert(header) {
+                    continue;
+                }
+                if let Some(f) = db.file_of(header).filter(|f| had_errors(f)) {
+                    symbols.extend(f.symbols.data.iter().map(|s| s.id));
+                }
+                queue.push(header);
+            }
+        }
+        r.broken.push(BrokenUnit {
+            uri: tu.uri.clone(),
+            shard: shard.clone(),
+            command: file.cmdline.data.first().cloned(),
+            symbols: symbols.len(),
+            only_here: symbols.iter().filter(|id| !healthy.contains(*id)).count(),
+        });
+    }
+
+    let affected: BTreeSet<SymbolId> = db.file.values()
+        .filter(|f| had_errors(f))
+        .flat_map(|f| f.symbols.data.iter().map(|s| s.id))
+        .filter(|id| !healthy.contains(id))
+        .collect();
+    r.affected_symbols = affected.len();
+    r.total_symbols = db.id.len();
+    r.failures = db.failures.clone();
+    r
+}
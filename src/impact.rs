@@ -0,0 +1,187 @@
+//! Test-impact analysis: from changed lines to the tests and test translation units that may be affected.
+
+use serde::Serialize;
+
+use std::fmt;
+use std::path::Path;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use crate::clangd::ClangdDatabase;
+use crate::includes::include_graph;
+use crate::query::NO_CONTAINER;
+use crate::symbols::{Symbol, SymbolId, SymbolProperty};
+use crate::uri::FileUri;
+
+/// Names of test functions when clangd doesn't mark them, matched against the name or qualified name
+pub const DEFAULT_TEST_PATTERNS: [&str; 4] = ["test*", "*_test", "*Test", "*::TestBody"];
+
+/// Lines `start` to `end` of `file`, 1-based and inclusive
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ChangedRange {
+    pub file: FileUri,
+    pub start: u32,
+    pub end: u32,
+}
+impl ChangedRange {
+    /// Parse `file:line` or `file:start-end`; relative files are resolved against `root`
+    pub fn parse(s: &str, root: &Path) -> Result<ChangedRange, String> {
+        let err = || format!("Invalid range '{}', expected file:line or file:start-end", s);
+        let (file, lines) = s.rsplit_once(':').ok_or_else(err)?;
+        let (start, end) = lines.split_once('-').unwrap_or((lines, lines));
+        let (start, end) = (start.parse().map_err(|_| err())?, end.parse().map_err(|_| err())?);
+        Ok(ChangedRange { file: FileUri::from_path(&root.join(file)), start, end })
+    }
+
+    fn contains(&self, line: u32) -> bool {
+        self.start <= line && line <= self.end
+    }
+}
+
+/// Ranges on the new side of a unified diff, i.e. from `git diff -U0`; paths are relative to `root`.
+/// A hunk only deleting lines becomes the line after the deletion, which `-U0` numbers as the line before it.
+pub fn parse_unified_diff(diff: &str, root: &Path) -> Vec<ChangedRange> {
+    let mut ranges: Vec<ChangedRange> = vec![];
+    let mut file: Option<FileUri> = None;
+    for line in diff.lines() {
+        if let Some(path) = line.strip_prefix("+++ ") {
+            let path = path.split('\t').next().unwrap_or(path).trim();
+            file = match path {
+                "/dev/null" => None,
+                p => Some(FileUri::from_path(&root.join(p.strip_prefix("b/").unwrap_or(p)))),
+            };
+        }
+        else if let (Some(hunk), Some(f)) = (line.strip_prefix("@@ "), file.as_ref()) {
+            // @@ -old,count +new,count @@
+            let Some(new) = hunk.split_whitespace().find_map(|w| w.strip_prefix('+')) else { continue };
+            let (start, count) = new.split_once(',').unwrap_or((new, "1"));
+            let (Ok(start), Ok(count)) = (start.parse::<u32>(), count.parse::<u32>()) else { continue };
+            let (start, end) = match count {
+                0 => (start + 1, start + 1),
+                n => (start, start + n - 1),
+            };
+            ranges.push(ChangedRange { file: f.clone(), start, end });
+        }
+    }
+    ranges
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ImpactReport {
+    /// Qualified names of the symbols the changes touch
+    pub changed: Vec<String>,
+    /// Test functions reaching a changed symbol
+    pub tests: Vec<String>,
+    /// Files defining affected tests, or including a changed header
+    pub test_units: Vec<FileUri>,
+}
+impl fmt::Display for ImpactReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Changed:")?;
+        for s in self.changed.iter() {
+            writeln!(f, "    {}", s)?;
+        }
+        writeln!(f, "Tests:")?;
+        for s in self.tests.iter() {
+            writeln!(f, "    {}", s)?;
+        }
+        writeln!(f, "Test units:")?;
+        for u in self.test_units.iter() {
+            writeln!(f, "    {}", u)?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether clangd marks `s` as a unit test, or its name matches one of `patterns`
+pub fn is_test(s: &Symbol, patterns: &[String]) -> bool {
+    if s.has_property(SymbolProperty::UnitTest) {
+        return true;
+    }
+    s.is_function() && patterns.iter().any(|p| crate::wildcard_match(p, &s.name) || crate::wildcard_match(p, &s.qualified_name()))
+}
+
+/// Symbols the ranges touch: containers of refs in them, definitions on them,
+/// and failing both, the closest definition above, since the index only has name positions
+pub fn changed_symbols(db: &ClangdDatabase, ranges: &[ChangedRange]) -> BTreeSet<SymbolId> {
+    let mut changed: BTreeSet<SymbolId> = BTreeSet::new();
+    for range in ranges.iter() {
+        let before = changed.len();
+        for r in db.refs.values().flatten() {
+            if r.location.file_uri == range.file && range.contains(r.location.start.line + 1) && r.container_id != NO_CONTAINER {
+                changed.insert(r.container_id);
+            }
+        }
+        let defs: Vec<&Symbol> = db.id.values().filter(|s| s.definition.file_uri == range.file).collect();
+        for s in defs.iter().filter(|s| range.contains(s.definition.start.line + 1)) {
+            changed.insert(s.id);
+        }
+        if changed.len() == before {
+            let above = defs.iter()
+                .filter(|s| s.definition.start.line < range.start)
+                .max_by_key(|s| (s.definition.start.line, s.definition.start.column));
+            if let Some(s) = above {
+                changed.insert(s.id);
+            }
+        }
+    }
+    changed
+}
+
+/// Tests affected by `ranges`: reverse references from the changed symbols, plus the reverse include graph of changed files
+pub fn analyze(db: &ClangdDatabase, ranges: &[ChangedRange], patterns: &[String]) -> ImpactReport {
+    // symbol -> symbols whose code refers to it
+    let mut users: BTreeMap<SymbolId, BTreeSet<SymbolId>> = BTreeMap::new();
+    for (id, refs) in db.refs.iter() {
+        for r in refs.iter().filter(|r| r.container_id != NO_CONTAINER && r.container_id != *id) {
+            users.entry(*id).or_default().insert(r.container_id);
+        }
+    }
+
+    let changed = changed_symbols(db, ranges);
+    let mut affected: BTreeSet<SymbolId> = changed.clone();
+    let mut queue: VecDeque<SymbolId> = changed.iter().copied().collect();
+    while let Some(id) = queue.pop_front() {
+        for u in users.get(&id).into_iter().flatten() {
+            if affected.insert(*u) {
+                queue.push_back(*u);
+            }
+        }
+    }
+
+    let tests: Vec<&Symbol> = affected.iter()
+        .filter_map(|id| db.id.get(id))
+        .filter(|s| is_test(s, patterns))
+        .collect();
+    let mut units: BTreeSet<FileUri> = tests.iter().map(|s| s.location().file_uri.clone()).collect();
+
+    // every file including a changed one, directly or not, is rebuilt with it
+    let mut includers: BTreeMap<FileUri, Vec<FileUri>> = BTreeMap::new();
+    for (from, tos) in include_graph(db) {
+        for to in tos {
+            includers.entry(to).or_default().push(from.clone());
+        }
+    }
+    let mut touched: BTreeSet<FileUri> = ranges.iter().map(|r| r.file.clone()).collect();
+    let mut queue: VecDeque<FileUri> = touched.iter().cloned().collect();
+    while let Some(f) = queue.pop_front() {
+        for i in includers.get(&f).into_iter().flatten() {
+            if touched.insert(i.clone()) {
+                queue.push_back(i.clone());
+            }
+        }
+    }
+    let test_files: BTreeSet<&FileUri> = db.id.values()
+        .filter(|s| is_test(s, patterns))
+        .map(|s| &s.location().file_uri)
+        .collect();
+    units.extend(touched.into_iter().filter(|f| test_files.contains(f)));
+
+    let mut report = ImpactReport {
+        changed: changed.iter().filter_map(|id| db.id.get(id)).map(|s| s.qualified_name()).collect(),
+        tests: tests.iter().map(|s| s.qualified_name()).collect(),
+        test_units: units.into_iter().collect(),
+    };
+    report.changed.sort();
+    report.tests.sort();
+    report
+}
@@ -0,0 +1,134 @@
+//! Analyses over the include graph recorded in each file's sources.
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use crate::clangd::ClangdDatabase;
+use crate::sarif::{Finding, Level, Rule};
+use crate::symbols::SymbolLocation;
+use crate::uri::FileUri;
+
+pub const INCLUDE_CYCLE_RULE: Rule = Rule {
+    id: "include-cycle",
+    description: "Files include each other in a cycle",
+};
+
+pub type IncludeGraph = BTreeMap<FileUri, BTreeSet<FileUri>>;
+
+/// Includer URI -> included URIs
+pub fn include_graph(db: &ClangdDatabase) -> IncludeGraph {
+    let mut g: IncludeGraph = BTreeMap::new();
+    for (from, to) in db.include_edges() {
+        g.entry(from).or_default().insert(to);
+    }
+    g
+}
+
+/// One cycle per strongly connected component of the include graph, starting and ending at the same file
+pub fn include_cycles(db: &ClangdDatabase) -> Vec<Vec<FileUri>> {
+    let g = include_graph(db);
+    strongly_connected(&g).iter()
+        .filter_map(|scc| {
+            let start = scc.iter().next().unwrap();
+            shortest_cycle(&g, start, scc)
+        })
+        .collect()
+}
+
+pub fn findings(db: &ClangdDatabase) -> Vec<Finding> {
+    include_cycles(db).into_iter()
+        .map(|cycle| Finding {
+            rule: INCLUDE_CYCLE_RULE.id,
+            level: Level::Warning,
+            message: format!("Include cycle: {}", cycle.iter().map(|u| u.to_string()).collect::<Vec<String>>().join(" -> ")),
+            location: SymbolLocation { file_uri: cycle[0].clone(), ..Default::default() },
+        })
+        .collect()
+}
+
+// Tarjan's algorithm, iterative so deep include chains can't overflow the stack
+fn strongly_connected(g: &IncludeGraph) -> Vec<BTreeSet<FileUri>> {
+    let nodes: BTreeSet<&FileUri> = g.keys().chain(g.values().flatten()).collect();
+    let mut index: BTreeMap<&FileUri, usize> = BTreeMap::new();
+    let mut low: BTreeMap<&FileUri, usize> = BTreeMap::new();
+    let mut on_stack: BTreeSet<&FileUri> = BTreeSet::new();
+    let mut stack: Vec<&FileUri> = vec![];
+    let mut sccs: Vec<BTreeSet<FileUri>> = vec![];
+    let empty: BTreeSet<FileUri> = BTreeSet::new();
+
+    for root in nodes {
+        if index.contains_key(root) {
+            continue;
+        }
+        let mut work: Vec<(&FileUri, Vec<&FileUri>)> = vec![];
+        let next = index.len();
+        index.insert(root, next);
+        low.insert(root, next);
+        stack.push(root);
+        on_stack.insert(root);
+        work.push((root, g.get(root).unwrap_or(&empty).iter().collect()));
+
+        while let Some((v, mut succ)) = work.pop() {
+            if let Some(w) = succ.pop() {
+                work.push((v, succ));
+                if !index.contains_key(w) {
+                    let next = index.len();
+                    index.insert(w, next);
+                    low.insert(w, next);
+                    stack.push(w);
+                    on_stack.insert(w);
+                    work.push((w, g.get(w).unwrap_or(&empty).iter().collect()));
+                }
+                else if on_stack.contains(w) {
+                    let l = low[v].min(index[w]);
+                    low.insert(v, l);
+                }
+                continue;
+            }
+            // v is done, propagate to its parent
+            if let Some((parent, _)) = work.last() {
+                let l = low[parent].min(low[v]);
+                low.insert(parent, l);
+            }
+            if low[v] == index[v] {
+                let mut scc: BTreeSet<FileUri> = BTreeSet::new();
+                while let Some(w) = stack.pop() {
+                    on_stack.remove(w);
+                    scc.insert(w.clone());
+                    if w == v {
+                        break;
+                    }
+                }
+                let self_loop = g.get(v).map(|s| s.contains(v)).unwrap_or(false);
+                if scc.len() > 1 || self_loop {
+                    sccs.push(scc);
+                }
+            }
+        }
+    }
+    sccs
+}
+
+fn shortest_cycle(g: &IncludeGraph, start: &FileUri, within: &BTreeSet<FileUri>) -> Option<Vec<FileUri>> {
+    let mut prev: BTreeMap<&FileUri, &FileUri> = BTreeMap::new();
+    let mut queue: VecDeque<&FileUri> = VecDeque::from([start]);
+    while let Some(v) = queue.pop_front() {
+        for w in g.get(v).into_iter().flatten().filter(|w| within.contains(*w)) {
+            if w == start {
+                let mut path: Vec<FileUri> = vec![start.clone()];
+                let mut cur = v;
+                while cur != start {
+                    path.push(cur.clone());
+                    cur = prev[cur];
+                }
+                path.push(start.clone());
+                path.reverse();
+                return Some(path);
+            }
+            if !prev.contains_key(w) {
+                prev.insert(w, v);
+                queue.push_back(w);
+            }
+        }
+    }
+    None
+}
@@ -7,17 +7,91 @@ pub mod rela;
 pub mod refs;
 pub mod srcs;
 pub mod cmdl;
+pub mod watch;
+pub mod diff;
+pub mod compat;
+pub mod sarif;
+pub mod dead;
+pub mod includes;
+pub mod health;
+pub mod compdb;
+pub mod flags;
+pub mod paths;
+pub mod uri;
+pub mod lookup;
+pub mod outline;
+pub mod query;
+pub mod lsp;
+pub mod serve;
+pub mod remote;
+pub mod lsif;
+pub mod scip;
+pub mod tags;
+pub mod graph;
+pub mod testgen;
+pub mod mocks;
+pub mod link;
+pub mod impact;
+pub mod globals;
 
 use async_std::task;
 
 use std::fs;
-use std::path::PathBuf;
-use std::collections::BTreeMap;
-use crate::symbols::SymbolKind;
+use std::path::{Path, PathBuf};
+use std::collections::{BTreeMap, BTreeSet};
+use crate::paths::PathMapping;
 
 /// Given a root directory containing .cache/index, parse the IDX files
-pub fn run(p: &PathBuf) -> clangd::ClangdDatabase {
-    let mut db = task::block_on(_run(p));
+pub fn run(p: &Path) -> clangd::ClangdDatabase {
+    run_with_mappings(p, &[])
+}
+
+/// Same as `run`, rewriting every recorded path with `mappings` as the IDX files are loaded
+pub fn run_with_mappings(p: &Path, mappings: &[PathMapping]) -> clangd::ClangdDatabase {
+    match load(p, mappings) {
+        Ok(db) => db,
+        Err(e) => panic!("{}", e),
+    }
+}
+
+/// Same as `run_with_mappings`, but a missing index is an error rather than a panic.
+/// IDX files that fail to parse are listed in `failures` and otherwise skipped.
+pub fn load(p: &Path, mappings: &[PathMapping]) -> Result<clangd::ClangdDatabase, clangd::LoadError> {
+    task::block_on(_run(p, mappings))
+}
+
+/// Merge parsed IDX files, keyed by file name, into a database
+pub fn build(shards: clangd::ClangdFileMap, failures: Vec<clangd::ClangdParseFailure>) -> clangd::ClangdDatabase {
+    let mut to_id: clangd::ClangdIdMap = BTreeMap::new();
+    let mut to_name: clangd::ClangdNameMap = BTreeMap::new();
+    let mut to_refs: clangd::ClangdRefMap = BTreeMap::new();
+    // shards repeat the refs from the headers they share
+    let mut seen_refs: BTreeSet<(symbols::SymbolId, &symbols::SymbolLocation, refs::RefKindSet, symbols::SymbolId)> = BTreeSet::new();
+
+    // shards are merged in name order, so which copy of a symbol comes first doesn't depend on the file system
+    for db in shards.values() {
+        for sym in db.symbols.data.iter() {
+            to_id.entry(sym.id).and_modify(|s| s.merge(sym)).or_insert_with(|| sym.clone());
+            to_name.entry(sym.name.clone()).or_insert_with(|| sym.clone());
+        }
+        for r in db.references.data.iter() {
+            let v = to_refs.entry(r.id).or_default();
+            for rr in r.refs.iter() {
+                if seen_refs.insert((r.id, &rr.location, rr.kind, rr.container_id)) {
+                    v.push(rr.clone());
+                }
+            }
+        }
+    }
+
+    symbols::derive_properties(&mut to_id);
+    for sym in to_name.values_mut() {
+        *sym = to_id[&sym.id].clone();
+    }
+    let positions = lookup::PositionIndex::build(&to_id, &to_refs);
+    let queries = query::QueryIndex::build(&shards, &to_id, &to_refs);
+    #[allow(unused_mut)]
+    let mut db = clangd::ClangdDatabase{ file: shards, id: to_id, name: to_name, refs: to_refs, failures, positions, queries };
     #[cfg(feature="post-process")]
     post_process(&mut db);
     db
@@ -25,70 +99,78 @@ pub fn run(p: &PathBuf) -> clangd::ClangdDatabase {
 
 #[cfg(feature="post-process")]
 fn post_process(db: &mut clangd::ClangdDatabase) {
-    // for each name
-    for entry in db.name.iter() {
-        let sym = entry.1;
-
-        match sym.syminfo.kind {
-            // For variables, check if declared in an H file.
-            // If so, map id in corresponding file entry
-            SymbolKind::Variable => {
-                let decl_file = sym.canonical_declaration.file_uri.rsplit_once("/");
-                if decl_file.is_none() {
-                    debug_assert!(false);
-                }
-                let decl_file = decl_file.unwrap().1;
-                if decl_file.ends_with(".h") {
-                    let hfile = db.file.get_mut(&decl_file.to_string());
-                    if hfile.is_none() {
-                        debug_assert!(false);
-                    }
-                    let hfile = hfile.unwrap();
-                    hfile.variable_declarations.push(sym.id.clone());
-                }
-            },
-            _ => ()
+    // Map each global declared in a header to that header's shard
+    let globals: Vec<(symbols::SymbolId, uri::FileUri)> = db.id.values()
+        .filter(|s| globals::is_global(s) && s.canonical_declaration.file_uri.is_header())
+        .map(|s| (s.id, s.canonical_declaration.file_uri.clone()))
+        .collect();
+    for (id, decl) in globals {
+        let Some(hfile) = db.file_of_mut(&decl) else { continue };
+        if !hfile.variable_declarations.contains(&id) {
+            hfile.variable_declarations.push(id);
         }
     }
 }
 
-async fn _run(p: &PathBuf) -> clangd::ClangdDatabase {
-    let mut to_file: clangd::ClangdFileMap = BTreeMap::new();
-    let mut to_id: clangd::ClangdIdMap = BTreeMap::new();
-    let mut to_name: clangd::ClangdNameMap = BTreeMap::new();
+/// Given a root directory, locate .cache/clangd/index
+pub fn index_path(p: &Path) -> Result<PathBuf, clangd::LoadError> {
     let mut path = p.join(".cache");
     if !path.exists() {
-        panic!("Unable to find .cache!");
+        return Err(clangd::LoadError::NoCache(p.to_path_buf()));
     }
     path = path.join("clangd").join("index");
     if !path.exists() {
-        panic!("Has clangd been run?");
+        return Err(clangd::LoadError::NoIndex(path));
     }
+    Ok(path)
+}
 
-    let rd = fs::read_dir(path.as_path()).unwrap();
-    for entry in rd {
-        let e = entry.unwrap();
-        if e.file_type().unwrap().is_file() {
-            let _ret = clangd::ClangdFile::parse(e.path()).await;
-            if _ret.is_ok() {
-                let fname = e.file_name().to_str().unwrap().to_string();
-                let parts: Vec<&str> = fname.split(".").collect();
-                let fname = format!("{}.{}", parts[0], parts[1]);
-                let db = _ret.unwrap();
-                if !to_file.contains_key(&fname) {
-                    let _ = to_file.insert(fname, db.clone());
-                }
-                for sym in db.symbols.data.iter() {
-                    if !to_id.contains_key(&sym.id) {
-                        let _ = to_id.insert(sym.id.clone(), sym.clone());
-                    }
-                    if !to_name.contains_key(&sym.name) {
-                        let _ = to_name.insert(sym.name.clone(), sym.clone());
-                    }
-                }
-            }
+/// Strip the hash and extension from an IDX file name, i.e. foo.pb.cc.0123ABCD.idx -> foo.pb.cc;
+/// None if it isn't one
+pub fn shard_name(fname: &str) -> Option<&str> {
+    let (rest, _) = fname.strip_suffix(".idx")?.rsplit_once('.')?;
+    Some(rest).filter(|r| !r.is_empty())
+}
+
+/// Match `s` against a pattern where `*` matches any run of characters
+pub fn wildcard_match(pattern: &str, s: &str) -> bool {
+    let parts: Vec<&str> = pattern.split('*').collect();
+    if parts.len() == 1 {
+        return pattern == s;
+    }
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+    if !s.starts_with(first) || !s[first.len()..].ends_with(last) {
+        return false;
+    }
+    let mut rest = &s[first.len()..s.len() - last.len()];
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+async fn _run(p: &Path, mappings: &[PathMapping]) -> Result<clangd::ClangdDatabase, clangd::LoadError> {
+    let mut to_file: clangd::ClangdFileMap = BTreeMap::new();
+    let mut failures: Vec<clangd::ClangdParseFailure> = vec![];
+    let path = index_path(p)?;
+
+    let rd = fs::read_dir(path.as_path()).map_err(clangd::LoadError::CannotReadIndex)?;
+    for e in rd.flatten() {
+        if e.file_type().is_ok_and(|t| t.is_file()) {
+            let mut db = match clangd::ClangdFile::parse(e.path()).await {
+                Ok(db) => db,
+                Err(err) => {
+                    failures.push(clangd::ClangdParseFailure { path: e.path(), error: format!("{:?}", err) });
+                    continue;
+                },
+            };
+            db.remap_paths(mappings);
+            to_file.insert(e.file_name().to_string_lossy().to_string(), db);
         }
     }
 
-    clangd::ClangdDatabase{ file: to_file, id: to_id, name: to_name }
+    Ok(build(to_file, failures))
 }
@@ -0,0 +1,97 @@
+//! Link seams: which other translation units a test needs linked in, following references to their definitions.
+
+use std::fmt;
+use std::path::Path;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use crate::clangd::ClangdDatabase;
+use crate::refs::RefKind;
+use crate::symbols::{Symbol, SymbolId, SymbolKind};
+use crate::uri::FileUri;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LinkReport {
+    pub test: FileUri,
+    // source URI -> qualified names of the definitions needed from it
+    pub sources: BTreeMap<FileUri, Vec<String>>,
+    // names used but defined nowhere in the index, i.e. from libraries
+    pub unresolved: Vec<String>,
+}
+impl LinkReport {
+    /// An add_executable() for the test and the sources it needs, with paths relative to `root`
+    pub fn cmake(&self, root: &Path) -> String {
+        let rel = |u: &FileUri| u.relative_to(root).map(|p| p.to_string_lossy().replace('\\', "/")).unwrap_or(u.path());
+        let target = self.test.file_name().unwrap_or_default();
+        let target = target.rsplit_once('.').map_or(target.as_str(), |(stem, _)| stem);
+        let mut out = format!("add_executable({}\n    {}\n", target, rel(&self.test));
+        for uri in self.sources.keys() {
+            out.push_str(&format!("    {}\n", rel(uri)));
+        }
+        out.push_str(")\n");
+        out
+    }
+}
+impl fmt::Display for LinkReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (uri, names) in self.sources.iter() {
+            writeln!(f, "{}", uri)?;
+            for n in names.iter() {
+                writeln!(f, "    {}", n)?;
+            }
+        }
+        if !self.unresolved.is_empty() {
+            writeln!(f, "Not defined in the index:")?;
+            for n in self.unresolved.iter() {
+                writeln!(f, "    {}", n)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Only functions and variables need a definition at link time
+fn needs_definition(s: &Symbol) -> bool {
+    s.is_function() || s.syminfo.kind == SymbolKind::Variable
+}
+
+/// Sources whose definitions `test` transitively needs.
+/// Inline code in headers is followed too, at file granularity, so the set can be larger than strictly required.
+pub fn link_set(db: &ClangdDatabase, test: &FileUri) -> LinkReport {
+    // file -> symbols referenced from it
+    let mut used: BTreeMap<&FileUri, BTreeSet<SymbolId>> = BTreeMap::new();
+    for (id, refs) in db.refs.iter() {
+        for r in refs.iter().filter(|r| r.has_kind(RefKind::Reference)) {
+            used.entry(&r.location.file_uri).or_default().insert(*id);
+        }
+    }
+
+    let mut report = LinkReport { test: test.clone(), ..Default::default() };
+    let mut unresolved: BTreeSet<String> = BTreeSet::new();
+    let mut seen: BTreeSet<&FileUri> = BTreeSet::from([test]);
+    let mut queue: VecDeque<&FileUri> = VecDeque::from([test]);
+    while let Some(file) = queue.pop_front() {
+        for id in used.get(file).into_iter().flatten() {
+            let Some(s) = db.id.get(id).filter(|s| needs_definition(s)) else { continue };
+            let def = &s.definition.file_uri;
+            if def.is_empty() {
+                unresolved.insert(s.qualified_name());
+                continue;
+            }
+            // the test's own definitions and those a source keeps to itself
+            if def == test || def == file {
+                continue;
+            }
+            if !def.is_header() {
+                let names = report.sources.entry(def.clone()).or_default();
+                if !names.contains(&s.qualified_name()) {
+                    names.push(s.qualified_name());
+                }
+            }
+            if seen.insert(def) {
+                queue.push_back(def);
+            }
+        }
+    }
+    report.unresolved = unresolved.into_iter().collect();
+    report
+}
@@ -0,0 +1,105 @@
+//! Find the symbol at a position in a source file, like clangd's go to definition.
+
+use std::path::Path;
+use std::collections::BTreeMap;
+
+use crate::clangd::{ClangdDatabase, ClangdIdMap, ClangdRefMap};
+use crate::symbols::{Symbol, SymbolId, SymbolLocation, SymbolPosition};
+use crate::uri::FileUri;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord)]
+struct Interval {
+    start: SymbolPosition,
+    end: SymbolPosition,
+    id: SymbolId,
+}
+
+#[derive(Debug, Clone, Default)]
+struct FileIntervals {
+    // sorted by start
+    intervals: Vec<Interval>,
+    // largest end among intervals[..=i], so a lookup knows when to stop looking back
+    max_end: Vec<SymbolPosition>,
+}
+
+/// Definition, declaration and reference ranges of every symbol, per file
+#[derive(Debug, Clone, Default)]
+pub struct PositionIndex {
+    files: BTreeMap<FileUri, FileIntervals>,
+}
+
+impl PositionIndex {
+    pub fn build(id: &ClangdIdMap, refs: &ClangdRefMap) -> Self {
+        let mut m: BTreeMap<FileUri, Vec<Interval>> = BTreeMap::new();
+        let mut add = |loc: &SymbolLocation, id: &SymbolId| {
+            if loc.file_uri.is_empty() {
+                return;
+            }
+            m.entry(loc.file_uri.clone()).or_default().push(Interval {
+                start: loc.start.clone(),
+                end: loc.end.clone(),
+                id: *id,
+            });
+        };
+        for sym in id.values() {
+            add(&sym.definition, &sym.id);
+            add(&sym.canonical_declaration, &sym.id);
+        }
+        for (id, rr) in refs.iter() {
+            for r in rr.iter() {
+                add(&r.location, id);
+            }
+        }
+
+        let mut index: PositionIndex = Default::default();
+        for (uri, mut intervals) in m {
+            // the definition is usually also a reference
+            intervals.sort();
+            intervals.dedup();
+            let mut max_end: Vec<SymbolPosition> = Vec::with_capacity(intervals.len());
+            for iv in intervals.iter() {
+                let end = match max_end.last() {
+                    Some(e) if *e > iv.end => e.clone(),
+                    _ => iv.end.clone(),
+                };
+                max_end.push(end);
+            }
+            index.files.insert(uri, FileIntervals { intervals, max_end });
+        }
+        index
+    }
+
+    /// Symbol whose range contains `pos`, the innermost one if several do
+    pub fn at(&self, uri: &FileUri, pos: &SymbolPosition) -> Option<SymbolId> {
+        let f = self.files.get(uri)?;
+        let n = f.intervals.partition_point(|iv| iv.start <= *pos);
+        let mut best: Option<&Interval> = None;
+        for i in (0..n).rev() {
+            if f.max_end[i] < *pos {
+                break;
+            }
+            let iv = &f.intervals[i];
+            // the end is inclusive so a cursor just after a name still finds it
+            if iv.end < *pos {
+                continue;
+            }
+            // later start first, then earlier end
+            if best.is_none_or(|b| iv.start > b.start || (iv.start == b.start && iv.end < b.end)) {
+                best = Some(iv);
+            }
+        }
+        best.map(|iv| iv.id)
+    }
+}
+
+impl ClangdDatabase {
+    /// Symbol defined, declared or referenced at the 0-based `line` and `column` of `path`
+    pub fn symbol_at(&self, path: &Path, line: u32, column: u32) -> Option<&Symbol> {
+        self.symbol_at_uri(&FileUri::from_path(path), line, column)
+    }
+
+    pub fn symbol_at_uri(&self, uri: &FileUri, line: u32, column: u32) -> Option<&Symbol> {
+        let id = self.positions.at(uri, &SymbolPosition { line, column })?;
+        self.id.get(&id)
+    }
+}
@@ -0,0 +1,174 @@
+//! Export the index as an LSIF dump (JSON lines), for code-intelligence platforms.
+
+use serde_json::{json, Value};
+
+use std::io::{self, Write};
+use std::path::Path;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::clangd::ClangdDatabase;
+use crate::refs::RefKind;
+use crate::symbols::{Symbol, SymbolId, SymbolKind, SymbolLocation, SymbolPosition};
+use crate::uri::FileUri;
+
+pub const LSIF_VERSION: &str = "0.5.0";
+
+// Writes vertices and edges, numbering them as it goes
+struct Emitter<'a, W: Write> {
+    out: &'a mut W,
+    next: u64,
+}
+impl<W: Write> Emitter<'_, W> {
+    fn vertex(&mut self, label: &str, mut v: Value) -> io::Result<u64> {
+        self.next += 1;
+        v["id"] = json!(self.next);
+        v["type"] = json!("vertex");
+        v["label"] = json!(label);
+        writeln!(self.out, "{}", v)?;
+        Ok(self.next)
+    }
+
+    // one to one edges, i.e. next and textDocument/*
+    fn edge(&mut self, label: &str, out_v: u64, in_v: u64) -> io::Result<()> {
+        self.next += 1;
+        writeln!(self.out, "{}", json!({ "id": self.next, "type": "edge", "label": label, "outV": out_v, "inV": in_v }))
+    }
+
+    // one to many edges, i.e. contains and item
+    fn edges(&mut self, label: &str, out_v: u64, in_vs: &[u64], mut extra: Value) -> io::Result<()> {
+        self.next += 1;
+        extra["id"] = json!(self.next);
+        extra["type"] = json!("edge");
+        extra["label"] = json!(label);
+        extra["outV"] = json!(out_v);
+        extra["inVs"] = json!(in_vs);
+        writeln!(self.out, "{}", extra)
+    }
+}
+
+/// C for .c and .h files, C++ for everything else
+pub fn language_id(uri: &FileUri) -> &'static str {
+    match uri.extension().as_deref() {
+        Some("c" | "h") => "c",
+        _ => "cpp",
+    }
+}
+
+/// What an editor shows on hover, i.e. `int add(int a, int b)`
+pub fn hover_text(s: &Symbol) -> String {
+    let decl = match s.syminfo.kind {
+        SymbolKind::Macro => format!("#define {}{}", s.name, s.signature),
+        // ReturnType also holds the type of variables and fields
+        _ => format!("{} {}{}", s.return_t, s.qualified_name(), s.signature),
+    };
+    decl.trim().to_string()
+}
+
+fn position(p: &SymbolPosition) -> Value {
+    json!({ "line": p.line, "character": p.column })
+}
+
+/// Write an LSIF dump of `db` to `out`; `root` is the project root recorded in the metadata
+pub fn export<W: Write>(db: &ClangdDatabase, root: &Path, out: &mut W) -> io::Result<()> {
+    let mut e = Emitter { out, next: 0 };
+    e.vertex("metaData", json!({
+        "version": LSIF_VERSION,
+        "projectRoot": FileUri::from_path(root),
+        "positionEncoding": "utf-16",
+        "toolInfo": { "name": env!("CARGO_PKG_NAME"), "version": env!("CARGO_PKG_VERSION") },
+    }))?;
+    let project = e.vertex("project", json!({ "kind": "cpp" }))?;
+
+    // every file:// location is a range in some document
+    let mut uris: BTreeSet<FileUri> = BTreeSet::new();
+    let mut add = |loc: &SymbolLocation| {
+        if loc.file_uri.scheme() == Some("file") {
+            uris.insert(loc.file_uri.clone());
+        }
+    };
+    for s in db.id.values() {
+        add(&s.definition);
+        add(&s.canonical_declaration);
+    }
+    for r in db.refs.values().flatten() {
+        add(&r.location);
+    }
+
+    let mut documents: BTreeMap<FileUri, u64> = BTreeMap::new();
+    for uri in uris {
+        let id = e.vertex("document", json!({ "uri": uri, "languageId": language_id(&uri) }))?;
+        documents.insert(uri, id);
+    }
+    let ids: Vec<u64> = documents.values().copied().collect();
+    if !ids.is_empty() {
+        e.edges("contains", project, &ids, json!({}))?;
+    }
+
+    // a range can only point at one result set: the first symbol to claim it owns it, later ones leave it out
+    let mut ranges: BTreeMap<(FileUri, SymbolPosition, SymbolPosition), (u64, SymbolId)> = BTreeMap::new();
+    let mut contains: BTreeMap<u64, Vec<u64>> = BTreeMap::new();
+
+    for s in db.id.values() {
+        let result_set = e.vertex("resultSet", json!({}))?;
+        let mut range = |e: &mut Emitter<W>, loc: &SymbolLocation| -> io::Result<Option<(u64, u64)>> {
+            let doc = match documents.get(&loc.file_uri) {
+                Some(d) => *d,
+                None => return Ok(None),
+            };
+            let key = (loc.file_uri.clone(), loc.start.clone(), loc.end.clone());
+            if let Some((r, owner)) = ranges.get(&key) {
+                return Ok(Some((*r, doc)).filter(|_| *owner == s.id));
+            }
+            let r = e.vertex("range", json!({ "start": position(&loc.start), "end": position(&loc.end) }))?;
+            e.edge("next", r, result_set)?;
+            ranges.insert(key, (r, s.id));
+            contains.entry(doc).or_default().push(r);
+            Ok(Some((r, doc)))
+        };
+
+        let mut contents = vec![json!({ "language": language_id(&s.location().file_uri), "value": hover_text(s) })];
+        if !s.documentation.is_empty() {
+            contents.push(json!(s.documentation));
+        }
+        let hover = e.vertex("hoverResult", json!({ "result": { "contents": contents } }))?;
+        e.edge("textDocument/hover", result_set, hover)?;
+
+        if let Some((r, doc)) = range(&mut e, s.location())? {
+            let def = e.vertex("definitionResult", json!({}))?;
+            e.edge("textDocument/definition", result_set, def)?;
+            e.edges("item", def, &[r], json!({ "document": doc }))?;
+        }
+
+        let refs = db.references(&s.id);
+        let mut items: BTreeMap<(u64, &str), Vec<u64>> = BTreeMap::new();
+        for rr in refs.iter() {
+            let property = if rr.has_kind(RefKind::Definition) {
+                "definitions"
+            }
+            else if rr.has_kind(RefKind::Declaration) {
+                "declarations"
+            }
+            else if rr.has_kind(RefKind::Reference) {
+                "references"
+            }
+            else {
+                continue;
+            };
+            if let Some((r, doc)) = range(&mut e, &rr.location)? {
+                items.entry((doc, property)).or_default().push(r);
+            }
+        }
+        if !items.is_empty() {
+            let result = e.vertex("referenceResult", json!({}))?;
+            e.edge("textDocument/references", result_set, result)?;
+            for ((doc, property), rs) in items.iter() {
+                e.edges("item", result, rs, json!({ "document": doc, "property": property }))?;
+            }
+        }
+    }
+
+    for (doc, rs) in contains.iter() {
+        e.edges("contains", *doc, rs, json!({}))?;
+    }
+    Ok(())
+}
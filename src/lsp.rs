@@ -0,0 +1,228 @@
+//! Read-only language server answering from a loaded index, without running clang.
+
+use lsp_server::{Connection, ErrorCode, Message, Request, Response};
+use lsp_types::request::{
+    CallHierarchyIncomingCalls, CallHierarchyOutgoingCalls, CallHierarchyPrepare, DocumentSymbolRequest,
+    GotoDefinition, References, Request as LspRequest, TypeHierarchyPrepare, TypeHierarchySubtypes,
+    TypeHierarchySupertypes, WorkspaceSymbolRequest,
+};
+use lsp_types::{
+    CallHierarchyIncomingCall, CallHierarchyItem, CallHierarchyOutgoingCall, CallHierarchyServerCapability,
+    DocumentSymbol, DocumentSymbolResponse, GotoDefinitionResponse, Location, OneOf, Position, Range,
+    ServerCapabilities, SymbolKind as LspSymbolKind, TextDocumentPositionParams, TypeHierarchyItem, Uri,
+    WorkspaceSymbol, WorkspaceSymbolResponse,
+};
+use serde_json::Value;
+
+use std::error::Error;
+use std::str::FromStr;
+
+use crate::clangd::ClangdDatabase;
+use crate::outline::{OutlineKind, OutlineSymbol};
+use crate::query::CallMap;
+use crate::refs::RefKind;
+use crate::symbols::{Symbol, SymbolId, SymbolKind, SymbolLocation, parse_symbol_id, symbol_id_str};
+use crate::uri::FileUri;
+
+// Same default limit as clangd
+const MAX_WORKSPACE_SYMBOLS: usize = 100;
+
+type LspResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
+
+/// Serve LSP requests over stdin/stdout until the client shuts down
+pub fn serve(db: &ClangdDatabase) -> LspResult<()> {
+    let (connection, io_threads) = Connection::stdio();
+    serve_connection(db, &connection)?;
+    drop(connection);
+    io_threads.join()?;
+    Ok(())
+}
+
+/// Serve LSP requests on `connection` until the client shuts down
+pub fn serve_connection(db: &ClangdDatabase, connection: &Connection) -> LspResult<()> {
+    let caps = ServerCapabilities {
+        workspace_symbol_provider: Some(OneOf::Left(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        references_provider: Some(OneOf::Left(true)),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        call_hierarchy_provider: Some(CallHierarchyServerCapability::Simple(true)),
+        ..Default::default()
+    };
+    let mut caps = serde_json::to_value(caps)?;
+    // not in lsp-types' ServerCapabilities yet
+    caps["typeHierarchyProvider"] = Value::Bool(true);
+    connection.initialize(caps)?;
+
+    for msg in &connection.receiver {
+        if let Message::Request(req) = msg {
+            if connection.handle_shutdown(&req)? {
+                return Ok(());
+            }
+            connection.sender.send(Message::Response(handle(db, &req)))?;
+        }
+    }
+    Ok(())
+}
+
+fn handle(db: &ClangdDatabase, req: &Request) -> Response {
+    on::<WorkspaceSymbolRequest>(req, |p| {
+        let syms: Vec<WorkspaceSymbol> = db.search(&p.query).into_iter()
+            .filter_map(workspace_symbol)
+            .take(MAX_WORKSPACE_SYMBOLS)
+            .collect();
+        Some(WorkspaceSymbolResponse::Nested(syms))
+    })
+    .or_else(|| on::<GotoDefinition>(req, |p| {
+        let sym = symbol_at(db, &p.text_document_position_params)?;
+        location(sym.location()).map(GotoDefinitionResponse::Scalar)
+    }))
+    .or_else(|| on::<References>(req, |p| {
+        let sym = symbol_at(db, &p.text_document_position)?;
+        let decl = p.context.include_declaration;
+        Some(db.references(&sym.id).iter()
+            .filter(|r| decl || !(r.has_kind(RefKind::Declaration) || r.has_kind(RefKind::Definition)))
+            .filter_map(|r| location(&r.location))
+            .collect())
+    }))
+    .or_else(|| on::<DocumentSymbolRequest>(req, |p| {
+        let uri = FileUri::new(p.text_document.uri.as_str());
+        let syms = db.outline_uri(&uri).iter().map(document_symbol).collect();
+        Some(DocumentSymbolResponse::Nested(syms))
+    }))
+    .or_else(|| on::<TypeHierarchyPrepare>(req, |p| {
+        let sym = symbol_at(db, &p.text_document_position_params).filter(|s| is_record(s))?;
+        type_item(sym).map(|i| vec![i])
+    }))
+    .or_else(|| on::<TypeHierarchySupertypes>(req, |p| {
+        let id = item_id(&p.item.data)?;
+        Some(db.supertypes(&id).into_iter().filter_map(type_item).collect())
+    }))
+    .or_else(|| on::<TypeHierarchySubtypes>(req, |p| {
+        let id = item_id(&p.item.data)?;
+        Some(db.subtypes(&id).into_iter().filter_map(type_item).collect())
+    }))
+    .or_else(|| on::<CallHierarchyPrepare>(req, |p| {
+        let sym = symbol_at(db, &p.text_document_position_params)?;
+        call_item(sym).map(|i| vec![i])
+    }))
+    .or_else(|| on::<CallHierarchyIncomingCalls>(req, |p| {
+        let id = item_id(&p.item.data)?;
+        Some(calls(db, &db.callers(&id)).into_iter()
+            .map(|(from, from_ranges)| CallHierarchyIncomingCall { from, from_ranges })
+            .collect())
+    }))
+    .or_else(|| on::<CallHierarchyOutgoingCalls>(req, |p| {
+        let id = item_id(&p.item.data)?;
+        Some(calls(db, &db.callees(&id)).into_iter()
+            .map(|(to, from_ranges)| CallHierarchyOutgoingCall { to, from_ranges })
+            .collect())
+    }))
+    .unwrap_or_else(|| Response::new_err(req.id.clone(), ErrorCode::MethodNotFound as i32,
+        format!("Unsupported method {}", req.method)))
+}
+
+// Answer `req` with `f` if it is an `R` request
+fn on<R: LspRequest>(req: &Request, f: impl FnOnce(R::Params) -> R::Result) -> Option<Response> {
+    if req.method != R::METHOD {
+        return None;
+    }
+    Some(match serde_json::from_value::<R::Params>(req.params.clone()) {
+        Ok(p) => Response::new_ok(req.id.clone(), f(p)),
+        Err(e) => Response::new_err(req.id.clone(), ErrorCode::InvalidParams as i32, e.to_string()),
+    })
+}
+
+fn symbol_at<'a>(db: &'a ClangdDatabase, p: &TextDocumentPositionParams) -> Option<&'a Symbol> {
+    let uri = FileUri::new(p.text_document.uri.as_str());
+    db.symbol_at_uri(&uri, p.position.line, p.position.character)
+}
+
+fn kind(k: OutlineKind) -> LspSymbolKind {
+    // OutlineKind is numbered as in the protocol
+    serde_json::from_value(Value::from(k as u8)).unwrap()
+}
+
+fn range(loc: &SymbolLocation) -> Range {
+    Range {
+        start: Position { line: loc.start.line, character: loc.start.column },
+        end: Position { line: loc.end.line, character: loc.end.column },
+    }
+}
+
+// None for URIs a client can't open, i.e. verbatim <headers>
+fn location(loc: &SymbolLocation) -> Option<Location> {
+    let uri = Uri::from_str(loc.file_uri.as_str()).ok()?;
+    Some(Location { uri, range: range(loc) })
+}
+
+// Symbol id the item was created for, as stored by `type_item` and `call_item`
+fn item_id(data: &Option<Value>) -> Option<SymbolId> {
+    parse_symbol_id(data.as_ref()?.as_str()?)
+}
+
+fn workspace_symbol(s: &Symbol) -> Option<WorkspaceSymbol> {
+    Some(WorkspaceSymbol {
+        name: s.name.clone(),
+        kind: kind(OutlineKind::from(&s.syminfo.kind)),
+        tags: None,
+        container_name: s.scope.strip_suffix("::").map(|c| c.to_string()),
+        location: OneOf::Left(location(s.location())?),
+        data: None,
+    })
+}
+
+#[allow(deprecated)]
+fn document_symbol(s: &OutlineSymbol) -> DocumentSymbol {
+    DocumentSymbol {
+        name: s.name.clone(),
+        detail: Some(s.detail.clone()).filter(|d| !d.is_empty()),
+        kind: kind(s.kind),
+        tags: None,
+        deprecated: None,
+        range: range(&s.location),
+        selection_range: range(&s.location),
+        children: Some(s.children.iter().map(document_symbol).collect()).filter(|c: &Vec<DocumentSymbol>| !c.is_empty()),
+    }
+}
+
+// Classes, structs and unions, the only symbols with a type hierarchy
+fn is_record(s: &Symbol) -> bool {
+    matches!(s.syminfo.kind, SymbolKind::Class | SymbolKind::Struct | SymbolKind::Union)
+}
+
+fn type_item(s: &Symbol) -> Option<TypeHierarchyItem> {
+    let loc = location(s.location())?;
+    Some(TypeHierarchyItem {
+        name: s.qualified_name(),
+        kind: kind(OutlineKind::from(&s.syminfo.kind)),
+        tags: None,
+        detail: None,
+        uri: loc.uri,
+        range: loc.range,
+        selection_range: loc.range,
+        data: Some(Value::from(symbol_id_str(&s.id))),
+    })
+}
+
+fn call_item(s: &Symbol) -> Option<CallHierarchyItem> {
+    let loc = location(s.location())?;
+    Some(CallHierarchyItem {
+        name: s.qualified_name(),
+        kind: kind(OutlineKind::from(&s.syminfo.kind)),
+        tags: None,
+        detail: Some(s.signature.clone()).filter(|d| !d.is_empty()),
+        uri: loc.uri,
+        range: loc.range,
+        selection_range: loc.range,
+        data: Some(Value::from(symbol_id_str(&s.id))),
+    })
+}
+
+fn calls(db: &ClangdDatabase, m: &CallMap) -> Vec<(CallHierarchyItem, Vec<Range>)> {
+    m.iter()
+        .filter_map(|(id, sites)| {
+            let item = call_item(db.id.get(id)?)?;
+            Some((item, sites.iter().map(range).collect()))
+        })
+        .collect()
+}
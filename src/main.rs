@@ -1,31 +1,594 @@
-mod clangd;
-mod rela;
-mod refs;
-mod srcs;
-mod symbols;
-mod cmdl;
-
-use clap::Parser;
+use clap::{Parser, Subcommand};
 
+use std::fs;
 use std::path::PathBuf;
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
-use clangd_parser;
+use clangd_parser::clangd::ClangdDatabase;
+use clangd_parser::compat;
+use clangd_parser::compdb::{self, CompileCommandOptions};
+use clangd_parser::dead;
+use clangd_parser::flags;
+use clangd_parser::globals;
+use clangd_parser::graph::{self, GraphFormat, GraphKind, GraphOptions};
+use clangd_parser::health;
+use clangd_parser::impact::{self, ChangedRange};
+use clangd_parser::includes;
+use clangd_parser::link;
+use clangd_parser::lsif;
+use clangd_parser::scip;
+use clangd_parser::lsp;
+use clangd_parser::mocks::{self, MockStyle};
+use clangd_parser::paths::PathMapping;
+use clangd_parser::remote;
+use clangd_parser::sarif::Sarif;
+use clangd_parser::serve;
+use clangd_parser::symbols::{self, SymbolProperty};
+use clangd_parser::tags;
+use clangd_parser::uri::FileUri;
+use clangd_parser::testgen::{self, TestFramework};
+use clangd_parser::watch::IndexWatcher;
 
 #[derive(Parser, Debug)]
 struct Cli {
     /// Path to repo root
-    #[arg(short='d', long, default_value_t=String::from("."))]
+    #[arg(short='d', long, default_value_t=String::from("."), global=true)]
     path: String,
+
+    /// Rewrite recorded paths, as comma separated from=to pairs
+    #[arg(long, global=true)]
+    path_mappings: Option<String>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Watch the clangd index and print changes as shards are rewritten
+    Watch {
+        /// Polling interval in milliseconds
+        #[arg(short='i', long, default_value_t=1000)]
+        interval: u64,
+    },
+    /// Compare two indexes, i.e. from two commits
+    Diff {
+        /// Path to the baseline repo root
+        old: String,
+        /// Path to the new repo root
+        new: String,
+    },
+    /// Check the public headers of two indexes for API-breaking changes, as JSON
+    Compat {
+        /// Path to the baseline repo root
+        old: String,
+        /// Path to the new repo root
+        new: String,
+        /// Only consider headers under this path, relative to each repo root unless absolute; may be repeated
+        #[arg(short='p', long)]
+        public: Vec<String>,
+    },
+    /// List symbols that are defined but never used
+    DeadCode {
+        /// Never report names matching this pattern (`*` wildcards); may be repeated
+        #[arg(short='a', long)]
+        allow: Vec<String>,
+        /// File with one allowlist pattern per line
+        #[arg(long)]
+        allowlist: Option<String>,
+        /// Emit SARIF instead of text
+        #[arg(long)]
+        sarif: bool,
+    },
+    /// Run every analysis: dead code, include cycles, unreadable or failed index files
+    Check {
+        /// Never report names matching this pattern as dead code; may be repeated
+        #[arg(short='a', long)]
+        allow: Vec<String>,
+        /// Emit SARIF instead of text
+        #[arg(long)]
+        sarif: bool,
+    },
+    /// List translation units clangd failed to compile, and what that may hide
+    IndexHealth,
+    /// Write a compile_commands.json recovered from the index
+    CompileCommands {
+        /// Output file, stdout if not given
+        #[arg(short='o', long)]
+        output: Option<String>,
+        /// Keep flags clangd injected, like -resource-dir
+        #[arg(long)]
+        keep_injected: bool,
+        /// Join split flags, make include paths absolute and drop duplicates
+        #[arg(long)]
+        normalize: bool,
+    },
+    /// Report the compile flags of each translation unit and where they differ from the majority
+    Flags {
+        /// Emit JSON instead of text
+        #[arg(long)]
+        json: bool,
+    },
+    /// Show the symbol at a position, like go to definition
+    SymbolAt {
+        /// Source file
+        file: String,
+        /// 1-based line
+        line: u32,
+        /// 1-based column
+        column: u32,
+    },
+    /// List the symbols of a file, nested by scope
+    Outline {
+        /// Source or header file
+        file: String,
+    },
+    /// Run a read-only language server over stdio, answering from the index
+    Lsp,
+    /// Answer JSON queries over HTTP, reloading the index when it changes
+    Serve {
+        /// Address to listen on
+        #[arg(short='a', long, default_value_t=String::from("127.0.0.1:8080"))]
+        addr: String,
+        /// Polling interval for index changes, in milliseconds
+        #[arg(short='i', long, default_value_t=1000)]
+        interval: u64,
+        /// Never reload the index
+        #[arg(long)]
+        no_reload: bool,
+    },
+    /// Serve clangd's remote-index gRPC protocol, for clangd --remote-index-address
+    RemoteIndex {
+        /// Address to listen on
+        #[arg(short='a', long, default_value_t=String::from("127.0.0.1:50051"))]
+        addr: String,
+        /// Root that paths are sent relative to, the repo root if not given
+        #[arg(long)]
+        project_root: Option<String>,
+    },
+    /// Export the index as an LSIF dump
+    Lsif {
+        /// Output file, stdout if not given
+        #[arg(short='o', long)]
+        output: Option<String>,
+    },
+    /// Export the index as a SCIP index
+    Scip {
+        /// Output file
+        #[arg(short='o', long, default_value="index.scip")]
+        output: String,
+    },
+    /// Write a universal-ctags tags file
+    Ctags {
+        /// Output file, tags in the repo root if not given
+        #[arg(short='o', long)]
+        output: Option<String>,
+    },
+    /// Write an Emacs TAGS file
+    Etags {
+        /// Output file, TAGS in the repo root if not given
+        #[arg(short='o', long)]
+        output: Option<String>,
+    },
+    /// Write a cscope cross-reference, for cscope -d
+    Cscope {
+        /// Output file, cscope.out in the repo root if not given
+        #[arg(short='o', long)]
+        output: Option<String>,
+    },
+    /// Export the call graph, include graph or class hierarchy as DOT, GraphML or Mermaid
+    Graph {
+        /// calls, includes or types
+        #[arg(default_value="calls")]
+        kind: GraphKind,
+        /// dot, graphml or mermaid
+        #[arg(short='f', long, default_value="dot")]
+        format: GraphFormat,
+        /// Start from this symbol (qualified name or hex id), or this file for includes
+        #[arg(short='r', long)]
+        root: Option<String>,
+        /// Go at most this many edges from the root
+        #[arg(long)]
+        depth: Option<usize>,
+        /// Group nodes by directory
+        #[arg(long)]
+        cluster: bool,
+        /// Output file, stdout if not given
+        #[arg(short='o', long)]
+        output: Option<String>,
+    },
+    /// Generate a unit-test skeleton for a function
+    GenTests {
+        /// Function name, or its position as file:line:column (1-based)
+        function: String,
+        /// unity, gtest or criterion
+        #[arg(short='f', long, default_value="unity")]
+        framework: TestFramework,
+        /// Output file, stdout if not given
+        #[arg(short='o', long)]
+        output: Option<String>,
+    },
+    /// Generate stubs or fakes for the functions a source file calls outside itself
+    Mocks {
+        /// Source file
+        file: String,
+        /// stub, fff or cmock
+        #[arg(short='s', long, default_value="stub")]
+        style: MockStyle,
+        /// Only list the functions
+        #[arg(long)]
+        list: bool,
+        /// Output file, stdout if not given
+        #[arg(short='o', long)]
+        output: Option<String>,
+    },
+    /// List the sources a test translation unit needs linked in
+    LinkSet {
+        /// Test source file
+        file: String,
+        /// Emit a CMake add_executable() instead of text
+        #[arg(long)]
+        cmake: bool,
+    },
+    /// Find the tests affected by changed lines
+    Impact {
+        /// Changed lines as file:line or file:start-end, relative to the repo root
+        ranges: Vec<String>,
+        /// Unified diff to take changed lines from, - for stdin
+        #[arg(long)]
+        diff: Option<String>,
+        /// Names of test functions (`*` wildcards); may be repeated, replaces the defaults
+        #[arg(short='t', long)]
+        test_pattern: Vec<String>,
+        /// Emit JSON instead of text
+        #[arg(long)]
+        json: bool,
+    },
+    /// List the specializations of a class, function or variable template
+    Specializations {
+        /// Qualified name or hex id of the primary template
+        template: String,
+    },
+    /// List the symbols the index marks as unit tests; only GoogleTest's TEST, TEST_F and TEST_P are recognised
+    UnitTests,
+    /// Report global variables and the functions reading and writing them
+    Globals {
+        /// Only rank this many functions
+        #[arg(long)]
+        top: Option<usize>,
+        /// Emit JSON instead of text
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+// Without a subcommand, only load the index and say how long that took
+fn time_load(timer: SystemTime, load: impl FnOnce() -> ClangdDatabase) {
+    let _db = load();
+    println!("Execution took {:.2}s.", timer.elapsed().unwrap().as_secs_f32());
 }
 
-#[allow(unused_variables, unused_assignments)]
 fn main() {
-    let results;
     let timer = SystemTime::now();
     let args = Cli::parse();
     let p = PathBuf::from(args.path.as_str());
+    let mappings = match args.path_mappings.as_deref().map(PathMapping::parse_list) {
+        Some(Ok(m)) => m,
+        Some(Err(e)) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        },
+        None => vec![],
+    };
+    let load = |p: &PathBuf| clangd_parser::load(p, &mappings).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
 
-    results = clangd_parser::run(&p);
-    println!("Execution took {:.2}s.", timer.elapsed().unwrap().as_secs_f32());
-}
\ No newline at end of file
+    match args.command {
+        Some(Command::Watch { interval }) => {
+            let mut w = IndexWatcher::with_mappings(&p, &mappings).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            });
+            w.watch(Duration::from_millis(interval), |ev| println!("{}", ev));
+        },
+        Some(Command::Diff { old, new }) => {
+            let old = load(&PathBuf::from(old));
+            let new = load(&PathBuf::from(new));
+            print!("{}", old.diff(&new));
+        },
+        Some(Command::Compat { old, new, public }) => {
+            let (old_root, new_root) = (PathBuf::from(old), PathBuf::from(new));
+            let old = load(&old_root);
+            let new = load(&new_root);
+            let report = compat::check(&old, &old_root, &new, &new_root, &public);
+            println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            if report.is_breaking() {
+                std::process::exit(1);
+            }
+        },
+        Some(Command::DeadCode { mut allow, allowlist, sarif }) => {
+            if let Some(f) = allowlist {
+                let contents = fs::read_to_string(f).expect("Unable to read allowlist!");
+                allow.extend(contents.lines()
+                    .map(|l| l.trim())
+                    .filter(|l| !l.is_empty() && !l.starts_with('#'))
+                    .map(|l| l.to_string()));
+            }
+            let db = load(&p);
+            let report = dead::find(&db, &allow);
+            if sarif {
+                let out = Sarif::new(&[dead::DEAD_CODE_RULE], &report.findings());
+                println!("{}", serde_json::to_string_pretty(&out).unwrap());
+            }
+            else {
+                print!("{}", report);
+            }
+        },
+        Some(Command::Check { allow, sarif }) => {
+            let db = load(&p);
+            let rules = [
+                dead::DEAD_CODE_RULE,
+                includes::INCLUDE_CYCLE_RULE,
+                health::PARSE_FAILURE_RULE,
+                health::INDEX_ERROR_RULE,
+            ];
+            let mut findings = health::findings(&db);
+            findings.append(&mut includes::findings(&db));
+            findings.append(&mut dead::find(&db, &allow).findings());
+            if sarif {
+                let out = Sarif::new(&rules, &findings);
+                println!("{}", serde_json::to_string_pretty(&out).unwrap());
+            }
+            else {
+                for f in findings.iter() {
+                    println!("{}", f);
+                }
+            }
+        },
+        Some(Command::IndexHealth) => {
+            let db = load(&p);
+            print!("{}", health::report(&db));
+        },
+        Some(Command::CompileCommands { output, keep_injected, normalize }) => {
+            let db = load(&p);
+            let opts = CompileCommandOptions { strip_injected: !keep_injected, normalize };
+            let json = serde_json::to_string_pretty(&compdb::compile_database(&db, &opts)).unwrap();
+            match output {
+                Some(f) => fs::write(f, json).expect("Unable to write compile commands!"),
+                None => println!("{}", json),
+            }
+        },
+        Some(Command::Flags { json }) => {
+            let db = load(&p);
+            let report = flags::report(&db);
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            }
+            else {
+                print!("{}", report);
+            }
+        },
+        Some(Command::SymbolAt { file, line, column }) => {
+            let db = load(&p);
+            let file = std::path::absolute(&file).expect("Invalid file path!");
+            match db.symbol_at(&file, line.saturating_sub(1), column.saturating_sub(1)) {
+                Some(sym) => {
+                    let def = sym.location();
+                    println!("{:?} {}{}", sym.syminfo.kind, sym.qualified_name(), sym.signature);
+                    println!("{}:{}:{}", def.file_uri, def.start.line + 1, def.start.column + 1);
+                },
+                None => {
+                    eprintln!("No symbol at {}:{}:{}", file.display(), line, column);
+                    std::process::exit(1);
+                },
+            }
+        },
+        Some(Command::Outline { file }) => {
+            let db = load(&p);
+            let file = std::path::absolute(&file).expect("Invalid file path!");
+            for s in db.outline(&file) {
+                print!("{}", s);
+            }
+        },
+        Some(Command::Lsp) => {
+            let db = load(&p);
+            if let Err(e) = lsp::serve(&db) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        },
+        Some(Command::Serve { addr, interval, no_reload }) => {
+            let reload = Some(Duration::from_millis(interval)).filter(|_| !no_reload);
+            if let Err(e) = serve::serve(&p, &mappings, &addr, reload) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        },
+        Some(Command::RemoteIndex { addr, project_root }) => {
+            let root = std::path::absolute(project_root.map(PathBuf::from).unwrap_or(p.clone())).expect("Invalid project root!");
+            let index = remote::RemoteIndex::new(load(&p), &root);
+            let rt = tokio::runtime::Runtime::new().expect("Unable to start the async runtime!");
+            let res = rt.block_on(async {
+                let listener = tokio::net::TcpListener::bind(&addr).await?;
+                eprintln!("Serving {} on {}", root.display(), listener.local_addr()?);
+                remote::serve(index, listener).await
+            });
+            if let Err(e) = res {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        },
+        Some(Command::Lsif { output }) => {
+            let db = load(&p);
+            let root = std::path::absolute(&p).expect("Invalid repo root!");
+            let res = match output {
+                Some(f) => fs::File::create(f).map(std::io::BufWriter::new).and_then(|mut w| lsif::export(&db, &root, &mut w)),
+                None => lsif::export(&db, &root, &mut std::io::stdout().lock()),
+            };
+            res.expect("Unable to write LSIF dump!");
+        },
+        Some(Command::Scip { output }) => {
+            let db = load(&p);
+            let root = std::path::absolute(&p).expect("Invalid repo root!");
+            fs::File::create(output).map(std::io::BufWriter::new)
+                .and_then(|mut w| scip::export(&db, &root, &mut w))
+                .expect("Unable to write SCIP index!");
+        },
+        Some(Command::Ctags { output }) => {
+            let db = load(&p);
+            let root = std::path::absolute(&p).expect("Invalid repo root!");
+            let f = output.map(PathBuf::from).unwrap_or(root.join("tags"));
+            fs::File::create(f).map(std::io::BufWriter::new)
+                .and_then(|mut w| tags::ctags(&db, &root, &mut w))
+                .expect("Unable to write tags file!");
+        },
+        Some(Command::Etags { output }) => {
+            let db = load(&p);
+            let root = std::path::absolute(&p).expect("Invalid repo root!");
+            let f = output.map(PathBuf::from).unwrap_or(root.join("TAGS"));
+            fs::File::create(f).map(std::io::BufWriter::new)
+                .and_then(|mut w| tags::etags(&db, &root, &mut w))
+                .expect("Unable to write TAGS file!");
+        },
+        Some(Command::Cscope { output }) => {
+            let db = load(&p);
+            let root = std::path::absolute(&p).expect("Invalid repo root!");
+            let f = output.map(PathBuf::from).unwrap_or(root.join("cscope.out"));
+            fs::File::create(f).map(std::io::BufWriter::new)
+                .and_then(|mut w| tags::cscope(&db, &root, &mut w))
+                .expect("Unable to write cscope cross-reference!");
+        },
+        Some(Command::Graph { kind, format, root, depth, cluster, output }) => {
+            let db = load(&p);
+            let repo = std::path::absolute(&p).expect("Invalid repo root!");
+            let g = match graph::build(&db, kind, &repo, &GraphOptions { root, depth, cluster }) {
+                Ok(g) => g,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                },
+            };
+            let res = match output {
+                Some(f) => fs::File::create(f).map(std::io::BufWriter::new).and_then(|mut w| g.write(format, &mut w)),
+                None => g.write(format, &mut std::io::stdout().lock()),
+            };
+            res.expect("Unable to write graph!");
+        },
+        Some(Command::GenTests { function, framework, output }) => {
+            let db = load(&p);
+            let sym = match testgen::find_function(&db, &function) {
+                Ok(s) => s,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                },
+            };
+            let root = std::path::absolute(&p).expect("Invalid repo root!");
+            let dirs = testgen::include_dirs(&db, &sym.definition.file_uri, &root);
+            let code = testgen::generate(sym, framework, &dirs);
+            match output {
+                Some(f) => fs::write(f, code).expect("Unable to write tests!"),
+                None => print!("{}", code),
+            }
+        },
+        Some(Command::Mocks { file, style, list, output }) => {
+            let db = load(&p);
+            let file = FileUri::from_path(&std::path::absolute(&file).expect("Invalid file path!"));
+            let deps = mocks::external_calls(&db, &file);
+            if list {
+                for s in deps.iter() {
+                    println!("{}{} {}", s.qualified_name(), s.signature, s.location().file_uri);
+                }
+                return;
+            }
+            let root = std::path::absolute(&p).expect("Invalid repo root!");
+            let code = mocks::generate(&file, &deps, style, &testgen::include_dirs(&db, &file, &root));
+            match output {
+                Some(f) => fs::write(f, code).expect("Unable to write mocks!"),
+                None => print!("{}", code),
+            }
+        },
+        Some(Command::LinkSet { file, cmake }) => {
+            let db = load(&p);
+            let file = FileUri::from_path(&std::path::absolute(&file).expect("Invalid file path!"));
+            let report = link::link_set(&db, &file);
+            if cmake {
+                let root = std::path::absolute(&p).expect("Invalid repo root!");
+                print!("{}", report.cmake(&root));
+            }
+            else {
+                print!("{}", report);
+            }
+        },
+        Some(Command::Impact { ranges, diff, mut test_pattern, json }) => {
+            let root = std::path::absolute(&p).expect("Invalid repo root!");
+            let mut changes: Vec<ChangedRange> = vec![];
+            for r in ranges.iter() {
+                match ChangedRange::parse(r, &root) {
+                    Ok(c) => changes.push(c),
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        std::process::exit(2);
+                    },
+                }
+            }
+            if let Some(d) = diff {
+                let text = match d.as_str() {
+                    "-" => std::io::read_to_string(std::io::stdin()),
+                    f => fs::read_to_string(f),
+                };
+                changes.extend(impact::parse_unified_diff(&text.expect("Unable to read diff!"), &root));
+            }
+            if test_pattern.is_empty() {
+                test_pattern = impact::DEFAULT_TEST_PATTERNS.iter().map(|p| p.to_string()).collect();
+            }
+            let db = load(&p);
+            let report = impact::analyze(&db, &changes, &test_pattern);
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            }
+            else {
+                print!("{}", report);
+            }
+        },
+        Some(Command::Specializations { template }) => {
+            let db = load(&p);
+            let primary = match symbols::parse_symbol_id(&template) {
+                Some(id) => db.id.get(&id),
+                None => db.id.values().find(|s| s.qualified_name() == template && s.template_specialization_args.is_empty()),
+            };
+            let Some(primary) = primary else {
+                eprintln!("No template named {}", template);
+                std::process::exit(1);
+            };
+            for s in db.specializations(&primary.id) {
+                let loc = s.location();
+                let partial = if s.has_property(SymbolProperty::TemplatePartialSpecialization) { " (partial)" } else { "" };
+                println!("{}{}{} {}:{}", s.qualified_name(), s.template_specialization_args, partial, loc.file_uri, loc.start.line + 1);
+            }
+        },
+        Some(Command::UnitTests) => {
+            let db = load(&p);
+            for s in db.unit_tests() {
+                let loc = s.location();
+                println!("{} {}:{}", s.qualified_name(), loc.file_uri, loc.start.line + 1);
+            }
+        },
+        Some(Command::Globals { top, json }) => {
+            let db = load(&p);
+            let mut report = globals::analyze(&db);
+            if let Some(n) = top {
+                report.functions.truncate(n);
+            }
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            }
+            else {
+                print!("{}", report);
+            }
+        },
+        None => time_load(timer, || load(&p)),
+    }
+}
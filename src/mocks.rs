@@ -0,0 +1,183 @@
+//! Stubs and fakes for the functions a source file calls outside itself, to isolate it in a unit test.
+
+use std::str::FromStr;
+use std::path::PathBuf;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::clangd::ClangdDatabase;
+use crate::symbols::{Param, Symbol, SymbolId, SymbolKind};
+use crate::testgen::include_for;
+use crate::uri::FileUri;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MockStyle {
+    /// Plain C definitions counting calls and returning a settable value
+    Stub,
+    /// Fake Function Framework declarations
+    Fff,
+    /// A header of prototypes for CMock to generate mocks from
+    CMock,
+}
+impl FromStr for MockStyle {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "stub" => Ok(MockStyle::Stub),
+            "fff" => Ok(MockStyle::Fff),
+            "cmock" => Ok(MockStyle::CMock),
+            _ => Err(format!("Unknown style '{}', expected stub, fff or cmock", s)),
+        }
+    }
+}
+
+/// Functions called from code in `file` and defined in another source file, or nowhere in the index, sorted by name.
+/// Those defined in headers, i.e. inline ones, come in with the includes so aren't stubbed.
+pub fn external_calls<'a>(db: &'a ClangdDatabase, file: &FileUri) -> Vec<&'a Symbol> {
+    let mut deps: BTreeMap<(String, SymbolId), &Symbol> = BTreeMap::new();
+    for (id, refs) in db.refs.iter() {
+        let Some(target) = db.id.get(id).filter(|s| s.syminfo.kind == SymbolKind::Function) else { continue };
+        if target.definition.file_uri == *file || target.definition.file_uri.is_header() {
+            continue;
+        }
+        let called_here = refs.iter()
+            .filter(|r| db.queries.is_call(r))
+            .filter_map(|r| db.id.get(&r.container_id))
+            .any(|c| c.location().file_uri == *file);
+        if called_here {
+            deps.insert((target.qualified_name(), target.id), target);
+        }
+    }
+    deps.into_values().collect()
+}
+
+// The qualified name as a C identifier, so a::f and b::f get their own stub state
+fn mangle(s: &Symbol) -> String {
+    s.qualified_name().replace("::", "_").chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '_' })
+        .collect()
+}
+
+fn is_variadic(s: &Symbol) -> bool {
+    s.signature.contains("...")
+}
+
+fn return_type(s: &Symbol) -> &str {
+    match s.return_t.trim() {
+        "" => "void",
+        t => t,
+    }
+}
+
+// Includes declaring the dependencies, so the generated code sees their types
+fn includes(deps: &[&Symbol], dirs: &[PathBuf]) -> String {
+    let lines: BTreeSet<String> = deps.iter().filter_map(|s| include_for(s, dirs)).collect();
+    lines.into_iter().map(|l| format!("{}\n", l)).collect()
+}
+
+/// Stubs, fakes or a CMock header for `deps`, the dependencies of `file`, with includes spelled relative to `dirs`
+pub fn generate(file: &FileUri, deps: &[&Symbol], style: MockStyle, dirs: &[PathBuf]) -> String {
+    let name = file.file_name().unwrap_or_default();
+    match style {
+        MockStyle::Stub => stubs(&name, deps, dirs),
+        MockStyle::Fff => fakes(&name, deps, dirs),
+        MockStyle::CMock => cmock_header(&name, deps, dirs),
+    }
+}
+
+fn stubs(file: &str, deps: &[&Symbol], dirs: &[PathBuf]) -> String {
+    let mut out = format!("/* Stubs for the functions {} calls outside itself */\n", file);
+    out.push_str(&includes(deps, dirs));
+    for s in deps.iter() {
+        let ret = return_type(s);
+        let state = mangle(s);
+        out.push_str(&format!("\nint {}_call_count;\n", state));
+        if ret != "void" {
+            out.push_str(&format!("{} {}_return_value;\n", ret, state));
+        }
+        // parameters as parsed, so unnamed ones get the names used below
+        let params = s.params();
+        let mut decls: Vec<String> = params.iter().map(|p| p.decl.clone()).collect();
+        if is_variadic(s) {
+            decls.push("...".to_string());
+        }
+        let decls = if decls.is_empty() { "void".to_string() } else { decls.join(", ") };
+        out.push_str(&format!("\n{} {}({})\n{{\n", ret, s.qualified_name(), decls));
+        for p in params.iter() {
+            out.push_str(&format!("    (void){};\n", p.name));
+        }
+        out.push_str(&format!("    {}_call_count++;\n", state));
+        if ret != "void" {
+            out.push_str(&format!("    return {}_return_value;\n", state));
+        }
+        out.push_str("}\n");
+    }
+    out
+}
+
+// FFF takes parameter types as macro arguments: arrays decay and function pointers need a name
+fn fff_type(s: &Symbol, p: &Param, typedefs: &mut String) -> String {
+    if p.t.contains("(*") {
+        let alias = format!("{}_{}_t", s.name, p.name);
+        typedefs.push_str(&format!("typedef {};\n", p.t.replacen("(*", &format!("(*{}", alias), 1)));
+        return alias;
+    }
+    match p.t.split_once('[') {
+        Some((base, _)) => format!("{} *", base.trim_end()),
+        None => p.t.clone(),
+    }
+}
+
+fn fakes(file: &str, deps: &[&Symbol], dirs: &[PathBuf]) -> String {
+    let mut out = format!("/* Fakes for the functions {} calls outside itself */\n#include \"fff.h\"\n", file);
+    out.push_str(&includes(deps, dirs));
+    out.push_str("\nDEFINE_FFF_GLOBALS;\n\n");
+    let mut typedefs = String::new();
+    let mut fakes = String::new();
+    for s in deps.iter() {
+        if !s.scope.is_empty() {
+            fakes.push_str(&format!("/* {} is scoped, which FFF can't fake */\n", s.qualified_name()));
+            continue;
+        }
+        let mut args: Vec<String> = s.params().iter().map(|p| fff_type(s, p, &mut typedefs)).collect();
+        let vararg = if is_variadic(s) {
+            args.push("...".to_string());
+            "_VARARG"
+        }
+        else {
+            ""
+        };
+        let args: String = args.iter().map(|a| format!(", {}", a)).collect();
+        match return_type(s) {
+            "void" => fakes.push_str(&format!("FAKE_VOID_FUNC{}({}{});\n", vararg, s.name, args)),
+            ret => fakes.push_str(&format!("FAKE_VALUE_FUNC{}({}, {}{});\n", vararg, ret, s.name, args)),
+        }
+    }
+    if !typedefs.is_empty() {
+        out.push_str(&typedefs);
+        out.push('\n');
+    }
+    out.push_str(&fakes);
+
+    // resetting every fake is the usual first line of setUp
+    out.push_str("\n#define RESET_FAKES() \\\n");
+    for s in deps.iter().filter(|s| s.scope.is_empty()) {
+        out.push_str(&format!("    RESET_FAKE({}); \\\n", s.name));
+    }
+    out.push_str("    FFF_RESET_HISTORY()\n");
+    out
+}
+
+fn cmock_header(file: &str, deps: &[&Symbol], dirs: &[PathBuf]) -> String {
+    let guard: String = format!("{}_deps_h", file).chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect();
+    let mut out = format!("/* Functions {} calls outside itself; run CMock on this header to mock them */\n", file);
+    out.push_str(&format!("#ifndef {}\n#define {}\n\n", guard, guard));
+    out.push_str(&includes(deps, dirs));
+    out.push('\n');
+    for s in deps.iter() {
+        out.push_str(&format!("{} {}{};\n", return_type(s), s.qualified_name(), s.signature));
+    }
+    out.push_str(&format!("\n#endif /* {} */\n", guard));
+    out
+}
@@ -0,0 +1,155 @@
+//! Symbols of a single file, nested by scope like an LSP document-symbol tree.
+
+use std::fmt;
+use std::path::Path;
+use std::collections::BTreeMap;
+
+use crate::clangd::ClangdDatabase;
+use crate::symbols::{Symbol, SymbolId, SymbolKind, SymbolLocation};
+use crate::uri::FileUri;
+
+/// LSP's SymbolKind, numbered as in the protocol
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[repr(u8)]
+pub enum OutlineKind {
+    File = 1,
+    Module,
+    Namespace,
+    Package,
+    Class,
+    Method,
+    Property,
+    Field,
+    Constructor,
+    Enum,
+    Interface,
+    Function,
+    #[default]
+    Variable,
+    Constant,
+    String,
+    Number,
+    Boolean,
+    Array,
+    Object,
+    Key,
+    Null,
+    EnumMember,
+    Struct,
+    Event,
+    Operator,
+    TypeParameter,
+}
+// Same mapping clangd uses for its document symbols
+impl From<&SymbolKind> for OutlineKind {
+    fn from(k: &SymbolKind) -> Self {
+        use SymbolKind::*;
+        match k {
+            Unknown | Variable | Parameter => OutlineKind::Variable,
+            Module => OutlineKind::Module,
+            Namespace | NamespaceAlias | Using => OutlineKind::Namespace,
+            Macro => OutlineKind::String,
+            Enum => OutlineKind::Enum,
+            Struct => OutlineKind::Struct,
+            Class | Union | TypeAlias => OutlineKind::Class,
+            Protocol | Extension => OutlineKind::Interface,
+            Function | ConversionFunction => OutlineKind::Function,
+            Field => OutlineKind::Field,
+            EnumConstant => OutlineKind::EnumMember,
+            InstanceMethod | ClassMethod | StaticMethod => OutlineKind::Method,
+            InstanceProperty | ClassProperty | StaticProperty => OutlineKind::Property,
+            Constructor | Destructor => OutlineKind::Constructor,
+            TemplateTypeParm | TemplateTemplateParm | NonTypeTemplateParm => OutlineKind::TypeParameter,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OutlineSymbol {
+    pub id: SymbolId,
+    pub name: String,
+    // signature, if any
+    pub detail: String,
+    pub kind: OutlineKind,
+    // the definition if it is in this file, the declaration otherwise
+    pub location: SymbolLocation,
+    pub children: Vec<OutlineSymbol>,
+}
+impl OutlineSymbol {
+    fn new(sym: &Symbol, location: &SymbolLocation) -> Self {
+        OutlineSymbol {
+            id: sym.id,
+            name: sym.name.clone(),
+            detail: sym.signature.clone(),
+            kind: OutlineKind::from(&sym.syminfo.kind),
+            location: location.clone(),
+            children: vec![],
+        }
+    }
+
+    fn fmt_indented(&self, f: &mut fmt::Formatter, depth: usize) -> fmt::Result {
+        writeln!(f, "{}{}:{} {:?} {}{}", "    ".repeat(depth),
+            self.location.start.line + 1, self.location.start.column + 1, self.kind, self.name, self.detail)?;
+        for c in self.children.iter() {
+            c.fmt_indented(f, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+impl fmt::Display for OutlineSymbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_indented(f, 0)
+    }
+}
+
+impl ClangdDatabase {
+    /// Every symbol defined or declared in `path`, sorted by position and nested by scope
+    pub fn outline(&self, path: &Path) -> Vec<OutlineSymbol> {
+        self.outline_uri(&FileUri::from_path(path))
+    }
+
+    pub fn outline_uri(&self, uri: &FileUri) -> Vec<OutlineSymbol> {
+        // headers have shards too, but they are named by a hash of the path and the merged symbols are what's
+        // wanted, so go through every symbol
+        let mut syms: Vec<(&Symbol, &SymbolLocation)> = self.id.values()
+            .filter_map(|s| {
+                if s.definition.file_uri == *uri {
+                    Some((s, &s.definition))
+                }
+                else if s.canonical_declaration.file_uri == *uri {
+                    Some((s, &s.canonical_declaration))
+                }
+                else {
+                    None
+                }
+            })
+            .collect();
+        syms.sort_by(|a, b| (&a.1.start, &a.1.end, &a.0.name).cmp(&(&b.1.start, &b.1.end, &b.0.name)));
+
+        let by_name: BTreeMap<String, usize> = syms.iter()
+            .enumerate()
+            .map(|(i, (s, _))| (s.qualified_name(), i))
+            .collect();
+        // index of each symbol's enclosing scope, if that is in this file too
+        let mut children: BTreeMap<Option<usize>, Vec<usize>> = BTreeMap::new();
+        for (i, (s, _)) in syms.iter().enumerate() {
+            let parent = s.scope.strip_suffix("::")
+                .and_then(|p| by_name.get(p))
+                .copied()
+                .filter(|p| *p != i);
+            children.entry(parent).or_default().push(i);
+        }
+
+        fn build(i: usize, syms: &[(&Symbol, &SymbolLocation)], children: &BTreeMap<Option<usize>, Vec<usize>>) -> OutlineSymbol {
+            let mut node = OutlineSymbol::new(syms[i].0, syms[i].1);
+            for c in children.get(&Some(i)).into_iter().flatten() {
+                node.children.push(build(*c, syms, children));
+            }
+            node
+        }
+        children.get(&None).into_iter()
+            .flatten()
+            .map(|i| build(*i, &syms, &children))
+            .collect()
+    }
+}
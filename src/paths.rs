@@ -0,0 +1,107 @@
+//! Rewrite the absolute paths recorded by clangd, so an index built on one machine can be used on another.
+
+use crate::clangd::ClangdFile;
+use crate::symbols::SymbolLocation;
+use crate::uri::{self, FileUri};
+
+// Flags that may carry a path glued to them, i.e. -I/ci/build/include
+const PATH_FLAGS: [&str; 8] = ["-I", "-isystem", "-iquote", "-idirafter", "-include", "-o", "--sysroot=", "-isysroot"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathMapping {
+    pub from: String,
+    pub to: String,
+}
+impl PathMapping {
+    /// Parse `from=to` pairs separated by commas, the same format as clangd's --path-mappings
+    pub fn parse_list(s: &str) -> Result<Vec<PathMapping>, String> {
+        s.split(',')
+            .filter(|m| !m.trim().is_empty())
+            .map(|m| match m.split_once('=').map(|(from, to)| (from.trim(), to.trim())) {
+                Some((from, to)) if !from.is_empty() && !to.is_empty() => Ok(PathMapping {
+                    from: from.trim_end_matches('/').to_string(),
+                    to: to.trim_end_matches('/').to_string(),
+                }),
+                _ => Err(format!("Invalid path mapping '{}', expected from=to", m)),
+            })
+            .collect()
+    }
+
+    /// `path` with this mapping's prefix replaced, if it starts with it
+    pub fn apply(&self, path: &str) -> Option<String> {
+        let rest = path.strip_prefix(self.from.as_str())?;
+        // only whole path components match, /src must not match /srcs
+        if !rest.is_empty() && !rest.starts_with('/') {
+            return None;
+        }
+        Some(format!("{}{}", self.to, rest))
+    }
+}
+
+/// Remap a plain path with the first matching mapping
+pub fn remap_path(path: &str, mappings: &[PathMapping]) -> String {
+    mappings.iter()
+        .find_map(|m| m.apply(path))
+        .unwrap_or_else(|| path.to_string())
+}
+
+/// Remap the path of a file:// URI; other schemes are left alone
+pub fn remap_uri(uri: &FileUri, mappings: &[PathMapping]) -> FileUri {
+    if uri.scheme() != Some("file") {
+        return uri.clone();
+    }
+    let path = uri.path();
+    match mappings.iter().find_map(|m| m.apply(&path)) {
+        Some(p) => FileUri::new(&format!("file://{}", uri::encode(&p))),
+        None => uri.clone(),
+    }
+}
+
+/// Remap a compile argument, either a path or a path flag with the path glued on, i.e. -I/ci/include
+pub fn remap_argument(arg: &str, mappings: &[PathMapping]) -> String {
+    for f in PATH_FLAGS {
+        if let Some(path) = arg.strip_prefix(f) {
+            if path.starts_with('/') {
+                return format!("{}{}", f, remap_path(path, mappings));
+            }
+        }
+    }
+    remap_path(arg, mappings)
+}
+
+fn remap_location(loc: &mut SymbolLocation, mappings: &[PathMapping]) {
+    loc.file_uri = remap_uri(&loc.file_uri, mappings);
+}
+
+impl ClangdFile {
+    /// Rewrite every file URI and directory in the file
+    pub fn remap_paths(&mut self, mappings: &[PathMapping]) {
+        if mappings.is_empty() {
+            return;
+        }
+        for s in self.symbols.data.iter_mut() {
+            remap_location(&mut s.definition, mappings);
+            remap_location(&mut s.canonical_declaration, mappings);
+            for h in s.headers.iter_mut() {
+                h.name = remap_uri(&h.name, mappings);
+            }
+        }
+        for r in self.references.data.iter_mut() {
+            for rr in r.refs.iter_mut() {
+                remap_location(&mut rr.location, mappings);
+            }
+        }
+        for s in self.sources.data.iter_mut() {
+            s.uri = remap_uri(&s.uri, mappings);
+            for i in s.direct_includes.iter_mut() {
+                *i = remap_uri(i, mappings);
+            }
+        }
+        for c in self.cmdline.data.iter_mut() {
+            c.directory = remap_path(&c.directory, mappings);
+            for a in c.cmdl.iter_mut() {
+                *a = remap_argument(a, mappings);
+            }
+        }
+    }
+}
@@ -0,0 +1,126 @@
+//! Queries across the whole index: name search, references, callers and callees, type hierarchy, templates and tests.
+
+use std::collections::BTreeMap;
+
+use crate::clangd::{ClangdDatabase, ClangdFileMap, ClangdIdMap, ClangdRefMap};
+use crate::refs::{RefKind, RefReferences};
+use crate::rela::RelationKind;
+use crate::symbols::{Symbol, SymbolId, SymbolLocation, SymbolProperty};
+
+// Container of references at file scope
+pub(crate) const NO_CONTAINER: SymbolId = [0; 8];
+
+/// Symbol id -> the locations of calls from or to it
+pub type CallMap = BTreeMap<SymbolId, Vec<SymbolLocation>>;
+
+/// Calls by caller and the class hierarchy both ways, so queries don't scan every ref or relation
+#[derive(Debug, Clone, Default)]
+pub struct QueryIndex {
+    // whether the index sets RefKind::Call; older ones don't
+    marks_calls: bool,
+    // caller -> callee -> call sites
+    callees: BTreeMap<SymbolId, CallMap>,
+    // derived -> direct bases, and base -> directly derived
+    bases: BTreeMap<SymbolId, Vec<SymbolId>>,
+    derived: BTreeMap<SymbolId, Vec<SymbolId>>,
+}
+
+impl QueryIndex {
+    pub fn build(files: &ClangdFileMap, id: &ClangdIdMap, refs: &ClangdRefMap) -> Self {
+        let mut q = QueryIndex {
+            marks_calls: refs.values().flatten().any(|r| r.has_kind(RefKind::Call)),
+            ..Default::default()
+        };
+        let mut callees: BTreeMap<SymbolId, CallMap> = BTreeMap::new();
+        for (target, rs) in refs.iter() {
+            if !id.get(target).is_some_and(|s| s.is_function()) {
+                continue;
+            }
+            for r in rs.iter().filter(|r| q.is_call(r)) {
+                callees.entry(r.container_id).or_default().entry(*target).or_default().push(r.location.clone());
+            }
+        }
+        q.callees = callees;
+        let mut relations: Vec<(SymbolId, SymbolId)> = files.values()
+            .flat_map(|f| f.relations.data.iter())
+            .filter(|r| r.predicate == RelationKind::BaseOf)
+            .map(|r| (r.subject, r.object))
+            .collect();
+        relations.sort();
+        relations.dedup();
+        for (base, derived) in relations {
+            q.bases.entry(derived).or_default().push(base);
+            q.derived.entry(base).or_default().push(derived);
+        }
+        q
+    }
+
+    /// Whether `r` is a call: RefKind::Call if the index sets it anywhere,
+    /// otherwise any reference from inside a function, which also counts taking its address
+    pub fn is_call(&self, r: &RefReferences) -> bool {
+        r.container_id != NO_CONTAINER && match self.marks_calls {
+            true => r.has_kind(RefKind::Call),
+            false => r.has_kind(RefKind::Reference),
+        }
+    }
+}
+
+impl ClangdDatabase {
+    /// Symbols whose qualified name contains `query`, ignoring case, sorted by name
+    pub fn search(&self, query: &str) -> Vec<&Symbol> {
+        let query = query.to_lowercase();
+        let mut v: Vec<&Symbol> = self.id.values()
+            .filter(|s| s.qualified_name().to_lowercase().contains(&query))
+            .collect();
+        v.sort_by_key(|s| s.qualified_name());
+        v
+    }
+
+    /// Every reference to `id`, including its declaration and definition
+    pub fn references(&self, id: &SymbolId) -> &[RefReferences] {
+        self.refs.get(id).map(|v| v.as_slice()).unwrap_or(&[])
+    }
+
+    /// Functions calling `id`, with the call sites
+    pub fn callers(&self, id: &SymbolId) -> CallMap {
+        let mut m: CallMap = BTreeMap::new();
+        for r in self.references(id).iter().filter(|r| self.queries.is_call(r)) {
+            m.entry(r.container_id).or_default().push(r.location.clone());
+        }
+        m
+    }
+
+    /// Functions called from `id`, with the call sites
+    pub fn callees(&self, id: &SymbolId) -> CallMap {
+        self.queries.callees.get(id).cloned().unwrap_or_default()
+    }
+
+    /// Direct base classes of `id`
+    pub fn supertypes(&self, id: &SymbolId) -> Vec<&Symbol> {
+        self.queries.bases.get(id).into_iter().flatten().filter_map(|b| self.id.get(b)).collect()
+    }
+
+    /// Classes deriving directly from `id`
+    pub fn subtypes(&self, id: &SymbolId) -> Vec<&Symbol> {
+        self.queries.derived.get(id).into_iter().flatten().filter_map(|d| self.id.get(d)).collect()
+    }
+
+    /// Full and partial specializations of the primary template `id`, sorted by their arguments
+    pub fn specializations(&self, id: &SymbolId) -> Vec<&Symbol> {
+        let Some(primary) = self.id.get(id) else { return vec![] };
+        let name = primary.qualified_name();
+        let mut v: Vec<&Symbol> = self.id.values()
+            .filter(|s| s.id != *id && s.qualified_name() == name)
+            .filter(|s| s.has_property(SymbolProperty::TemplateSpecialization) || s.has_property(SymbolProperty::TemplatePartialSpecialization))
+            .collect();
+        v.sort_by(|a, b| a.template_specialization_args.cmp(&b.template_specialization_args));
+        v
+    }
+
+    /// Symbols marked as unit tests, sorted by name; only GoogleTest tests are marked, see `symbols::derive_properties`
+    pub fn unit_tests(&self) -> Vec<&Symbol> {
+        let mut v: Vec<&Symbol> = self.id.values().filter(|s| s.has_property(SymbolProperty::UnitTest)).collect();
+        v.sort_by_key(|s| s.qualified_name());
+        v
+    }
+}
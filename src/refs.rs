@@ -1,16 +1,30 @@
-use crate::symbols::{SymbolId, SymbolLocation, SymbolKind};
+use crate::symbols::{SymbolId, SymbolLocation};
 use crate::clangd::ClangdUtility;
 
 use griff::ChunkStream;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq)]
+#[repr(u8)]
+pub enum RefKind {
+    #[default]
+    Unknown = 0,
+
+    Declaration = 1 << 0,
+    Definition = 1 << 1,
+    Reference = 1 << 2,
+    Spelled = 1 << 3,
+    Call = 1 << 4,
+}
+pub type RefKindSet = u8;
+
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct RefReferences {
-    pub kind: SymbolKind,
+    pub kind: RefKindSet,
     pub location: SymbolLocation,
     pub container_id: SymbolId,
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct Refs {
     pub id: SymbolId,
     pub cnt: usize,
@@ -18,13 +32,20 @@ pub struct Refs {
 }
 impl ClangdUtility for Refs{}
 
+impl RefReferences {
+    pub fn has_kind(&self, kind: RefKind) -> bool {
+        self.kind & (kind as u8) != 0
+    }
+}
+
 impl Refs {
     #[allow(dead_code)]
-    pub fn parse(buf: &ChunkStream, string_table: &Vec<String>) -> Vec<Refs> {
+    #[allow(clippy::field_reassign_with_default)]
+    pub fn parse(buf: &ChunkStream, string_table: &[String]) -> Vec<Refs> {
         let mut refs: Vec<Refs> = vec![];
         let mut cursor: usize = 0;
         let data = buf.data.as_slice();
-        if data.len() == 0 {
+        if data.is_empty() {
             return refs;
         }
 
@@ -36,9 +57,9 @@ impl Refs {
             cursor += sz;
             for _ in 0..content {
                 let mut rr: RefReferences = Default::default();
-                rr.kind = SymbolKind::from(data[cursor]);
+                rr.kind = data[cursor];
                 cursor += 1;
-                let (sz, loc) = SymbolLocation::get_location(&data.get(cursor..).unwrap(), string_table);
+                let (sz, loc) = SymbolLocation::get_location(data.get(cursor..).unwrap(), string_table);
                 rr.location = loc;
                 cursor += sz;
                 rr.container_id = data.get(cursor..cursor+8).unwrap().try_into().unwrap();
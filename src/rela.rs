@@ -3,7 +3,7 @@ use crate::symbols::SymbolId;
 
 use griff::ChunkStream;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord)]
 #[repr(u8)]
 pub enum RelationKind {
     #[default]
@@ -20,7 +20,7 @@ impl From<u8> for RelationKind {
     }
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Rela {
     pub subject: SymbolId,
     pub predicate: RelationKind,
@@ -30,11 +30,12 @@ impl ClangdUtility for Rela{}
 
 impl Rela {
     #[allow(dead_code)]
+    #[allow(clippy::field_reassign_with_default)]
     pub fn parse(buf: &ChunkStream) -> Vec<Rela> {
         let mut rela: Vec<Rela> = vec![];
         let mut cursor: usize = 0;
         let data = buf.data.as_slice();
-        if data.len() == 0 {
+        if data.is_empty() {
             return rela;
         }
 
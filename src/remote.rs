@@ -0,0 +1,560 @@
+//! Serve clangd's remote-index gRPC protocol from local shards, as clangd-index-server does.
+//! clangd connects with --remote-index-address=host:port --project-root=<its checkout>.
+// tonic's Status is large, but it is what every handler has to return
+#![allow(clippy::result_large_err)]
+
+use tonic::codec::ProstCodec;
+use tonic::codegen::{http, tokio_stream, Body, BoxFuture, BoxStream, Context, Poll, Service, StdError};
+use tonic::server::{Grpc, NamedService, ServerStreamingService};
+use tonic::transport::server::TcpIncoming;
+use tonic::{Code, Status};
+
+use std::convert::Infallible;
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::clangd::ClangdDatabase;
+use crate::rela::{Rela, RelationKind};
+use crate::symbols::{self, SymbolFlags, parse_symbol_id, symbol_id_str};
+
+/// Messages of clangd's index/remote/Index.proto, package clang.clangd.remote
+#[allow(clippy::large_enum_variant)]
+pub mod proto {
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct LookupRequest {
+        #[prost(string, repeated, tag = "1")]
+        pub ids: Vec<String>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct LookupReply {
+        #[prost(oneof = "LookupReplyKind", tags = "1, 2")]
+        pub kind: Option<LookupReplyKind>,
+    }
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum LookupReplyKind {
+        #[prost(message, tag = "1")]
+        StreamResult(Symbol),
+        #[prost(message, tag = "2")]
+        FinalResult(FinalResult),
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct FuzzyFindRequest {
+        #[prost(string, optional, tag = "1")]
+        pub query: Option<String>,
+        #[prost(string, repeated, tag = "2")]
+        pub scopes: Vec<String>,
+        #[prost(bool, optional, tag = "3")]
+        pub any_scope: Option<bool>,
+        #[prost(uint32, optional, tag = "4")]
+        pub limit: Option<u32>,
+        #[prost(bool, optional, tag = "5")]
+        pub restricted_for_code_completion: Option<bool>,
+        #[prost(string, repeated, tag = "6")]
+        pub proximity_paths: Vec<String>,
+        #[prost(string, repeated, tag = "7")]
+        pub preferred_types: Vec<String>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct FuzzyFindReply {
+        #[prost(oneof = "FuzzyFindReplyKind", tags = "1, 2")]
+        pub kind: Option<FuzzyFindReplyKind>,
+    }
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum FuzzyFindReplyKind {
+        #[prost(message, tag = "1")]
+        StreamResult(Symbol),
+        #[prost(message, tag = "2")]
+        FinalResult(FinalResult),
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct RefsRequest {
+        #[prost(string, repeated, tag = "1")]
+        pub ids: Vec<String>,
+        #[prost(uint32, optional, tag = "2")]
+        pub filter: Option<u32>,
+        #[prost(uint32, optional, tag = "3")]
+        pub limit: Option<u32>,
+        #[prost(bool, optional, tag = "4")]
+        pub want_container: Option<bool>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct RefsReply {
+        #[prost(oneof = "RefsReplyKind", tags = "1, 2")]
+        pub kind: Option<RefsReplyKind>,
+    }
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum RefsReplyKind {
+        #[prost(message, tag = "1")]
+        StreamResult(Ref),
+        #[prost(message, tag = "2")]
+        FinalResult(FinalResult),
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ContainedRefsRequest {
+        #[prost(string, required, tag = "1")]
+        pub id: String,
+        #[prost(uint32, optional, tag = "2")]
+        pub limit: Option<u32>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ContainedRefsReply {
+        #[prost(oneof = "ContainedRefsReplyKind", tags = "1, 2")]
+        pub kind: Option<ContainedRefsReplyKind>,
+    }
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum ContainedRefsReplyKind {
+        #[prost(message, tag = "1")]
+        StreamResult(ContainedRef),
+        #[prost(message, tag = "2")]
+        FinalResult(FinalResult),
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct RelationsRequest {
+        #[prost(string, repeated, tag = "1")]
+        pub subjects: Vec<String>,
+        #[prost(uint32, optional, tag = "2")]
+        pub predicate: Option<u32>,
+        #[prost(uint32, optional, tag = "3")]
+        pub limit: Option<u32>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct RelationsReply {
+        #[prost(oneof = "RelationsReplyKind", tags = "1, 2")]
+        pub kind: Option<RelationsReplyKind>,
+    }
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum RelationsReplyKind {
+        #[prost(message, tag = "1")]
+        StreamResult(Relation),
+        #[prost(message, tag = "2")]
+        FinalResult(FinalResult),
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct FinalResult {
+        #[prost(bool, optional, tag = "1")]
+        pub has_more: Option<bool>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Symbol {
+        #[prost(string, optional, tag = "1")]
+        pub id: Option<String>,
+        #[prost(message, optional, tag = "2")]
+        pub info: Option<SymbolInfo>,
+        #[prost(string, optional, tag = "3")]
+        pub name: Option<String>,
+        #[prost(message, optional, tag = "4")]
+        pub definition: Option<SymbolLocation>,
+        #[prost(string, optional, tag = "5")]
+        pub scope: Option<String>,
+        #[prost(message, optional, tag = "6")]
+        pub canonical_declaration: Option<SymbolLocation>,
+        #[prost(int32, optional, tag = "7")]
+        pub references: Option<i32>,
+        #[prost(uint32, optional, tag = "8")]
+        pub origin: Option<u32>,
+        #[prost(string, optional, tag = "9")]
+        pub signature: Option<String>,
+        #[prost(string, optional, tag = "10")]
+        pub template_specialization_args: Option<String>,
+        #[prost(string, optional, tag = "11")]
+        pub completion_snippet_suffix: Option<String>,
+        #[prost(string, optional, tag = "12")]
+        pub documentation: Option<String>,
+        #[prost(string, optional, tag = "13")]
+        pub return_type: Option<String>,
+        #[prost(string, optional, tag = "14")]
+        pub r#type: Option<String>,
+        #[prost(message, repeated, tag = "15")]
+        pub headers: Vec<HeaderWithReferences>,
+        #[prost(uint32, optional, tag = "16")]
+        pub flags: Option<u32>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Ref {
+        #[prost(message, optional, tag = "1")]
+        pub location: Option<SymbolLocation>,
+        #[prost(uint32, optional, tag = "2")]
+        pub kind: Option<u32>,
+        #[prost(string, optional, tag = "3")]
+        pub container: Option<String>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ContainedRef {
+        #[prost(message, required, tag = "1")]
+        pub location: SymbolLocation,
+        #[prost(uint32, required, tag = "2")]
+        pub kind: u32,
+        #[prost(string, required, tag = "3")]
+        pub symbol: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct SymbolInfo {
+        #[prost(uint32, optional, tag = "1")]
+        pub kind: Option<u32>,
+        #[prost(uint32, optional, tag = "2")]
+        pub subkind: Option<u32>,
+        #[prost(uint32, optional, tag = "3")]
+        pub language: Option<u32>,
+        #[prost(uint32, optional, tag = "4")]
+        pub properties: Option<u32>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct SymbolLocation {
+        #[prost(message, optional, tag = "1")]
+        pub start: Option<Position>,
+        #[prost(message, optional, tag = "2")]
+        pub end: Option<Position>,
+        // relative to the project root, each side maps it to its own checkout
+        #[prost(string, optional, tag = "3")]
+        pub file_path: Option<String>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Position {
+        #[prost(uint32, optional, tag = "1")]
+        pub line: Option<u32>,
+        #[prost(uint32, optional, tag = "2")]
+        pub column: Option<u32>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct HeaderWithReferences {
+        #[prost(string, optional, tag = "1")]
+        pub header: Option<String>,
+        #[prost(uint32, optional, tag = "2")]
+        pub references: Option<u32>,
+        #[prost(uint32, optional, tag = "3")]
+        pub supported_directives: Option<u32>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Relation {
+        #[prost(string, optional, tag = "1")]
+        pub subject_id: Option<String>,
+        #[prost(message, optional, tag = "2")]
+        pub object: Option<Symbol>,
+    }
+}
+
+/// The gRPC service name, from index/remote/Service.proto
+pub const SERVICE_NAME: &str = "clang.clangd.remote.v1.SymbolIndex";
+
+// clangd's RefKind::All, what clients ask for by default
+const ALL_REF_KINDS: u32 = 0b1111;
+
+/// Answers remote-index requests from a loaded database
+#[derive(Debug, Clone)]
+pub struct RemoteIndex {
+    db: ClangdDatabase,
+    // every shard's relations, collected once rather than per request
+    relations: BTreeSet<Rela>,
+    // paths are sent relative to this
+    root: PathBuf,
+}
+
+impl RemoteIndex {
+    pub fn new(db: ClangdDatabase, root: &Path) -> Self {
+        let relations = db.relations();
+        RemoteIndex { db, relations, root: root.to_path_buf() }
+    }
+
+    pub fn lookup(&self, req: proto::LookupRequest) -> Result<Vec<proto::LookupReply>, Status> {
+        use proto::LookupReplyKind::*;
+        let mut out: Vec<proto::LookupReply> = vec![];
+        for id in req.ids.iter() {
+            let id = symbol_id(id)?;
+            if let Some(s) = self.db.id.get(&id).and_then(|s| self.symbol(s)) {
+                out.push(proto::LookupReply { kind: Some(StreamResult(s)) });
+            }
+        }
+        out.push(proto::LookupReply { kind: Some(FinalResult(final_result(false))) });
+        Ok(out)
+    }
+
+    pub fn fuzzy_find(&self, req: proto::FuzzyFindRequest) -> Result<Vec<proto::FuzzyFindReply>, Status> {
+        use proto::FuzzyFindReplyKind::*;
+        let query = req.query.clone().unwrap_or_default();
+        let any_scope = req.any_scope.unwrap_or(false);
+        let completion = req.restricted_for_code_completion.unwrap_or(false);
+        let mut matches: Vec<&symbols::Symbol> = self.db.id.values()
+            .filter(|s| any_scope || req.scopes.contains(&s.scope))
+            .filter(|s| !completion || s.has_flag(SymbolFlags::IndexedForCodeCompletion))
+            .filter(|s| fuzzy_match(&query, &s.name))
+            .collect();
+        // most referenced first, as a stand-in for clangd's ranking
+        matches.sort_by(|a, b| b.references.cmp(&a.references).then(a.qualified_name().cmp(&b.qualified_name())));
+
+        let limit = req.limit.map(|l| l as usize).unwrap_or(usize::MAX);
+        let mut out: Vec<proto::FuzzyFindReply> = matches.iter()
+            .filter_map(|s| self.symbol(s))
+            .take(limit)
+            .map(|s| proto::FuzzyFindReply { kind: Some(StreamResult(s)) })
+            .collect();
+        let has_more = matches.len() > out.len() && out.len() == limit;
+        out.push(proto::FuzzyFindReply { kind: Some(FinalResult(final_result(has_more))) });
+        Ok(out)
+    }
+
+    pub fn refs(&self, req: proto::RefsRequest) -> Result<Vec<proto::RefsReply>, Status> {
+        use proto::RefsReplyKind::*;
+        let filter = req.filter.unwrap_or(ALL_REF_KINDS);
+        let limit = req.limit.map(|l| l as usize).unwrap_or(usize::MAX);
+        let want_container = req.want_container.unwrap_or(false);
+        let mut out: Vec<proto::RefsReply> = vec![];
+        let mut has_more = false;
+        // ids past a full result are left unparsed
+        'ids: for id in req.ids.iter() {
+            let id = symbol_id(id)?;
+            for r in self.db.references(&id).iter().filter(|r| r.kind as u32 & filter != 0) {
+                let location = match self.location(&r.location) {
+                    Some(l) => l,
+                    None => continue,
+                };
+                if out.len() == limit {
+                    has_more = true;
+                    break 'ids;
+                }
+                let container = Some(symbol_id_str(&r.container_id)).filter(|_| want_container);
+                out.push(proto::RefsReply { kind: Some(StreamResult(proto::Ref {
+                    location: Some(location),
+                    kind: Some(r.kind as u32),
+                    container,
+                })) });
+            }
+        }
+        out.push(proto::RefsReply { kind: Some(FinalResult(final_result(has_more))) });
+        Ok(out)
+    }
+
+    pub fn contained_refs(&self, req: proto::ContainedRefsRequest) -> Result<Vec<proto::ContainedRefsReply>, Status> {
+        use proto::ContainedRefsReplyKind::*;
+        let id = symbol_id(&req.id)?;
+        let limit = req.limit.map(|l| l as usize).unwrap_or(usize::MAX);
+        let mut out: Vec<proto::ContainedRefsReply> = vec![];
+        let mut has_more = false;
+        'targets: for (target, refs) in self.db.refs.iter() {
+            for r in refs.iter().filter(|r| r.container_id == id) {
+                let location = match self.location(&r.location) {
+                    Some(l) => l,
+                    None => continue,
+                };
+                if out.len() == limit {
+                    has_more = true;
+                    break 'targets;
+                }
+                out.push(proto::ContainedRefsReply { kind: Some(StreamResult(proto::ContainedRef {
+                    location,
+                    kind: r.kind as u32,
+                    symbol: symbol_id_str(target),
+                })) });
+            }
+        }
+        out.push(proto::ContainedRefsReply { kind: Some(FinalResult(final_result(has_more))) });
+        Ok(out)
+    }
+
+    pub fn relations(&self, req: proto::RelationsRequest) -> Result<Vec<proto::RelationsReply>, Status> {
+        use proto::RelationsReplyKind::*;
+        let predicate = match req.predicate {
+            Some(p) => RelationKind::from(p as u8),
+            None => return Err(Status::invalid_argument("Missing relation predicate")),
+        };
+        let limit = req.limit.map(|l| l as usize).unwrap_or(usize::MAX);
+        let subjects = req.subjects.iter().map(|s| symbol_id(s)).collect::<Result<Vec<symbols::SymbolId>, Status>>()?;
+        let mut out: Vec<proto::RelationsReply> = vec![];
+        let mut has_more = false;
+        for r in self.relations.iter().filter(|r| r.predicate == predicate && subjects.contains(&r.subject)) {
+            let object = match self.db.id.get(&r.object).and_then(|s| self.symbol(s)) {
+                Some(o) => o,
+                None => continue,
+            };
+            if out.len() == limit {
+                has_more = true;
+                break;
+            }
+            out.push(proto::RelationsReply { kind: Some(StreamResult(proto::Relation {
+                subject_id: Some(symbol_id_str(&r.subject)),
+                object: Some(object),
+            })) });
+        }
+        out.push(proto::RelationsReply { kind: Some(FinalResult(final_result(has_more))) });
+        Ok(out)
+    }
+
+    // None if the symbol lies outside the project root, like clangd-index-server
+    fn symbol(&self, s: &symbols::Symbol) -> Option<proto::Symbol> {
+        let definition = match s.definition.file_uri.is_empty() {
+            true => None,
+            false => Some(self.location(&s.definition)?),
+        };
+        Some(proto::Symbol {
+            id: Some(symbol_id_str(&s.id)),
+            info: Some(proto::SymbolInfo {
+                kind: Some(s.syminfo.kind.clone() as u32),
+                subkind: Some(s.syminfo.subkind.clone() as u32),
+                language: Some(s.syminfo.lang.clone() as u32),
+                properties: Some(s.syminfo.properties as u32),
+            }),
+            name: Some(s.name.clone()),
+            definition,
+            scope: Some(s.scope.clone()),
+            canonical_declaration: Some(self.location(&s.canonical_declaration)?),
+            references: Some(s.references as i32),
+            origin: Some(s.origin.clone() as u32),
+            signature: Some(s.signature.clone()),
+            template_specialization_args: Some(s.template_specialization_args.clone()),
+            completion_snippet_suffix: Some(s.completion_snippet_suffix.clone()),
+            documentation: Some(s.documentation.clone()),
+            return_type: Some(s.return_t.clone()),
+            r#type: Some(s.t.clone()),
+            headers: s.headers.iter()
+                .filter_map(|h| Some(proto::HeaderWithReferences {
+                    header: Some(self.header(&h.name)?),
+                    references: Some(h.refs as u32),
+                    supported_directives: Some(h.supported_directives as u32),
+                }))
+                .collect(),
+            flags: Some(s.flags as u32),
+        })
+    }
+
+    fn location(&self, loc: &symbols::SymbolLocation) -> Option<proto::SymbolLocation> {
+        let path = loc.file_uri.relative_to(&self.root)?;
+        let position = |p: &symbols::SymbolPosition| proto::Position { line: Some(p.line), column: Some(p.column) };
+        Some(proto::SymbolLocation {
+            start: Some(position(&loc.start)),
+            end: Some(position(&loc.end)),
+            file_path: Some(path.to_string_lossy().replace('\\', "/")),
+        })
+    }
+
+    // <stdio.h> and "foo.h" are sent as is, URIs as paths relative to the root
+    fn header(&self, h: &crate::uri::FileUri) -> Option<String> {
+        let s = h.as_str();
+        if s.starts_with('<') || s.starts_with('"') {
+            return Some(s.to_string());
+        }
+        Some(h.relative_to(&self.root)?.to_string_lossy().replace('\\', "/"))
+    }
+}
+
+fn symbol_id(s: &str) -> Result<symbols::SymbolId, Status> {
+    parse_symbol_id(s).ok_or_else(|| Status::invalid_argument(format!("Invalid symbol id {}", s)))
+}
+
+fn final_result(has_more: bool) -> proto::FinalResult {
+    proto::FinalResult { has_more: Some(has_more) }
+}
+
+// Every character of `query` appears in `name` in order, ignoring case
+fn fuzzy_match(query: &str, name: &str) -> bool {
+    let mut chars = name.chars().flat_map(|c| c.to_lowercase());
+    query.chars()
+        .flat_map(|c| c.to_lowercase())
+        .all(|q| chars.any(|c| c == q))
+}
+
+/// tower service routing gRPC calls to a `RemoteIndex`
+#[derive(Debug, Clone)]
+pub struct RemoteIndexService {
+    index: Arc<RemoteIndex>,
+}
+impl RemoteIndexService {
+    pub fn new(index: RemoteIndex) -> Self {
+        RemoteIndexService { index: Arc::new(index) }
+    }
+}
+
+type Handler<Req, Reply> = fn(&RemoteIndex, Req) -> Result<Vec<Reply>, Status>;
+
+// One server-streaming method, replying with everything `handler` returns
+struct Method<Req, Reply> {
+    index: Arc<RemoteIndex>,
+    handler: Handler<Req, Reply>,
+}
+impl<Req: Send + 'static, Reply: Send + 'static> ServerStreamingService<Req> for Method<Req, Reply> {
+    type Response = Reply;
+    type ResponseStream = BoxStream<Reply>;
+    type Future = BoxFuture<tonic::Response<Self::ResponseStream>, Status>;
+
+    fn call(&mut self, req: tonic::Request<Req>) -> Self::Future {
+        let res = (self.handler)(&self.index, req.into_inner())
+            .map(|v| tonic::Response::new(Box::pin(tokio_stream::iter(v.into_iter().map(Ok))) as BoxStream<Reply>));
+        Box::pin(std::future::ready(res))
+    }
+}
+
+fn stream<Req, Reply, B>(req: http::Request<B>, index: Arc<RemoteIndex>, handler: Handler<Req, Reply>) -> BoxFuture<http::Response<tonic::body::BoxBody>, Infallible>
+where
+    Req: prost::Message + Default + Send + 'static,
+    Reply: prost::Message + Send + 'static,
+    B: Body + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+{
+    Box::pin(async move {
+        let mut grpc = Grpc::new(ProstCodec::default());
+        Ok(grpc.server_streaming(Method { index, handler }, req).await)
+    })
+}
+
+impl<B> Service<http::Request<B>> for RemoteIndexService
+where
+    B: Body + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+{
+    type Response = http::Response<tonic::body::BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        let index = self.index.clone();
+        match req.uri().path().strip_prefix(&format!("/{}/", SERVICE_NAME)) {
+            Some("Lookup") => stream(req, index, RemoteIndex::lookup),
+            Some("FuzzyFind") => stream(req, index, RemoteIndex::fuzzy_find),
+            Some("Refs") => stream(req, index, RemoteIndex::refs),
+            Some("ContainedRefs") => stream(req, index, RemoteIndex::contained_refs),
+            Some("Relations") => stream(req, index, RemoteIndex::relations),
+            _ => Box::pin(async move {
+                let mut resp = http::Response::new(tonic::codegen::empty_body());
+                resp.headers_mut().insert(Status::GRPC_STATUS, (Code::Unimplemented as i32).into());
+                resp.headers_mut().insert(http::header::CONTENT_TYPE, tonic::metadata::GRPC_CONTENT_TYPE);
+                Ok(resp)
+            }),
+        }
+    }
+}
+
+impl NamedService for RemoteIndexService {
+    const NAME: &'static str = SERVICE_NAME;
+}
+
+/// Serve `index` to clients connecting on `listener`, until the process is killed
+pub async fn serve(index: RemoteIndex, listener: tokio::net::TcpListener) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let incoming = TcpIncoming::from_listener(listener, true, None)?;
+    tonic::transport::Server::builder()
+        .add_service(RemoteIndexService::new(index))
+        .serve_with_incoming(incoming)
+        .await?;
+    Ok(())
+}
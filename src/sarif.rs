@@ -0,0 +1,183 @@
+//! SARIF 2.1.0 output for analysis findings.
+
+use serde::Serialize;
+
+use std::fmt;
+
+use crate::symbols::SymbolLocation;
+
+pub const SARIF_VERSION: &str = "2.1.0";
+pub const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Error,
+    Warning,
+    Note,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    pub id: &'static str,
+    pub description: &'static str,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub rule: &'static str,
+    pub level: Level,
+    pub message: String,
+    pub location: SymbolLocation,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.location.start == self.location.end {
+            write!(f, "{}: ", self.location.file_uri)?;
+        }
+        else {
+            write!(f, "{}:{}:{}: ", self.location.file_uri, self.location.start.line + 1, self.location.start.column + 1)?;
+        }
+        write!(f, "{:?}: {} [{}]", self.level, self.message, self.rule)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Sarif {
+    #[serde(rename = "$schema")]
+    pub schema: &'static str,
+    pub version: &'static str,
+    pub runs: Vec<Run>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Run {
+    pub tool: Tool,
+    pub results: Vec<SarifResult>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Tool {
+    pub driver: Driver,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Driver {
+    pub name: &'static str,
+    pub version: &'static str,
+    pub information_uri: &'static str,
+    pub rules: Vec<ReportingDescriptor>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReportingDescriptor {
+    pub id: &'static str,
+    pub short_description: Message,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Message {
+    pub text: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SarifResult {
+    pub rule_id: &'static str,
+    // omitted for a rule the run doesn't describe
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rule_index: Option<usize>,
+    pub level: Level,
+    pub message: Message,
+    pub locations: Vec<Location>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Location {
+    pub physical_location: PhysicalLocation,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PhysicalLocation {
+    pub artifact_location: ArtifactLocation,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub region: Option<Region>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ArtifactLocation {
+    pub uri: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Region {
+    pub start_line: u32,
+    pub start_column: u32,
+    pub end_line: u32,
+    pub end_column: u32,
+}
+impl Region {
+    /// Findings about a whole file carry an empty range and get no region
+    pub fn of(loc: &SymbolLocation) -> Option<Self> {
+        if loc.start == loc.end {
+            return None;
+        }
+        Some(Region::from(loc))
+    }
+}
+impl From<&SymbolLocation> for Region {
+    // clangd positions are 0-based, SARIF regions are 1-based
+    fn from(loc: &SymbolLocation) -> Self {
+        Region {
+            start_line: loc.start.line + 1,
+            start_column: loc.start.column + 1,
+            end_line: loc.end.line + 1,
+            end_column: loc.end.column + 1,
+        }
+    }
+}
+
+impl Sarif {
+    /// A single run of this tool, reporting `findings` against `rules`
+    pub fn new(rules: &[Rule], findings: &[Finding]) -> Self {
+        let results = findings.iter()
+            .map(|f| SarifResult {
+                rule_id: f.rule,
+                rule_index: rules.iter().position(|r| r.id == f.rule),
+                level: f.level.clone(),
+                message: Message { text: f.message.clone() },
+                locations: vec![Location {
+                    physical_location: PhysicalLocation {
+                        artifact_location: ArtifactLocation { uri: f.location.file_uri.to_string() },
+                        region: Region::of(&f.location),
+                    },
+                }],
+            })
+            .collect();
+        let rules = rules.iter()
+            .map(|r| ReportingDescriptor { id: r.id, short_description: Message { text: r.description.to_string() } })
+            .collect();
+
+        Sarif {
+            schema: SARIF_SCHEMA,
+            version: SARIF_VERSION,
+            runs: vec![Run {
+                tool: Tool {
+                    driver: Driver {
+                        name: env!("CARGO_PKG_NAME"),
+                        version: env!("CARGO_PKG_VERSION"),
+                        information_uri: env!("CARGO_PKG_REPOSITORY"),
+                        rules,
+                    },
+                },
+                results,
+            }],
+        }
+    }
+}
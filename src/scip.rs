@@ -0,0 +1,360 @@
+//! Export the index in SCIP, the protobuf format read by Sourcegraph and other code-intelligence tools.
+
+use prost::Message;
+
+use std::io::{self, Write};
+use std::path::Path;
+use std::collections::BTreeMap;
+
+use crate::clangd::ClangdDatabase;
+use crate::lsif::{hover_text, language_id};
+use crate::refs::RefKind;
+use crate::rela::RelationKind;
+use crate::symbols::{Symbol, SymbolFlags, SymbolKind, SymbolLocation, symbol_id_str};
+use crate::uri::FileUri;
+
+/// Messages of scip.proto, package scip
+#[allow(clippy::enum_variant_names)]
+pub mod proto {
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Index {
+        #[prost(message, optional, tag = "1")]
+        pub metadata: Option<Metadata>,
+        #[prost(message, repeated, tag = "2")]
+        pub documents: Vec<Document>,
+        #[prost(message, repeated, tag = "3")]
+        pub external_symbols: Vec<SymbolInformation>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Metadata {
+        #[prost(enumeration = "ProtocolVersion", tag = "1")]
+        pub version: i32,
+        #[prost(message, optional, tag = "2")]
+        pub tool_info: Option<ToolInfo>,
+        #[prost(string, tag = "3")]
+        pub project_root: String,
+        #[prost(enumeration = "TextEncoding", tag = "4")]
+        pub text_document_encoding: i32,
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+    #[repr(i32)]
+    pub enum ProtocolVersion {
+        UnspecifiedProtocolVersion = 0,
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+    #[repr(i32)]
+    pub enum TextEncoding {
+        UnspecifiedTextEncoding = 0,
+        Utf8 = 1,
+        Utf16 = 2,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ToolInfo {
+        #[prost(string, tag = "1")]
+        pub name: String,
+        #[prost(string, tag = "2")]
+        pub version: String,
+        #[prost(string, repeated, tag = "3")]
+        pub arguments: Vec<String>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Document {
+        #[prost(string, tag = "4")]
+        pub language: String,
+        #[prost(string, tag = "1")]
+        pub relative_path: String,
+        #[prost(message, repeated, tag = "2")]
+        pub occurrences: Vec<Occurrence>,
+        #[prost(message, repeated, tag = "3")]
+        pub symbols: Vec<SymbolInformation>,
+        #[prost(string, tag = "5")]
+        pub text: String,
+        #[prost(enumeration = "PositionEncoding", tag = "6")]
+        pub position_encoding: i32,
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+    #[repr(i32)]
+    pub enum PositionEncoding {
+        UnspecifiedPositionEncoding = 0,
+        Utf8CodeUnitOffsetFromLineStart = 1,
+        Utf16CodeUnitOffsetFromLineStart = 2,
+        Utf32CodeUnitOffsetFromLineStart = 3,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct SymbolInformation {
+        #[prost(string, tag = "1")]
+        pub symbol: String,
+        #[prost(string, repeated, tag = "3")]
+        pub documentation: Vec<String>,
+        #[prost(message, repeated, tag = "4")]
+        pub relationships: Vec<Relationship>,
+        #[prost(enumeration = "Kind", tag = "5")]
+        pub kind: i32,
+        #[prost(string, tag = "6")]
+        pub display_name: String,
+        #[prost(string, tag = "8")]
+        pub enclosing_symbol: String,
+    }
+
+    /// The subset of SymbolInformation.Kind that clangd's symbol kinds map to
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+    #[repr(i32)]
+    pub enum Kind {
+        UnspecifiedKind = 0,
+        Class = 7,
+        Constructor = 9,
+        Enum = 11,
+        EnumMember = 12,
+        Field = 15,
+        Function = 17,
+        Macro = 25,
+        Method = 26,
+        Module = 29,
+        Namespace = 30,
+        Parameter = 37,
+        Property = 41,
+        Protocol = 42,
+        Struct = 49,
+        TypeAlias = 55,
+        TypeParameter = 58,
+        Union = 59,
+        Variable = 61,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Relationship {
+        #[prost(string, tag = "1")]
+        pub symbol: String,
+        #[prost(bool, tag = "2")]
+        pub is_reference: bool,
+        #[prost(bool, tag = "3")]
+        pub is_implementation: bool,
+        #[prost(bool, tag = "4")]
+        pub is_type_definition: bool,
+        #[prost(bool, tag = "5")]
+        pub is_definition: bool,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Occurrence {
+        // [start line, start column, end line, end column], end line left out when on the start line
+        #[prost(int32, repeated, tag = "1")]
+        pub range: Vec<i32>,
+        #[prost(string, tag = "2")]
+        pub symbol: String,
+        #[prost(int32, tag = "3")]
+        pub symbol_roles: i32,
+    }
+
+    /// Bits of Occurrence.symbol_roles
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+    #[repr(i32)]
+    pub enum SymbolRole {
+        UnspecifiedSymbolRole = 0,
+        Definition = 0x1,
+        Import = 0x2,
+        WriteAccess = 0x4,
+        ReadAccess = 0x8,
+        Generated = 0x10,
+        Test = 0x20,
+        ForwardDefinition = 0x40,
+    }
+}
+
+// scheme, then package manager, name and version, which clangd doesn't know
+const SYMBOL_PREFIX: &str = "cxx . . . ";
+
+impl From<&SymbolKind> for proto::Kind {
+    fn from(k: &SymbolKind) -> Self {
+        use SymbolKind::*;
+        match k {
+            Unknown => proto::Kind::UnspecifiedKind,
+            Module => proto::Kind::Module,
+            Namespace | NamespaceAlias | Using => proto::Kind::Namespace,
+            Macro => proto::Kind::Macro,
+            Enum => proto::Kind::Enum,
+            Struct => proto::Kind::Struct,
+            Class => proto::Kind::Class,
+            Protocol | Extension => proto::Kind::Protocol,
+            Union => proto::Kind::Union,
+            TypeAlias => proto::Kind::TypeAlias,
+            Function => proto::Kind::Function,
+            Variable => proto::Kind::Variable,
+            Field => proto::Kind::Field,
+            EnumConstant => proto::Kind::EnumMember,
+            InstanceMethod | ClassMethod | StaticMethod | Destructor | ConversionFunction => proto::Kind::Method,
+            InstanceProperty | ClassProperty | StaticProperty => proto::Kind::Property,
+            Constructor => proto::Kind::Constructor,
+            Parameter => proto::Kind::Parameter,
+            TemplateTypeParm | TemplateTemplateParm | NonTypeTemplateParm => proto::Kind::TypeParameter,
+        }
+    }
+}
+
+// Names that aren't plain identifiers are quoted in backticks
+fn escape(name: &str) -> String {
+    if !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || "_+-$".contains(c)) {
+        return name.to_string();
+    }
+    format!("`{}`", name.replace('`', "``"))
+}
+
+// Symbols by qualified name, to tell which scopes are types and find enclosing symbols
+fn qualified(db: &ClangdDatabase) -> BTreeMap<String, &Symbol> {
+    db.id.values().map(|s| (s.qualified_name(), s)).collect()
+}
+
+/// SCIP symbol string for `s`, i.e. `cxx . . . ns/Shape#area(0123ABCD).`.
+/// Functions carry their SymbolId so overloads stay apart; symbols not visible outside their file,
+/// i.e. statics and locals, are document-local `local 0123ABCD` ones.
+pub fn symbol_string(db: &ClangdDatabase, s: &Symbol) -> String {
+    scip_symbol(&qualified(db), s)
+}
+
+fn scip_symbol(qualified: &BTreeMap<String, &Symbol>, s: &Symbol) -> String {
+    use SymbolKind::*;
+    if !s.has_flag(SymbolFlags::VisibleOutsideFile) {
+        return format!("local {}", symbol_id_str(&s.id));
+    }
+    let mut out = SYMBOL_PREFIX.to_string();
+    // each scope is a type if the index knows it as one, a namespace otherwise
+    let mut scope = String::new();
+    for part in s.scope.split("::").filter(|p| !p.is_empty()) {
+        scope.push_str(part);
+        let is_type = qualified.get(&scope).is_some_and(|t| matches!(t.syminfo.kind, Class | Struct | Union | Enum));
+        out.push_str(&escape(part));
+        out.push(if is_type { '#' } else { '/' });
+        scope.push_str("::");
+    }
+    let name = escape(&s.name);
+    match s.syminfo.kind {
+        Namespace | NamespaceAlias | Module => out.push_str(&format!("{}/", name)),
+        Class | Struct | Union | Enum | TypeAlias | Protocol | Extension => out.push_str(&format!("{}#", name)),
+        Macro => out.push_str(&format!("{}!", name)),
+        TemplateTypeParm | TemplateTemplateParm | NonTypeTemplateParm => out.push_str(&format!("[{}]", name)),
+        Parameter => out.push_str(&format!("({})", name)),
+        _ if s.is_function() => out.push_str(&format!("{}({}).", name, symbol_id_str(&s.id))),
+        _ => out.push_str(&format!("{}.", name)),
+    }
+    out
+}
+
+fn range(loc: &SymbolLocation) -> Vec<i32> {
+    let (sl, sc, el, ec) = (loc.start.line as i32, loc.start.column as i32, loc.end.line as i32, loc.end.column as i32);
+    if sl == el { vec![sl, sc, ec] } else { vec![sl, sc, el, ec] }
+}
+
+fn roles(kind: u8) -> i32 {
+    use proto::SymbolRole;
+    let mut r = 0;
+    if kind & RefKind::Definition as u8 != 0 {
+        r |= SymbolRole::Definition as i32;
+    }
+    else if kind & RefKind::Declaration as u8 != 0 {
+        r |= SymbolRole::ForwardDefinition as i32;
+    }
+    r
+}
+
+// The document holding `uri`, if it is under `root`
+fn document<'a>(docs: &'a mut BTreeMap<String, proto::Document>, root: &Path, uri: &FileUri) -> Option<&'a mut proto::Document> {
+    let path = uri.relative_to(root)?.to_string_lossy().replace('\\', "/");
+    Some(docs.entry(path.clone()).or_insert_with(|| proto::Document {
+        language: language_id(uri).to_uppercase(),
+        relative_path: path,
+        position_encoding: proto::PositionEncoding::Utf16CodeUnitOffsetFromLineStart as i32,
+        ..Default::default()
+    }))
+}
+
+/// Build a SCIP index of `db`; documents are named relative to `root`, anything outside it is external
+pub fn index(db: &ClangdDatabase, root: &Path) -> proto::Index {
+    let mut ix = proto::Index { metadata: Some(proto::Metadata {
+        version: proto::ProtocolVersion::UnspecifiedProtocolVersion as i32,
+        tool_info: Some(proto::ToolInfo {
+            name: env!("CARGO_PKG_NAME").to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            arguments: vec![],
+        }),
+        project_root: FileUri::from_path(root).to_string(),
+        text_document_encoding: proto::TextEncoding::Utf8 as i32,
+    }), ..Default::default() };
+
+    let qualified = qualified(db);
+    let names: BTreeMap<_, String> = db.id.iter().map(|(id, s)| (*id, scip_symbol(&qualified, s))).collect();
+    let mut docs: BTreeMap<String, proto::Document> = BTreeMap::new();
+    // base or overridden symbols of each derived or overriding one
+    let mut relationships: BTreeMap<_, Vec<proto::Relationship>> = BTreeMap::new();
+    for r in db.relations().iter() {
+        let (Some(base), true) = (names.get(&r.subject), names.contains_key(&r.object)) else { continue };
+        relationships.entry(r.object).or_default().push(proto::Relationship {
+            symbol: base.clone(),
+            is_reference: r.predicate == RelationKind::OverriddenBy,
+            is_implementation: true,
+            ..Default::default()
+        });
+    }
+
+    for (id, s) in db.id.iter() {
+        let symbol = names[id].clone();
+        let mut documentation: Vec<String> = vec![];
+        let signature = hover_text(s);
+        if !signature.is_empty() {
+            documentation.push(format!("```{}\n{}\n```", language_id(&s.location().file_uri), signature));
+        }
+        if !s.documentation.is_empty() {
+            documentation.push(s.documentation.clone());
+        }
+        let enclosing_symbol = s.scope.strip_suffix("::")
+            .and_then(|scope| qualified.get(scope))
+            .map(|p| names[&p.id].clone())
+            .unwrap_or_default();
+        let info = proto::SymbolInformation {
+            symbol: symbol.clone(),
+            documentation,
+            relationships: relationships.remove(id).unwrap_or_default(),
+            kind: proto::Kind::from(&s.syminfo.kind) as i32,
+            display_name: s.name.clone(),
+            enclosing_symbol,
+        };
+        match document(&mut docs, root, &s.location().file_uri) {
+            Some(d) => d.symbols.push(info),
+            None if s.has_flag(SymbolFlags::VisibleOutsideFile) => ix.external_symbols.push(info),
+            // a local symbol outside the root has no document to live in
+            None => (),
+        }
+
+        // the definition and declaration are usually among the refs too
+        let mut occurrences: Vec<(&SymbolLocation, i32)> = db.references(id).iter()
+            .map(|r| (&r.location, roles(r.kind)))
+            .collect();
+        for (loc, role) in [(&s.definition, proto::SymbolRole::Definition), (&s.canonical_declaration, proto::SymbolRole::ForwardDefinition)] {
+            if !loc.file_uri.is_empty() && !occurrences.iter().any(|(l, _)| *l == loc) {
+                occurrences.push((loc, role as i32));
+            }
+        }
+        for (loc, symbol_roles) in occurrences {
+            if let Some(d) = document(&mut docs, root, &loc.file_uri) {
+                d.occurrences.push(proto::Occurrence { range: range(loc), symbol: symbol.clone(), symbol_roles });
+            }
+        }
+    }
+
+    for d in docs.values_mut() {
+        d.occurrences.sort_by(|a, b| a.range.cmp(&b.range).then(a.symbol.cmp(&b.symbol)));
+    }
+    ix.documents = docs.into_values().collect();
+    ix
+}
+
+/// Write a SCIP index of `db` to `out`
+pub fn export<W: Write>(db: &ClangdDatabase, root: &Path, out: &mut W) -> io::Result<()> {
+    out.write_all(&index(db, root).encode_to_vec())
+}
@@ -0,0 +1,251 @@
+//! Local HTTP server answering JSON queries from a loaded index, reloading it as clangd rewrites shards.
+//!
+//! GET /symbols?q=name&limit=N          symbols whose qualified name contains `q`
+//! GET /symbols/ID                      a symbol by its hex id
+//! GET /symbols/ID/refs                 references to it
+//! GET /symbols/ID/callers              functions calling it, with the call sites
+//! GET /symbols/ID/callees              functions it calls, with the call sites
+//! GET /symbols/ID/supertypes           direct base classes
+//! GET /symbols/ID/subtypes             classes deriving directly from it
+//! GET /symbol-at?file=F&line=L&column=C  symbol at a 0-based position
+//! GET /includes?file=F                 files F includes and is included by; every edge without `file`
+//! GET /compile-commands?file=F         compile commands, all of them without `file`
+
+use serde::Serialize;
+use serde_json::{json, Value};
+use tiny_http::{Header, Method, Response, Server};
+
+use std::error::Error;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::clangd::ClangdDatabase;
+use crate::compdb::{self, CompileCommandOptions};
+use crate::paths::PathMapping;
+use crate::query::CallMap;
+use crate::refs::{RefKind, RefReferences};
+use crate::symbols::{Symbol, SymbolLocation, parse_symbol_id, symbol_id_str};
+use crate::uri::{self, FileUri};
+use crate::watch::IndexWatcher;
+
+const DEFAULT_LIMIT: usize = 100;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SymbolJson {
+    pub id: String,
+    pub name: String,
+    pub scope: String,
+    pub kind: String,
+    pub signature: String,
+    pub return_type: String,
+    #[serde(rename = "type")]
+    pub t: String,
+    pub documentation: String,
+    pub definition: Option<SymbolLocation>,
+    pub declaration: Option<SymbolLocation>,
+    pub references: u32,
+}
+impl From<&Symbol> for SymbolJson {
+    fn from(s: &Symbol) -> Self {
+        let loc = |l: &SymbolLocation| Some(l.clone()).filter(|l| !l.file_uri.is_empty());
+        SymbolJson {
+            id: symbol_id_str(&s.id),
+            name: s.name.clone(),
+            scope: s.scope.clone(),
+            kind: format!("{:?}", s.syminfo.kind),
+            signature: s.signature.clone(),
+            return_type: s.return_t.clone(),
+            t: s.t.clone(),
+            documentation: s.documentation.clone(),
+            definition: loc(&s.definition),
+            declaration: loc(&s.canonical_declaration),
+            references: s.references,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RefJson {
+    pub kinds: Vec<&'static str>,
+    pub location: SymbolLocation,
+    pub container: String,
+}
+impl From<&RefReferences> for RefJson {
+    fn from(r: &RefReferences) -> Self {
+        let kinds = [
+            (RefKind::Declaration, "declaration"),
+            (RefKind::Definition, "definition"),
+            (RefKind::Reference, "reference"),
+            (RefKind::Spelled, "spelled"),
+            (RefKind::Call, "call"),
+        ];
+        RefJson {
+            kinds: kinds.into_iter().filter(|(k, _)| r.has_kind(k.clone())).map(|(_, n)| n).collect(),
+            location: r.location.clone(),
+            container: symbol_id_str(&r.container_id),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CallJson {
+    pub symbol: SymbolJson,
+    pub sites: Vec<SymbolLocation>,
+}
+
+// HTTP status and message of a failed query
+type QueryError = (u16, String);
+
+/// Serve queries on `addr` until the process is killed.
+/// With `reload`, the index is polled at that interval and rebuilt once any shard changed.
+/// A shard that fails to parse, i.e. one clangd is still writing, keeps its previous version until the next poll.
+pub fn serve(root: &Path, mappings: &[PathMapping], addr: &str, reload: Option<Duration>) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut watcher = match reload {
+        Some(_) => Some(IndexWatcher::with_mappings(root, mappings)?),
+        None => None,
+    };
+    let mut db = match watcher.as_ref() {
+        Some(w) => w.database(),
+        None => crate::load(root, mappings)?,
+    };
+    let server = Server::http(addr)?;
+    eprintln!("Listening on http://{}", server.server_addr());
+
+    let interval = reload.unwrap_or(Duration::from_secs(1));
+    let mut polled = Instant::now();
+    loop {
+        if let Some(w) = watcher.as_mut() {
+            if polled.elapsed() >= interval {
+                polled = Instant::now();
+                let changed = !w.poll().is_empty();
+                for f in w.failures() {
+                    eprintln!("Unable to reload {}: {}", f.path.display(), f.error);
+                }
+                if changed {
+                    // drop the old index first so a reload never holds two
+                    drop(db);
+                    db = w.database();
+                    eprintln!("Index changed, reloaded {} symbols", db.id.len());
+                }
+            }
+        }
+        let req = match server.recv_timeout(interval)? {
+            Some(req) => req,
+            None => continue,
+        };
+        let (status, body) = match req.method() {
+            Method::Get => match handle(&db, req.url()) {
+                Ok(v) => (200, v),
+                Err((status, message)) => (status, json!({ "error": message })),
+            },
+            _ => (405, json!({ "error": "Only GET is supported" })),
+        };
+        let header = Header::from_bytes("Content-Type", "application/json").unwrap();
+        let resp = Response::from_string(body.to_string()).with_status_code(status).with_header(header);
+        // the client hanging up is no reason to stop serving
+        let _ = req.respond(resp);
+    }
+}
+
+/// Answer the query in `url`, a path with an optional query string
+pub fn handle(db: &ClangdDatabase, url: &str) -> Result<Value, QueryError> {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let params = parse_query(query);
+    let param = |k: &str| params.get(k).map(|v| v.as_str());
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+
+    let v = match segments.as_slice() {
+        ["symbols"] => {
+            let limit = match param("limit") {
+                Some(l) => l.parse().map_err(|_| bad_request("limit must be a number"))?,
+                None => DEFAULT_LIMIT,
+            };
+            let syms: Vec<SymbolJson> = db.search(param("q").unwrap_or("")).into_iter()
+                .take(limit)
+                .map(SymbolJson::from)
+                .collect();
+            json!(syms)
+        },
+        ["symbols", id, rest @ ..] => {
+            let sym = parse_symbol_id(id)
+                .and_then(|id| db.id.get(&id))
+                .ok_or_else(|| not_found(&format!("No symbol {}", id)))?;
+            match rest {
+                [] => json!(SymbolJson::from(sym)),
+                ["refs"] => json!(db.references(&sym.id).iter().map(RefJson::from).collect::<Vec<RefJson>>()),
+                ["callers"] => json!(calls(db, &db.callers(&sym.id))),
+                ["callees"] => json!(calls(db, &db.callees(&sym.id))),
+                ["supertypes"] => json!(db.supertypes(&sym.id).into_iter().map(SymbolJson::from).collect::<Vec<SymbolJson>>()),
+                ["subtypes"] => json!(db.subtypes(&sym.id).into_iter().map(SymbolJson::from).collect::<Vec<SymbolJson>>()),
+                _ => return Err(not_found(&format!("Unknown endpoint {}", path))),
+            }
+        },
+        ["symbol-at"] => {
+            let file = file_uri(param("file").ok_or_else(|| bad_request("file is required"))?);
+            let line = number(param("line"), "line")?;
+            let column = number(param("column"), "column")?;
+            let sym = db.symbol_at_uri(&file, line, column)
+                .ok_or_else(|| not_found(&format!("No symbol at {}:{}:{}", file, line, column)))?;
+            json!(SymbolJson::from(sym))
+        },
+        ["includes"] => {
+            let edges = db.include_edges();
+            match param("file").map(file_uri) {
+                Some(file) => json!({
+                    "file": file,
+                    "includes": edges.iter().filter(|(from, _)| *from == file).map(|(_, to)| to).collect::<Vec<&FileUri>>(),
+                    "included_by": edges.iter().filter(|(_, to)| *to == file).map(|(from, _)| from).collect::<Vec<&FileUri>>(),
+                }),
+                None => json!(edges),
+            }
+        },
+        ["compile-commands"] => {
+            let opts = CompileCommandOptions { strip_injected: true, normalize: false };
+            let mut cmds = compdb::compile_database(db, &opts);
+            if let Some(file) = param("file").map(file_uri) {
+                cmds.retain(|c| FileUri::from_path(Path::new(&c.file)) == file);
+            }
+            json!(cmds)
+        },
+        _ => return Err(not_found(&format!("Unknown endpoint {}", path))),
+    };
+    Ok(v)
+}
+
+fn calls(db: &ClangdDatabase, m: &CallMap) -> Vec<CallJson> {
+    m.iter()
+        .filter_map(|(id, sites)| Some(CallJson { symbol: SymbolJson::from(db.id.get(id)?), sites: sites.clone() }))
+        .collect()
+}
+
+// Either a URI or a path, made absolute against the current directory
+fn file_uri(s: &str) -> FileUri {
+    if s.contains("://") {
+        return FileUri::new(s);
+    }
+    let path = std::path::absolute(s).unwrap_or_else(|_| Path::new(s).to_path_buf());
+    FileUri::from_path(&path)
+}
+
+fn parse_query(q: &str) -> BTreeMap<String, String> {
+    q.split('&')
+        .filter(|kv| !kv.is_empty())
+        .map(|kv| {
+            let (k, v) = kv.split_once('=').unwrap_or((kv, ""));
+            (uri::decode(&k.replace('+', " ")), uri::decode(&v.replace('+', " ")))
+        })
+        .collect()
+}
+
+fn number(v: Option<&str>, name: &str) -> Result<u32, QueryError> {
+    v.and_then(|v| v.parse().ok()).ok_or_else(|| bad_request(&format!("{} must be a number", name)))
+}
+
+fn bad_request(message: &str) -> QueryError {
+    (400, message.to_string())
+}
+
+fn not_found(message: &str) -> QueryError {
+    (404, message.to_string())
+}
@@ -1,8 +1,9 @@
 use crate::clangd::ClangdUtility;
+use crate::uri::FileUri;
 
 use griff::ChunkStream;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq)]
 #[repr(u8)]
 pub enum SourceFlags {
     #[default]
@@ -11,45 +12,43 @@ pub enum SourceFlags {
     IsTU = 1 << 0,
     HadErrors = 1 << 1,
 }
-impl From<u8> for SourceFlags {
-    fn from(b: u8) -> Self {
-        use SourceFlags::*;
-        match b {
-            1 => IsTU,
-            2 => HadErrors,
-            _ => None,
-        }
-    }
-}
+pub type SourceFlagSet = u8;
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct Srcs {
-    pub flags: SourceFlags,
-    pub uri: String,
+    pub flags: SourceFlagSet,
+    pub uri: FileUri,
     pub digest: [u8; 8],
-    pub direct_includes: Vec<String>,
+    pub direct_includes: Vec<FileUri>,
 
 }
 impl ClangdUtility for Srcs{}
 
+impl Srcs {
+    pub fn has_flag(&self, flag: SourceFlags) -> bool {
+        self.flags & (flag as u8) != 0
+    }
+}
+
 impl Srcs {
     #[allow(dead_code)]
-    pub fn parse(stream: &ChunkStream, string_table: &Vec<String>) -> Vec<Srcs> {
+    #[allow(clippy::field_reassign_with_default)]
+    pub fn parse(stream: &ChunkStream, string_table: &[String]) -> Vec<Srcs> {
         let mut cursor: usize = 0;
         let mut idx: u32;
         let _data = stream.data.clone();
         let data = _data.as_slice();
         let mut srcs: Vec<Srcs> = vec![];
-        if data.len() == 0 {
+        if data.is_empty() {
             return srcs;
         }
 
         loop {
             let mut src: Srcs = Default::default();
-            src.flags = SourceFlags::from(data[cursor]);
+            src.flags = data[cursor];
             cursor += 1;
             let (sz, content) = Self::get_string(data.get(cursor..).unwrap(), string_table);
-            src.uri = content;
+            src.uri = FileUri::from(content);
             cursor += sz;
             src.digest = data.get(cursor..cursor+8).unwrap().try_into().unwrap();
             cursor += 8;
@@ -58,7 +57,7 @@ impl Srcs {
             idx = 0;
             while idx < len {
                 let (sz, content) = Self::get_string(data.get(cursor..).unwrap(), string_table);
-                src.direct_includes.push(content);
+                src.direct_includes.push(FileUri::from(content));
                 cursor += sz;
                 idx += 1;
             }
@@ -1,4 +1,9 @@
-use crate::clangd::ClangdUtility;
+use serde::Serialize;
+
+use std::collections::BTreeSet;
+
+use crate::clangd::{ClangdIdMap, ClangdUtility};
+use crate::uri::FileUri;
 use griff::ChunkStream;
 
 #[derive(Debug, Clone, Default, PartialEq)]
@@ -81,7 +86,7 @@ impl From<u8> for SymbolKind {
     }
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq)]
 #[repr(u8)]
 pub enum SymbolLanguage {
     #[default]
@@ -104,7 +109,7 @@ impl From<u8> for SymbolLanguage {
     }
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq)]
 #[repr(u8)]
 #[allow(dead_code)]
 pub enum SymbolSubKind {
@@ -119,7 +124,7 @@ pub enum SymbolSubKind {
     UsingEnum,
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq)]
 #[repr(u16)]
 #[allow(dead_code)]
 pub enum SymbolProperty {
@@ -137,26 +142,35 @@ pub enum SymbolProperty {
 }
 pub type SymbolPropertySet = u16;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct SymbolInfo {
     pub kind: SymbolKind,
     pub subkind: SymbolSubKind,
     pub lang: SymbolLanguage,
     pub properties: SymbolPropertySet,
 }
+impl SymbolInfo {
+    pub fn has_property(&self, p: SymbolProperty) -> bool {
+        self.properties & (p as u16) != 0
+    }
+
+    pub fn set_property(&mut self, p: SymbolProperty) {
+        self.properties |= p as u16;
+    }
+}
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Serialize)]
 pub struct SymbolLocation {
     pub start: SymbolPosition,
     pub end: SymbolPosition,
-    pub file_uri: String,
+    pub file_uri: FileUri,
 }
 impl SymbolLocation {
-    pub fn get_location(buf: &[u8], string_table: &Vec<String>) -> (usize, Self) {
+    pub fn get_location(buf: &[u8], string_table: &[String]) -> (usize, Self) {
         let mut loc: SymbolLocation = Default::default();
         let mut bytes_read: usize = 0;
         let (sz, content) = Symbol::get_string(buf, string_table);
-        loc.file_uri = content;
+        loc.file_uri = FileUri::from(content);
         bytes_read += sz;
 
         let (sz, content) = Symbol::get_varint(buf.get(bytes_read..).unwrap());
@@ -177,13 +191,13 @@ impl SymbolLocation {
     }
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Serialize)]
 pub struct SymbolPosition {
     pub line: u32,
     pub column: u32,
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq)]
 #[repr(u16)]
 #[allow(dead_code)]
 pub enum SymbolOrigin {
@@ -201,7 +215,7 @@ pub enum SymbolOrigin {
     StdLib = 1 << 9,
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq)]
 #[repr(u8)]
 pub enum SymbolFlags {
     #[default]
@@ -212,31 +226,98 @@ pub enum SymbolFlags {
     ImplementationDetail = 1 << 2,
     VisibleOutsideFile = 1 << 3,
 }
-impl From<u8> for SymbolFlags {
-    fn from(b: u8) -> Self {
-        use SymbolFlags::*;
+pub type SymbolFlagSet = u8;
 
-        match b {
-            1 => IndexedForCodeCompletion,
-            2 => Deprecated,
-            4 => ImplementationDetail,
-            8 => VisibleOutsideFile,
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SymbolIncludedHeader {
+    // URI of the header, or a verbatim spelling like <stdio.h>
+    pub name: FileUri,
+    pub refs: usize,
+    pub supported_directives: usize,
+}
 
-            _ => None,
+/// A function parameter, i.e. `const char *name`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Param {
+    pub name: String,
+    /// The type alone, i.e. `const char *`
+    pub t: String,
+    /// Type and name as a declaration
+    pub decl: String,
+}
+impl Param {
+    fn parse(p: &str, index: usize) -> Self {
+        let ident = |c: char| c.is_alphanumeric() || c == '_';
+        // function pointers name themselves inside the parens: int (*cb)(int)
+        if let Some(start) = p.find("(*") {
+            let rest = &p[start + 2..];
+            let len = rest.find(|c: char| !ident(c)).unwrap_or(rest.len());
+            let name = if len > 0 { rest[..len].to_string() } else { format!("arg{}", index) };
+            let t = format!("{}{}", &p[..start + 2], &rest[len..]);
+            let decl = format!("{}{}{}", &p[..start + 2], name, &rest[len..]);
+            return Param { name, t, decl };
+        }
+        let (head, array) = p.find('[').map_or((p, ""), |i| p.split_at(i));
+        let head = head.trim_end();
+        let start = head.rfind(|c: char| !ident(c)).map_or(0, |i| i + 1);
+        let (before, last) = (head[..start].trim_end(), &head[start..]);
+        // the word before the last one, to tell `struct tag` or `const T` from a name
+        let prev = before.rsplit(|c: char| !ident(c)).next().unwrap_or("");
+        let builtin = ["int", "char", "short", "long", "float", "double", "signed", "unsigned", "bool", "_Bool", "void"].contains(&last);
+        // `const T` is unnamed, `char *const p` isn't
+        let qualified_pointer = before[..before.len() - prev.len()].trim_end().ends_with(['*', '&']);
+        let tagged = ["struct", "union", "enum", "class", "typename"].contains(&prev)
+            || (["const", "volatile"].contains(&prev) && !qualified_pointer);
+        if last.is_empty() || before.is_empty() || builtin || tagged {
+            let name = format!("arg{}", index);
+            let sep = if head.ends_with(['*', '&']) { "" } else { " " };
+            return Param { decl: format!("{}{}{}{}", head, sep, name, array), t: format!("{}{}", head, array), name };
         }
+        Param { name: last.to_string(), t: format!("{}{}", before, array), decl: p.to_string() }
     }
 }
 
-#[derive(Debug, Clone, Default)]
-pub struct SymbolIncludedHeader {
-    pub name: String,
-    pub refs: usize,
-    pub supported_directives: usize,
+// Split on commas outside of parens, brackets and template arguments
+fn split_top_level(s: &str) -> Vec<String> {
+    let mut parts: Vec<String> = vec![];
+    let mut depth = 0;
+    let mut cur = String::new();
+    for c in s.chars() {
+        match c {
+            '(' | '[' | '<' | '{' => depth += 1,
+            ')' | ']' | '>' | '}' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(std::mem::take(&mut cur));
+                continue;
+            },
+            _ => (),
+        }
+        cur.push(c);
+    }
+    parts.push(cur);
+    parts
 }
 
 pub type SymbolId = [u8; 8];
 
-#[derive(Debug, Clone, Default)]
+/// Hex representation of a SymbolId, as printed by clangd
+pub fn symbol_id_str(id: &SymbolId) -> String {
+    id.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+/// Inverse of `symbol_id_str`
+pub fn parse_symbol_id(s: &str) -> Option<SymbolId> {
+    if s.len() != 16 || !s.is_ascii() {
+        return None;
+    }
+    let mut id: SymbolId = Default::default();
+    for (i, b) in id.iter_mut().enumerate() {
+        *b = u8::from_str_radix(&s[i*2..i*2+2], 16).ok()?;
+    }
+    Some(id)
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct Symbol {
     pub id: SymbolId,
     pub syminfo: SymbolInfo,
@@ -245,7 +326,7 @@ pub struct Symbol {
     pub definition: SymbolLocation,
     pub canonical_declaration: SymbolLocation,
     pub references: u32,
-    pub flags: SymbolFlags,
+    pub flags: SymbolFlagSet,
     pub origin: SymbolOrigin,
     pub signature: String,
     pub template_specialization_args: String,
@@ -258,11 +339,85 @@ pub struct Symbol {
 impl ClangdUtility for Symbol {}
 
 impl Symbol {
-    pub fn parse(stream: &ChunkStream, string_table: &Vec<String>) -> Vec<Symbol> {
+    /// Scope and name, i.e. ns::Class::method
+    pub fn qualified_name(&self) -> String {
+        format!("{}{}", self.scope, self.name)
+    }
+
+    pub fn has_flag(&self, flag: SymbolFlags) -> bool {
+        self.flags & (flag as u8) != 0
+    }
+
+    pub fn has_property(&self, p: SymbolProperty) -> bool {
+        self.syminfo.has_property(p)
+    }
+
+    /// The definition if the index has one, the declaration otherwise
+    pub fn location(&self) -> &SymbolLocation {
+        if self.definition.file_uri.is_empty() { &self.canonical_declaration } else { &self.definition }
+    }
+
+    /// Functions, methods, constructors and the like
+    pub fn is_function(&self) -> bool {
+        use SymbolKind::*;
+        matches!(self.syminfo.kind,
+            Function | InstanceMethod | ClassMethod | StaticMethod | Constructor | Destructor | ConversionFunction)
+    }
+
+    /// Fold in another shard's copy of the same symbol, the way clangd's mergeSymbol does:
+    /// the copy with a definition wins, gaps are filled from the other, references add up and headers are unioned
+    pub fn merge(&mut self, other: &Symbol) {
+        let mut o = other.clone();
+        if self.definition.file_uri.is_empty() && !o.definition.file_uri.is_empty() {
+            std::mem::swap(self, &mut o);
+        }
+        if self.definition.file_uri.is_empty() {
+            self.definition = o.definition.clone();
+        }
+        if self.canonical_declaration.file_uri.is_empty() {
+            self.canonical_declaration = o.canonical_declaration.clone();
+        }
+        self.references += o.references;
+        for (mine, theirs) in [
+            (&mut self.signature, o.signature),
+            (&mut self.completion_snippet_suffix, o.completion_snippet_suffix),
+            (&mut self.documentation, o.documentation),
+            (&mut self.return_t, o.return_t),
+            (&mut self.t, o.t),
+        ] {
+            if mine.is_empty() {
+                *mine = theirs;
+            }
+        }
+        for h in o.headers {
+            match self.headers.iter_mut().find(|m| m.name == h.name) {
+                Some(m) => {
+                    m.refs += h.refs;
+                    m.supported_directives |= h.supported_directives;
+                },
+                None => self.headers.push(h),
+            }
+        }
+        self.flags |= o.flags;
+    }
+
+    /// Parameters parsed from the signature; unnamed ones are called arg1, arg2, ...
+    pub fn params(&self) -> Vec<Param> {
+        let inner = self.signature.trim().strip_prefix('(').and_then(|s| s.rsplit_once(')')).map_or("", |(p, _)| p);
+        split_top_level(inner).iter()
+            .map(|p| p.split_once('=').map_or(p.as_str(), |(d, _)| d).trim())
+            .filter(|p| !p.is_empty() && *p != "void" && *p != "...")
+            .enumerate()
+            .map(|(i, p)| Param::parse(p, i + 1))
+            .collect()
+    }
+
+    #[allow(clippy::field_reassign_with_default)]
+    pub fn parse(stream: &ChunkStream, string_table: &[String]) -> Vec<Symbol> {
         let mut syms: Vec<Symbol> = vec![];
         let len = stream.data.len();
         let data = stream.data.as_slice();
-        if data.len() == 0 {
+        if data.is_empty() {
             return syms;
         }
         let mut cursor: usize = 0;
@@ -277,62 +432,66 @@ impl Symbol {
             // LANGUAGE
             s.syminfo.lang = SymbolLanguage::from(data[cursor]);
             cursor += 1;
-            let (sz, content) = Self::get_string(&data.get(cursor..).unwrap(), string_table);
+            let (sz, content) = Self::get_string(data.get(cursor..).unwrap(), string_table);
             s.name = content;
             cursor += sz;
             // SCOPE
-            let (sz, content) = Self::get_string(&data.get(cursor..).unwrap(), string_table);
+            let (sz, content) = Self::get_string(data.get(cursor..).unwrap(), string_table);
             s.scope = content;
             cursor += sz;
             // TEMPLATE SPECIALIZATION ARGUMENTS
-            let (sz, content) = Self::get_string(&data.get(cursor..).unwrap(), string_table);
+            let (sz, content) = Self::get_string(data.get(cursor..).unwrap(), string_table);
             s.template_specialization_args = content;
             cursor += sz;
+            // the index doesn't store properties; partial specializations are told apart in derive_properties()
+            if !s.template_specialization_args.is_empty() {
+                s.syminfo.set_property(SymbolProperty::TemplateSpecialization);
+            }
             // LOCATION
-            let (sz, loc) = SymbolLocation::get_location(&data.get(cursor..).unwrap(), string_table);
+            let (sz, loc) = SymbolLocation::get_location(data.get(cursor..).unwrap(), string_table);
             s.definition = loc;
             cursor += sz;
             // CANONICAL DECLARATION
-            let (sz, loc) = SymbolLocation::get_location(&data.get(cursor..).unwrap(), string_table);
+            let (sz, loc) = SymbolLocation::get_location(data.get(cursor..).unwrap(), string_table);
             s.canonical_declaration = loc;
             cursor += sz;
             // REFERENCES
-            let (sz, content) = Self::get_varint(&data.get(cursor..).unwrap());
+            let (sz, content) = Self::get_varint(data.get(cursor..).unwrap());
             s.references = content;
             cursor += sz;
             // FLAGS
-            s.flags = SymbolFlags::from(*data.get(cursor).unwrap());
+            s.flags = *data.get(cursor).unwrap();
             cursor += 1;
             // SIGNATURE
-            let (sz, content) = Self::get_string(&data.get(cursor..).unwrap(), string_table);
+            let (sz, content) = Self::get_string(data.get(cursor..).unwrap(), string_table);
             s.signature = content;
             cursor += sz;
             // COMPLETION SNIPPET SUFFIX
-            let (sz, content) = Self::get_string(&data.get(cursor..).unwrap(), string_table);
+            let (sz, content) = Self::get_string(data.get(cursor..).unwrap(), string_table);
             s.completion_snippet_suffix = content;
             cursor += sz;
             // DOCUMENTATION
-            let (sz, content) = Self::get_string(&data.get(cursor..).unwrap(), string_table);
+            let (sz, content) = Self::get_string(data.get(cursor..).unwrap(), string_table);
             s.documentation = content;
             cursor += sz;
             // RETURN TYPE
-            let (sz, content) = Self::get_string(&data.get(cursor..).unwrap(), string_table);
+            let (sz, content) = Self::get_string(data.get(cursor..).unwrap(), string_table);
             s.return_t = content;
             cursor += sz;
             // TYPE
-            let (sz, content) = Self::get_string(&data.get(cursor..).unwrap(), string_table);
+            let (sz, content) = Self::get_string(data.get(cursor..).unwrap(), string_table);
             s.t = content;
             cursor += sz;
             // INCLUDES
-            let (sz, h_content) = Self::get_varint(&data.get(cursor..).unwrap());
+            let (sz, h_content) = Self::get_varint(data.get(cursor..).unwrap());
             cursor += sz;
             idx = 0;
             while idx < h_content {
                 let mut hdr: SymbolIncludedHeader = Default::default();
-                let (sz, content) = Self::get_string(&data.get(cursor..).unwrap(), string_table);
-                hdr.name = content;
+                let (sz, content) = Self::get_string(data.get(cursor..).unwrap(), string_table);
+                hdr.name = FileUri::from(content);
                 cursor += sz;
-                let (sz, content) = Self::get_varint(&data.get(cursor..).unwrap());
+                let (sz, content) = Self::get_varint(data.get(cursor..).unwrap());
                 hdr.refs = (content >> 2) as usize;
                 hdr.supported_directives = (content & 0x3) as usize;
                 s.headers.push(hdr);
@@ -347,3 +506,61 @@ impl Symbol {
         syms
     }
 }
+
+// Words in template arguments that name no declaration
+const TEMPLATE_ARG_KEYWORDS: [&str; 30] = [
+    "bool", "char", "char8_t", "char16_t", "char32_t", "wchar_t", "short", "int", "long", "float", "double", "void",
+    "signed", "unsigned", "const", "volatile", "true", "false", "nullptr", "auto", "decltype", "sizeof", "typename",
+    "class", "struct", "union", "enum", "size_t", "ptrdiff_t", "nullptr_t",
+];
+
+// Whether specialization arguments mention a name the index doesn't know, which is then a template parameter
+fn has_template_parameter(args: &str, names: &BTreeSet<&str>) -> bool {
+    args.split(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == ':'))
+        .filter(|w| !w.is_empty() && !w.contains("::") && !w.starts_with(|c: char| c.is_ascii_digit()))
+        .any(|w| !TEMPLATE_ARG_KEYWORDS.contains(&w) && !names.contains(w))
+}
+
+/// Properties the index doesn't store, worked out from the rest of it:
+/// partial specializations by their arguments naming template parameters, which clangd never indexes,
+/// templates by their snippet or by having specializations, locals by a function scope,
+/// and GoogleTest bodies as unit tests. Only GoogleTest's are recognised; tests of other frameworks
+/// stay unmarked, `impact` finds those by name with `impact::DEFAULT_TEST_PATTERNS`.
+pub fn derive_properties(syms: &mut ClangdIdMap) {
+    let names: BTreeSet<&str> = syms.values().map(|s| s.name.as_str()).collect();
+    let functions: BTreeSet<String> = syms.values().filter(|s| s.is_function()).map(|s| s.qualified_name()).collect();
+    let specialized: BTreeSet<String> = syms.values()
+        .filter(|s| s.has_property(SymbolProperty::TemplateSpecialization))
+        .map(|s| s.qualified_name())
+        .collect();
+
+    let mut derived: Vec<(SymbolId, SymbolPropertySet)> = vec![];
+    for s in syms.values() {
+        let mut info: SymbolInfo = s.syminfo.clone();
+        if info.has_property(SymbolProperty::TemplateSpecialization) && !s.is_function()
+            && has_template_parameter(&s.template_specialization_args, &names) {
+            info.properties &= !(SymbolProperty::TemplateSpecialization as u16);
+            info.set_property(SymbolProperty::TemplatePartialSpecialization);
+            info.set_property(SymbolProperty::Generic);
+        }
+        else if s.template_specialization_args.is_empty()
+            && (s.completion_snippet_suffix.starts_with('<') || specialized.contains(&s.qualified_name())) {
+            info.set_property(SymbolProperty::Generic);
+        }
+        if functions.contains(s.scope.trim_end_matches("::")) {
+            info.set_property(SymbolProperty::Local);
+        }
+        // TEST(Suite, Name) defines Suite_Name_Test::TestBody()
+        if s.syminfo.kind == SymbolKind::InstanceMethod && s.name == "TestBody" && s.scope.ends_with("_Test::") {
+            info.set_property(SymbolProperty::UnitTest);
+        }
+        if info.properties != s.syminfo.properties {
+            derived.push((s.id, info.properties));
+        }
+    }
+    for (id, properties) in derived {
+        if let Some(s) = syms.get_mut(&id) {
+            s.syminfo.properties = properties;
+        }
+    }
+}
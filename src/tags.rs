@@ -0,0 +1,254 @@
+//! Tag files for editors: universal-ctags `tags`, Emacs `TAGS` and a cscope cross-reference.
+//! File names are relative to the project root, where the tag file is expected to live.
+
+use std::io::{self, Write};
+use std::fs;
+use std::path::Path;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::clangd::ClangdDatabase;
+use crate::refs::RefKind;
+use crate::symbols::{Symbol, SymbolFlags, SymbolKind, SymbolLocation};
+use crate::uri::FileUri;
+
+const CSCOPE_VERSION: u32 = 15;
+
+// A definition or declaration to tag
+struct Tag<'a> {
+    sym: &'a Symbol,
+    loc: &'a SymbolLocation,
+    // ctags kind letter and name
+    kind: (char, &'static str),
+}
+
+// ctags kinds of the C/C++ parsers; None for symbols not worth a tag
+fn ctags_kind(s: &Symbol, declaration: bool) -> Option<(char, &'static str)> {
+    use SymbolKind::*;
+    let k = match s.syminfo.kind {
+        Macro => ('d', "macro"),
+        Enum => ('g', "enum"),
+        Struct => ('s', "struct"),
+        Class => ('c', "class"),
+        Union => ('u', "union"),
+        TypeAlias => ('t', "typedef"),
+        Namespace | NamespaceAlias => ('n', "namespace"),
+        Variable if declaration => ('x', "externvar"),
+        Variable => ('v', "variable"),
+        Field => ('m', "member"),
+        EnumConstant => ('e', "enumerator"),
+        _ if s.is_function() && declaration => ('p', "prototype"),
+        _ if s.is_function() => ('f', "function"),
+        _ => return None,
+    };
+    Some(k)
+}
+
+// Definitions, plus declarations kept elsewhere, i.e. prototypes in headers
+fn tags(db: &ClangdDatabase) -> Vec<Tag<'_>> {
+    let mut tags: Vec<Tag> = vec![];
+    for s in db.id.values() {
+        let has_def = !s.definition.file_uri.is_empty();
+        if let (true, Some(kind)) = (has_def, ctags_kind(s, false)) {
+            tags.push(Tag { sym: s, loc: &s.definition, kind });
+        }
+        let decl = &s.canonical_declaration;
+        let elsewhere = !has_def || s.is_function() || s.syminfo.kind == SymbolKind::Variable;
+        if !decl.file_uri.is_empty() && *decl != s.definition && elsewhere {
+            if let Some(kind) = ctags_kind(s, true) {
+                tags.push(Tag { sym: s, loc: decl, kind });
+            }
+        }
+    }
+    tags
+}
+
+// Path written to the tag file: relative to `root` when under it, absolute otherwise
+fn tag_path(uri: &FileUri, root: &Path) -> Option<String> {
+    let path = match uri.relative_to(root) {
+        Some(p) => p,
+        None => uri.to_path()?,
+    };
+    Some(path.to_string_lossy().replace('\\', "/"))
+}
+
+// Per file, by the name written to the tag file
+type Files<T> = BTreeMap<(String, FileUri), T>;
+
+// Lines of the source files, read on first use; tags still work for files that are gone
+#[derive(Default)]
+pub(crate) struct Sources {
+    files: BTreeMap<FileUri, Option<Vec<String>>>,
+}
+impl Sources {
+    // The 0-based `line` of `uri` and its byte offset, line terminator included
+    pub(crate) fn line(&mut self, uri: &FileUri, line: u32) -> Option<(&str, usize)> {
+        let lines = self.files.entry(uri.clone()).or_insert_with(|| {
+            let text = fs::read(uri.to_path()?).ok()?;
+            Some(String::from_utf8_lossy(&text).split_inclusive('\n').map(String::from).collect())
+        }).as_ref()?;
+        let offset = lines.iter().take(line as usize).map(|l| l.len()).sum();
+        Some((lines.get(line as usize)?.as_str(), offset))
+    }
+}
+
+// Byte offset of a clangd column, which counts UTF-16 code units
+pub(crate) fn byte_column(line: &str, column: u32) -> usize {
+    let mut units = 0;
+    for (i, c) in line.char_indices() {
+        if units >= column as usize {
+            return i;
+        }
+        units += c.len_utf16();
+    }
+    line.len()
+}
+
+/// Write a universal-ctags `tags` file, sorted so vim can binary search it
+pub fn ctags<W: Write>(db: &ClangdDatabase, root: &Path, out: &mut W) -> io::Result<()> {
+    let scopes: BTreeMap<String, &Symbol> = db.id.values().map(|s| (s.qualified_name(), s)).collect();
+    let mut lines: BTreeSet<String> = BTreeSet::new();
+    for t in tags(db) {
+        let Some(file) = tag_path(&t.loc.file_uri, root) else { continue };
+        let s = t.sym;
+        let mut line = format!("{}\t{}\t{};\"\t{}", s.name, file, t.loc.start.line + 1, t.kind.0);
+        if let Some(scope) = s.scope.strip_suffix("::") {
+            // the enclosing symbol's kind names the field, i.e. class:Shape
+            let kind = scopes.get(scope).and_then(|p| ctags_kind(p, false)).map_or("namespace", |k| k.1);
+            line.push_str(&format!("\t{}:{}", kind, scope));
+        }
+        if !s.return_t.is_empty() {
+            line.push_str(&format!("\ttyperef:typename:{}", s.return_t));
+        }
+        if !s.signature.is_empty() && (s.is_function() || s.syminfo.kind == SymbolKind::Macro) {
+            line.push_str(&format!("\tsignature:{}", s.signature));
+        }
+        if !s.has_flag(SymbolFlags::VisibleOutsideFile) && matches!(t.kind.0, 'f' | 'v') {
+            line.push_str("\tfile:");
+        }
+        lines.insert(line);
+    }
+
+    writeln!(out, "!_TAG_FILE_FORMAT\t2\t/extended format; --format=1 will not append ;\" to lines/")?;
+    writeln!(out, "!_TAG_FILE_SORTED\t1\t/0=unsorted, 1=sorted, 2=foldcase/")?;
+    writeln!(out, "!_TAG_PROGRAM_NAME\t{}\t//", env!("CARGO_PKG_NAME"))?;
+    writeln!(out, "!_TAG_PROGRAM_VERSION\t{}\t//", env!("CARGO_PKG_VERSION"))?;
+    for l in lines {
+        writeln!(out, "{}", l)?;
+    }
+    Ok(())
+}
+
+/// Write an Emacs `TAGS` file; tag text comes from the sources, or is just the name when they can't be read
+pub fn etags<W: Write>(db: &ClangdDatabase, root: &Path, out: &mut W) -> io::Result<()> {
+    let mut files: Files<BTreeSet<(u32, u32, &str)>> = BTreeMap::new();
+    for t in tags(db) {
+        let Some(file) = tag_path(&t.loc.file_uri, root) else { continue };
+        files.entry((file, t.loc.file_uri.clone())).or_default().insert((t.loc.start.line, t.loc.end.column, &t.sym.name));
+    }
+
+    let mut sources: Sources = Default::default();
+    for ((file, uri), entries) in files.iter() {
+        let mut section = String::new();
+        for (line, end, name) in entries.iter() {
+            // the text Emacs searches for: the line up to the end of the name
+            match sources.line(uri, *line) {
+                Some((text, offset)) => {
+                    let text = text.trim_end_matches(['\n', '\r']);
+                    let text = &text[..byte_column(text, *end)];
+                    section.push_str(&format!("{}\x7f{}\x01{},{}\n", text, name, line + 1, offset));
+                },
+                None => section.push_str(&format!("{}\x7f{}\x01{},\n", name, name, line + 1)),
+            }
+        }
+        write!(out, "\x0c\n{},{}\n{}", file, section.len(), section)?;
+    }
+    Ok(())
+}
+
+// cscope marks, written as a tab and the mark before the symbol
+fn cscope_mark(s: &Symbol) -> Option<char> {
+    use SymbolKind::*;
+    let m = match s.syminfo.kind {
+        Macro => '#',
+        Class => 'c',
+        Enum => 'e',
+        Struct => 's',
+        Union => 'u',
+        TypeAlias => 't',
+        Field | EnumConstant => 'm',
+        Parameter => 'p',
+        _ if s.is_function() => '$',
+        _ => 'g',
+    };
+    Some(m).filter(|_| !matches!(s.syminfo.kind, Namespace | NamespaceAlias | Using | Unknown))
+}
+
+/// Write an uncompressed cscope cross-reference (`cscope -d -f cscope.out` reads it without rebuilding).
+/// Definitions come from symbols, calls and other uses from the refs.
+pub fn cscope<W: Write>(db: &ClangdDatabase, root: &Path, out: &mut W) -> io::Result<()> {
+    // per line and column: end column, mark and name
+    type Marks<'a> = BTreeMap<u32, BTreeMap<u32, (u32, Option<char>, &'a str)>>;
+    let mut files: Files<Marks> = BTreeMap::new();
+    for s in db.id.values() {
+        let mut add = |loc: &SymbolLocation, m: Option<char>| {
+            let Some(file) = tag_path(&loc.file_uri, root) else { return };
+            let line = files.entry((file, loc.file_uri.clone())).or_default().entry(loc.start.line).or_default();
+            // a definition beats a plain use at the same spot
+            let e = line.entry(loc.start.column).or_insert((loc.end.column, m, &s.name));
+            if e.1.is_none() {
+                e.1 = m;
+            }
+        };
+        if !s.definition.file_uri.is_empty() {
+            add(&s.definition, cscope_mark(s));
+        }
+        for r in db.references(&s.id).iter() {
+            let m = if r.has_kind(RefKind::Definition) {
+                cscope_mark(s)
+            }
+            else if s.is_function() && r.has_kind(RefKind::Reference) {
+                Some('`')
+            }
+            else {
+                None
+            };
+            add(&r.location, m);
+        }
+    }
+
+    let mut sources: Sources = Default::default();
+    let mut body = String::new();
+    for ((file, uri), lines) in files.iter() {
+        body.push_str(&format!("\t@{}\n\n", file));
+        for (line, syms) in lines.iter() {
+            // source text between the symbols, whitespace collapsed as cscope does
+            let text = sources.line(uri, *line).map(|(t, _)| t.trim_end().to_string()).unwrap_or_default();
+            let between = |from: u32, to: Option<u32>| -> String {
+                let (a, b) = (byte_column(&text, from), to.map_or(text.len(), |t| byte_column(&text, t)));
+                text.get(a..b.max(a)).unwrap_or("").split_whitespace().collect::<Vec<&str>>().join(" ")
+            };
+            body.push_str(&format!("{} {}\n", line + 1, between(0, syms.keys().next().copied())));
+            let mut it = syms.iter().peekable();
+            while let Some((_, (end, m, name))) = it.next() {
+                if let Some(m) = m {
+                    body.push_str(&format!("\t{}", m));
+                }
+                body.push_str(&format!("{}\n{}\n", name, between(*end, it.peek().map(|(c, _)| **c))));
+            }
+            body.push('\n');
+        }
+    }
+    // an empty file name ends the data
+    body.push_str("\t@\n");
+
+    let names: Vec<&String> = files.keys().map(|(f, _)| f).collect();
+    let dir = root.to_string_lossy();
+    let header_len = format!("cscope {} {} -c {:010}\n", CSCOPE_VERSION, dir, 0).len();
+    write!(out, "cscope {} {} -c {:010}\n{}", CSCOPE_VERSION, dir, header_len + body.len(), body)?;
+    // trailer: source directories, include directories, then the files and the string space they need
+    writeln!(out, "1\n.\n0\n{}\n{}", names.len(), names.iter().map(|n| n.len() + 1).sum::<usize>())?;
+    for n in names {
+        writeln!(out, "{}", n)?;
+    }
+    Ok(())
+}
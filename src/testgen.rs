@@ -0,0 +1,247 @@
+//! Generate a unit-test skeleton for a function: includes, placeholder arguments, the call and assertions on its result.
+
+use std::str::FromStr;
+use std::path::{Path, PathBuf};
+
+use crate::clangd::ClangdDatabase;
+use crate::symbols::{Symbol, SymbolKind};
+use crate::uri::FileUri;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TestFramework {
+    Unity,
+    GoogleTest,
+    Criterion,
+}
+impl FromStr for TestFramework {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unity" => Ok(TestFramework::Unity),
+            "gtest" | "googletest" => Ok(TestFramework::GoogleTest),
+            "criterion" => Ok(TestFramework::Criterion),
+            _ => Err(format!("Unknown framework '{}', expected unity, gtest or criterion", s)),
+        }
+    }
+}
+impl TestFramework {
+    fn is_cpp(&self) -> bool {
+        *self == TestFramework::GoogleTest
+    }
+}
+
+// What the result of a call looks like, to pick an assertion
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ValueKind {
+    Integer,
+    Float,
+    Bool,
+    String,
+    Pointer,
+    Other,
+}
+
+fn value_kind(t: &str) -> ValueKind {
+    let t = t.trim();
+    let base = t.trim_start_matches("const ").trim();
+    if base == "char *" || base == "char*" {
+        return ValueKind::String;
+    }
+    if t.ends_with('*') || t.contains("(*") {
+        return ValueKind::Pointer;
+    }
+    let words: Vec<&str> = base.split_whitespace().collect();
+    if words.iter().any(|w| matches!(*w, "float" | "double")) {
+        return ValueKind::Float;
+    }
+    if matches!(base, "bool" | "_Bool") {
+        return ValueKind::Bool;
+    }
+    let integer = |w: &str| matches!(w, "int" | "char" | "short" | "long" | "signed" | "unsigned" | "size_t" | "ssize_t" | "ptrdiff_t")
+        || (w.ends_with("_t") && (w.starts_with("int") || w.starts_with("uint")));
+    if !words.is_empty() && words.iter().all(|w| integer(w)) {
+        return ValueKind::Integer;
+    }
+    ValueKind::Other
+}
+
+// A value to start arguments and expectations from
+fn placeholder(t: &str, cpp: bool) -> &'static str {
+    match value_kind(t) {
+        ValueKind::Integer => "0",
+        ValueKind::Float => "0.0",
+        ValueKind::Bool => if cpp { "false" } else { "0" },
+        ValueKind::String => "\"\"",
+        ValueKind::Pointer => if cpp { "nullptr" } else { "NULL" },
+        ValueKind::Other => if cpp { "{}" } else { "{0}" },
+    }
+}
+
+/// The function `spec` names: a qualified name, a plain name, or a 1-based `file:line:column`
+pub fn find_function<'a>(db: &'a ClangdDatabase, spec: &str) -> Result<&'a Symbol, String> {
+    let mut parts = spec.rsplitn(3, ':');
+    if let (Some(col), Some(line), Some(file)) = (parts.next(), parts.next(), parts.next()) {
+        if let (Ok(line), Ok(col)) = (line.parse::<u32>(), col.parse::<u32>()) {
+            let path = std::path::absolute(file).map_err(|e| e.to_string())?;
+            return match db.symbol_at(&path, line.saturating_sub(1), col.saturating_sub(1)) {
+                Some(s) if s.is_function() => Ok(s),
+                Some(s) => Err(format!("{} is not a function", s.qualified_name())),
+                None => Err(format!("No symbol at {}", spec)),
+            };
+        }
+    }
+
+    let mut found: Vec<&Symbol> = db.id.values()
+        .filter(|s| s.is_function() && (s.qualified_name() == spec || s.name == spec))
+        .collect();
+    // an exact qualified match beats plain names in other scopes
+    if found.iter().any(|s| s.qualified_name() == spec) {
+        found.retain(|s| s.qualified_name() == spec);
+    }
+    match found.as_slice() {
+        [] => Err(format!("No function named {}", spec)),
+        [s] => Ok(s),
+        _ => Err(format!("{} is ambiguous: {}", spec,
+            found.iter().map(|s| format!("{}{}", s.qualified_name(), s.signature)).collect::<Vec<String>>().join(", "))),
+    }
+}
+
+/// Where a test built like `tu` finds quoted includes: its -I and -iquote directories, then the project `root`
+pub fn include_dirs(db: &ClangdDatabase, tu: &FileUri, root: &Path) -> Vec<PathBuf> {
+    let mut dirs = db.include_dirs(tu);
+    dirs.push(root.to_path_buf());
+    dirs
+}
+
+// How to spell `file` in a quoted include: the shortest path relative to one of `dirs`, else its name
+fn include_path(file: &FileUri, dirs: &[PathBuf]) -> Option<String> {
+    dirs.iter()
+        .filter_map(|d| file.relative_to(d))
+        .min_by_key(|p| p.components().count())
+        .map(|p| p.to_string_lossy().replace('\\', "/"))
+        .or_else(|| file.file_name())
+}
+
+/// The #include line making `s` visible to a test, spelled relative to `dirs` (see `include_dirs`).
+/// Prefers the header clangd would insert, then the declaring header, then the source file itself for file-local functions.
+pub fn include_for(s: &Symbol, dirs: &[PathBuf]) -> Option<String> {
+    if let Some(h) = s.headers.iter().max_by_key(|h| h.refs) {
+        let spelled = h.name.as_str();
+        if spelled.starts_with('"') || spelled.starts_with('<') {
+            return Some(format!("#include {}", spelled));
+        }
+        if let Some(path) = include_path(&h.name, dirs) {
+            return Some(format!("#include \"{}\"", path));
+        }
+    }
+    let decl = &s.canonical_declaration.file_uri;
+    let file = if decl.is_header() { decl } else { &s.location().file_uri };
+    include_path(file, dirs).map(|path| format!("#include \"{}\"", path))
+}
+
+/// A test file skeleton for the function `s`, with includes spelled relative to `dirs`
+pub fn generate(s: &Symbol, framework: TestFramework, dirs: &[PathBuf]) -> String {
+    let cpp = framework.is_cpp();
+    let mut out = String::new();
+    out.push_str(match framework {
+        TestFramework::Unity => "#include \"unity.h\"\n",
+        TestFramework::GoogleTest => "#include <gtest/gtest.h>\n",
+        TestFramework::Criterion => "#include <criterion/criterion.h>\n",
+    });
+    if let Some(include) = include_for(s, dirs) {
+        if s.headers.is_empty() && !s.canonical_declaration.file_uri.is_header() {
+            out.push_str("// not declared in a header, so the source is compiled into the test\n");
+        }
+        // C functions need C linkage when called from a C++ test
+        if cpp && s.location().file_uri.extension().as_deref() == Some("c") {
+            out.push_str(&format!("extern \"C\" {{\n{}\n}}\n", include));
+        }
+        else {
+            out.push_str(&format!("{}\n", include));
+        }
+    }
+    out.push('\n');
+
+    let body = body(s, framework);
+    let test_name = s.name.trim_start_matches('~');
+    match framework {
+        TestFramework::Unity => {
+            out.push_str("void setUp(void)\n{\n}\n\nvoid tearDown(void)\n{\n}\n\n");
+            out.push_str(&format!("void test_{}(void)\n{{\n{}}}\n\n", test_name, body));
+            out.push_str(&format!("int main(void)\n{{\n    UNITY_BEGIN();\n    RUN_TEST(test_{});\n    return UNITY_END();\n}}\n", test_name));
+        },
+        TestFramework::GoogleTest => {
+            let suite: String = test_name.split('_')
+                .map(|w| w.chars().take(1).flat_map(char::to_uppercase).chain(w.chars().skip(1)).collect::<String>())
+                .collect();
+            out.push_str(&format!("TEST({}Test, ReturnsExpectedValue)\n{{\n{}}}\n", suite, body));
+        },
+        TestFramework::Criterion => {
+            out.push_str(&format!("Test({}, returns_expected_value)\n{{\n{}}}\n", test_name, body));
+        },
+    }
+    out
+}
+
+// Arguments, the call and the assertions, indented for a test body
+fn body(s: &Symbol, framework: TestFramework) -> String {
+    let cpp = framework.is_cpp();
+    let mut body = String::new();
+    let params = s.params();
+    for p in params.iter() {
+        // references can't be left unbound, so the local holds the value
+        let decl = match p.t.strip_suffix('&') {
+            Some(t) => format!("{} {}", t.trim_end_matches('&').trim_end(), p.name),
+            None => p.decl.clone(),
+        };
+        body.push_str(&format!("    {} = {};\n", decl, placeholder(&p.t, cpp)));
+    }
+    if !params.is_empty() {
+        body.push('\n');
+    }
+
+    let args: Vec<&str> = params.iter().map(|p| p.name.as_str()).collect();
+    let scope = s.scope.trim_end_matches("::");
+    let callee = match s.syminfo.kind {
+        SymbolKind::InstanceMethod | SymbolKind::ConversionFunction => {
+            body.push_str(&format!("    {} object{};\n", scope, if cpp { "{}" } else { " = {0}" }));
+            format!("object.{}", s.name)
+        },
+        _ => s.qualified_name(),
+    };
+    let call = format!("{}({})", callee, args.join(", "));
+
+    let ret = s.return_t.trim();
+    if ret.is_empty() || ret == "void" || matches!(s.syminfo.kind, SymbolKind::Constructor | SymbolKind::Destructor) {
+        body.push_str(&format!("    {};\n\n", call));
+        body.push_str("    // TODO: assert on the side effects\n");
+        return body;
+    }
+    body.push_str(&format!("    {} result = {};\n\n", ret, call));
+    body.push_str("    // TODO: replace the expected value\n");
+    let expected = placeholder(ret, cpp);
+    let assertion = match (framework, value_kind(ret)) {
+        (TestFramework::Unity, ValueKind::Integer) => format!("TEST_ASSERT_EQUAL_INT({}, result);", expected),
+        (TestFramework::Unity, ValueKind::Float) => format!("TEST_ASSERT_EQUAL_DOUBLE({}, result);", expected),
+        (TestFramework::Unity, ValueKind::Bool) => "TEST_ASSERT_TRUE(result);".to_string(),
+        (TestFramework::Unity, ValueKind::String) => format!("TEST_ASSERT_EQUAL_STRING({}, result);", expected),
+        (TestFramework::Unity, ValueKind::Pointer) => "TEST_ASSERT_NOT_NULL(result);".to_string(),
+        (TestFramework::Unity, ValueKind::Other) =>
+            format!("{} expected = {};\n    TEST_ASSERT_EQUAL_MEMORY(&expected, &result, sizeof(result));", ret, expected),
+        (TestFramework::GoogleTest, ValueKind::Float) => format!("EXPECT_DOUBLE_EQ({}, result);", expected),
+        (TestFramework::GoogleTest, ValueKind::Bool) => "EXPECT_TRUE(result);".to_string(),
+        (TestFramework::GoogleTest, ValueKind::String) => format!("EXPECT_STREQ({}, result);", expected),
+        (TestFramework::GoogleTest, ValueKind::Pointer) => "EXPECT_NE(nullptr, result);".to_string(),
+        (TestFramework::GoogleTest, ValueKind::Integer) => format!("EXPECT_EQ({}, result);", expected),
+        (TestFramework::GoogleTest, ValueKind::Other) => format!("{} expected = {};\n    EXPECT_EQ(expected, result);", ret, expected),
+        (TestFramework::Criterion, ValueKind::Integer) => format!("cr_assert_eq(result, {});", expected),
+        (TestFramework::Criterion, ValueKind::Float) => format!("cr_assert_float_eq(result, {}, 1e-9);", expected),
+        (TestFramework::Criterion, ValueKind::Bool) => "cr_assert(result);".to_string(),
+        (TestFramework::Criterion, ValueKind::String) => format!("cr_assert_str_eq(result, {});", expected),
+        (TestFramework::Criterion, ValueKind::Pointer) => "cr_assert_not_null(result);".to_string(),
+        (TestFramework::Criterion, ValueKind::Other) =>
+            format!("{} expected = {};\n    cr_assert_arr_eq(&result, &expected, sizeof(result));", ret, expected),
+    };
+    body.push_str(&format!("    {}\n", assertion));
+    body
+}
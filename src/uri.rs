@@ -0,0 +1,223 @@
+//! URIs clangd records for files, i.e. file:///abs/path.c, test:///foo.h or a verbatim <header>.
+
+use serde::{Serialize, Serializer};
+
+use std::fmt;
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Default)]
+pub struct FileUri {
+    // exactly as clangd recorded it
+    raw: String,
+    // scheme and decoded, normalized path; what comparisons use
+    key: String,
+}
+
+impl FileUri {
+    pub fn new(raw: &str) -> Self {
+        let key = match Self::split(raw) {
+            Some((scheme, path)) => format!("{}:{}", scheme, fold_drive(&normalize(&decode(path)))),
+            None => raw.to_string(),
+        };
+        FileUri { raw: raw.to_string(), key }
+    }
+
+    /// file:// URI for an absolute path
+    pub fn from_path(p: &Path) -> Self {
+        let path = p.to_string_lossy().replace('\\', "/");
+        let sep = if path.starts_with('/') { "" } else { "/" };
+        Self::new(&format!("file://{}{}", sep, encode(&path)))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.raw
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.raw.is_empty()
+    }
+
+    /// file, test, unittest... or None for verbatim headers like <stdio.h>
+    pub fn scheme(&self) -> Option<&str> {
+        Self::split(&self.raw).map(|(s, _)| s)
+    }
+
+    /// The decoded path part, without scheme or authority
+    pub fn path(&self) -> String {
+        match Self::split(&self.raw) {
+            Some((_, path)) => decode(path),
+            None => self.raw.clone(),
+        }
+    }
+
+    /// Filesystem path, for file:// URIs only
+    pub fn to_path(&self) -> Option<PathBuf> {
+        if self.scheme() != Some("file") {
+            return None;
+        }
+        let path = self.path();
+        // file:///C:/foo on Windows
+        let b = path.as_bytes();
+        if b.len() > 2 && b[0] == b'/' && b[1].is_ascii_alphabetic() && b[2] == b':' {
+            return Some(PathBuf::from(&path[1..]));
+        }
+        Some(PathBuf::from(path))
+    }
+
+    pub fn file_name(&self) -> Option<String> {
+        let path = self.path();
+        // verbatim headers keep their quotes, i.e. <sys/types.h> or "config.h"
+        let path = [('<', '>'), ('"', '"')].iter()
+            .find_map(|(open, close)| path.strip_prefix(*open)?.strip_suffix(*close))
+            .unwrap_or(&path);
+        let name = path.rsplit('/').next()?;
+        if name.is_empty() {
+            return None;
+        }
+        Some(name.to_string())
+    }
+
+    pub fn extension(&self) -> Option<String> {
+        let name = self.file_name()?;
+        let (_, ext) = name.rsplit_once('.')?;
+        Some(ext.to_string())
+    }
+
+    /// Whether this names a C/C++ header
+    pub fn is_header(&self) -> bool {
+        matches!(self.extension().as_deref(), Some("h" | "hh" | "hpp" | "hxx" | "inc"))
+    }
+
+    /// Path relative to the project `root`, if it lies underneath it
+    pub fn relative_to(&self, root: &Path) -> Option<PathBuf> {
+        let path = PathBuf::from(fold_drive(&normalize(&self.to_path()?.to_string_lossy())));
+        let root = fold_drive(&normalize(&root.to_string_lossy().replace('\\', "/")));
+        if root.is_empty() {
+            return None;
+        }
+        path.strip_prefix(root).ok().map(|p| p.to_path_buf())
+    }
+
+    // (scheme, path) with any //authority dropped
+    fn split(raw: &str) -> Option<(&str, &str)> {
+        let (scheme, rest) = raw.split_once(':')?;
+        // a single letter is a Windows drive, not a scheme
+        if scheme.len() < 2
+        || !scheme.chars().all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.') {
+            return None;
+        }
+        let path = match rest.strip_prefix("//") {
+            Some(auth) => &auth[auth.find('/').unwrap_or(auth.len())..],
+            None => rest,
+        };
+        Some((scheme, path))
+    }
+}
+
+impl From<String> for FileUri {
+    fn from(s: String) -> Self {
+        FileUri::new(&s)
+    }
+}
+impl From<&str> for FileUri {
+    fn from(s: &str) -> Self {
+        FileUri::new(s)
+    }
+}
+
+impl fmt::Display for FileUri {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.raw)
+    }
+}
+
+impl PartialEq for FileUri {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+impl Eq for FileUri {}
+impl PartialOrd for FileUri {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for FileUri {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key.cmp(&other.key)
+    }
+}
+impl Hash for FileUri {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key.hash(state);
+    }
+}
+
+impl Serialize for FileUri {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&self.raw)
+    }
+}
+
+/// Decode %XX escapes
+pub fn decode(s: &str) -> String {
+    let b = s.as_bytes();
+    let mut out: Vec<u8> = vec![];
+    let mut i = 0;
+    while i < b.len() {
+        if b[i] == b'%'
+        && i + 2 < b.len()
+        && b[i+1].is_ascii_hexdigit()
+        && b[i+2].is_ascii_hexdigit() {
+            out.push(u8::from_str_radix(&s[i+1..i+3], 16).unwrap());
+            i += 3;
+            continue;
+        }
+        out.push(b[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+/// Escape everything clangd would, leaving path separators alone
+pub fn encode(s: &str) -> String {
+    let mut out = String::new();
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || b"/-_.~:".contains(&b) {
+            out.push(b as char);
+        }
+        else {
+            out.push_str(&format!("%{:02X}", b));
+        }
+    }
+    out
+}
+
+// Lowercase a Windows drive letter, as in /C:/src or C:/src, which clients spell either way
+fn fold_drive(path: &str) -> String {
+    let mut path = path.to_string();
+    let at = if path.starts_with('/') { 1 } else { 0 };
+    let b = path.as_bytes();
+    if b.len() > at + 1 && b[at].is_ascii_alphabetic() && b[at + 1] == b':' {
+        path[at..at + 1].make_ascii_lowercase();
+    }
+    path
+}
+
+// Drop empty and . components and resolve .. lexically
+fn normalize(path: &str) -> String {
+    let mut parts: Vec<&str> = vec![];
+    for c in path.split('/') {
+        match c {
+            "" | "." => (),
+            ".." => {
+                parts.pop();
+            },
+            _ => parts.push(c),
+        }
+    }
+    let lead = if path.starts_with('/') { "/" } else { "" };
+    format!("{}{}", lead, parts.join("/"))
+}
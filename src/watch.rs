@@ -0,0 +1,206 @@
+//! Watch the clangd index directory and report what changed as shards are rewritten.
+
+use async_std::task;
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::clangd::{ClangdDatabase, ClangdFile, ClangdParseFailure, LoadError};
+use crate::paths::PathMapping;
+use crate::uri::FileUri;
+use crate::refs::RefReferences;
+use crate::symbols::{Symbol, SymbolId, symbol_id_str};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum IndexEvent {
+    SymbolAdded { shard: String, symbol: Symbol },
+    SymbolRemoved { shard: String, symbol: Symbol },
+    SymbolChanged { shard: String, old: Box<Symbol>, new: Box<Symbol> },
+    RefsChanged { shard: String, id: SymbolId, refs: Vec<RefReferences> },
+    IncludesChanged { shard: String, uri: FileUri, added: Vec<FileUri>, removed: Vec<FileUri> },
+}
+impl fmt::Display for IndexEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use IndexEvent::*;
+        match self {
+            SymbolAdded { shard, symbol } =>
+                write!(f, "[{}] + {}{}", shard, symbol.scope, symbol.name),
+            SymbolRemoved { shard, symbol } =>
+                write!(f, "[{}] - {}{}", shard, symbol.scope, symbol.name),
+            SymbolChanged { shard, new, .. } =>
+                write!(f, "[{}] ~ {}{}", shard, new.scope, new.name),
+            RefsChanged { shard, id, refs } =>
+                write!(f, "[{}] refs {} ({} refs)", shard, symbol_id_str(id), refs.len()),
+            IncludesChanged { shard, uri, added, removed } =>
+                write!(f, "[{}] includes {} (+{} -{})", shard, uri, added.len(), removed.len()),
+        }
+    }
+}
+
+struct WatchedShard {
+    modified: SystemTime,
+    contents: ClangdFile,
+}
+
+pub struct IndexWatcher {
+    path: PathBuf,
+    mappings: Vec<PathMapping>,
+    shards: BTreeMap<PathBuf, WatchedShard>,
+    // shards the last poll couldn't parse; the previous version of each, if any, is kept
+    failures: Vec<ClangdParseFailure>,
+}
+
+impl IndexWatcher {
+    /// Given a root directory containing .cache/index, take an initial snapshot of the IDX files
+    pub fn new(p: &Path) -> Result<Self, LoadError> {
+        Self::with_mappings(p, &[])
+    }
+
+    /// Same as `new`, rewriting every recorded path with `mappings`
+    pub fn with_mappings(p: &Path, mappings: &[PathMapping]) -> Result<Self, LoadError> {
+        let mut w = IndexWatcher {
+            path: crate::index_path(p)?,
+            mappings: mappings.to_vec(),
+            shards: BTreeMap::new(),
+            failures: vec![],
+        };
+        let _ = w.poll();
+        Ok(w)
+    }
+
+    /// Shards the last poll couldn't parse, i.e. caught while clangd was writing them
+    pub fn failures(&self) -> &[ClangdParseFailure] {
+        &self.failures
+    }
+
+    /// A database of the shards as of the last poll, without parsing them again
+    pub fn database(&self) -> ClangdDatabase {
+        let shards = self.shards.iter()
+            .map(|(p, s)| (p.file_name().unwrap().to_string_lossy().to_string(), s.contents.clone()))
+            .collect();
+        crate::build(shards, self.failures.clone())
+    }
+
+    /// Re-parse any shard that was added, rewritten or removed since the last poll
+    pub fn poll(&mut self) -> Vec<IndexEvent> {
+        let mut events: Vec<IndexEvent> = vec![];
+        let mut seen: BTreeSet<PathBuf> = BTreeSet::new();
+        let empty: ClangdFile = Default::default();
+        self.failures.clear();
+
+        let rd = match fs::read_dir(self.path.as_path()) {
+            Ok(rd) => rd,
+            Err(_) => return events,
+        };
+        for entry in rd.flatten() {
+            let p = entry.path();
+            if !entry.file_type().map(|t| t.is_file()).unwrap_or(false) {
+                continue;
+            }
+            let modified = match entry.metadata().and_then(|m| m.modified()) {
+                Ok(m) => m,
+                Err(_) => continue,
+            };
+            // names that aren't UTF-8 can't be clangd's
+            let Some(shard) = entry.file_name().to_str().and_then(crate::shard_name).map(String::from) else { continue };
+            seen.insert(p.clone());
+            if let Some(old) = self.shards.get(&p) {
+                if old.modified == modified {
+                    continue;
+                }
+            }
+            // clangd may still be writing the shard; try again on the next poll
+            let mut contents = match task::block_on(ClangdFile::parse(p.clone())) {
+                Ok(cd) => cd,
+                Err(err) => {
+                    self.failures.push(ClangdParseFailure { path: p, error: format!("{:?}", err) });
+                    continue;
+                },
+            };
+            contents.remap_paths(&self.mappings);
+            let old = self.shards.get(&p).map(|s| &s.contents).unwrap_or(&empty);
+            events.append(&mut diff_shards(&shard, old, &contents));
+            self.shards.insert(p, WatchedShard { modified, contents });
+        }
+
+        let removed: Vec<PathBuf> = self.shards.keys()
+            .filter(|p| !seen.contains(*p))
+            .cloned()
+            .collect();
+        for p in removed {
+            let old = self.shards.remove(&p).unwrap();
+            let shard = p.file_name().and_then(|n| n.to_str()).and_then(crate::shard_name).unwrap_or_default();
+            events.append(&mut diff_shards(shard, &old.contents, &empty));
+        }
+        events
+    }
+
+    /// Poll forever, handing each change to `f`
+    pub fn watch<F: FnMut(IndexEvent)>(&mut self, interval: Duration, mut f: F) {
+        loop {
+            for ev in self.poll() {
+                f(ev);
+            }
+            std::thread::sleep(interval);
+        }
+    }
+}
+
+/// Compare two versions of the same shard
+pub fn diff_shards(shard: &str, old: &ClangdFile, new: &ClangdFile) -> Vec<IndexEvent> {
+    let mut events: Vec<IndexEvent> = vec![];
+
+    // SYMBOLS
+    let old_syms: BTreeMap<SymbolId, &Symbol> = old.symbols.data.iter().map(|s| (s.id, s)).collect();
+    let new_syms: BTreeMap<SymbolId, &Symbol> = new.symbols.data.iter().map(|s| (s.id, s)).collect();
+    for (id, sym) in new_syms.iter() {
+        match old_syms.get(id) {
+            None => events.push(IndexEvent::SymbolAdded { shard: shard.to_string(), symbol: (*sym).clone() }),
+            Some(o) if o != sym => events.push(IndexEvent::SymbolChanged {
+                shard: shard.to_string(), old: Box::new((*o).clone()), new: Box::new((*sym).clone())
+            }),
+            _ => (),
+        }
+    }
+    for (id, sym) in old_syms.iter() {
+        if !new_syms.contains_key(id) {
+            events.push(IndexEvent::SymbolRemoved { shard: shard.to_string(), symbol: (*sym).clone() });
+        }
+    }
+
+    // REFERENCES
+    let old_refs: BTreeMap<SymbolId, &Vec<RefReferences>> = old.references.data.iter().map(|r| (r.id, &r.refs)).collect();
+    let new_refs: BTreeMap<SymbolId, &Vec<RefReferences>> = new.references.data.iter().map(|r| (r.id, &r.refs)).collect();
+    for (id, refs) in new_refs.iter() {
+        if old_refs.get(id) != Some(refs) {
+            events.push(IndexEvent::RefsChanged { shard: shard.to_string(), id: *id, refs: (*refs).clone() });
+        }
+    }
+    for id in old_refs.keys() {
+        if !new_refs.contains_key(id) {
+            events.push(IndexEvent::RefsChanged { shard: shard.to_string(), id: *id, refs: vec![] });
+        }
+    }
+
+    // INCLUDES
+    let old_srcs: BTreeMap<&FileUri, &Vec<FileUri>> = old.sources.data.iter().map(|s| (&s.uri, &s.direct_includes)).collect();
+    let new_srcs: BTreeMap<&FileUri, &Vec<FileUri>> = new.sources.data.iter().map(|s| (&s.uri, &s.direct_includes)).collect();
+    let no_includes: Vec<FileUri> = vec![];
+    let mut uris: Vec<&FileUri> = old_srcs.keys().chain(new_srcs.keys()).cloned().collect();
+    uris.sort();
+    uris.dedup();
+    for uri in uris {
+        let o = old_srcs.get(uri).cloned().unwrap_or(&no_includes);
+        let n = new_srcs.get(uri).cloned().unwrap_or(&no_includes);
+        let added: Vec<FileUri> = n.iter().filter(|i| !o.contains(i)).cloned().collect();
+        let removed: Vec<FileUri> = o.iter().filter(|i| !n.contains(i)).cloned().collect();
+        if !added.is_empty() || !removed.is_empty() {
+            events.push(IndexEvent::IncludesChanged { shard: shard.to_string(), uri: uri.clone(), added, removed });
+        }
+    }
+
+    events
+}
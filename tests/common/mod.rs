@@ -0,0 +1,105 @@
+//! Builders for the small indexes the tests run against; every file lives under /work.
+// each test crate uses a different part of this
+#![allow(dead_code)]
+
+use clangd_parser::clangd::ClangdDatabase;
+use clangd_parser::refs::RefReferences;
+use clangd_parser::symbols::{Symbol, SymbolId, SymbolInfo, SymbolKind, SymbolLocation, SymbolPosition};
+use clangd_parser::uri::FileUri;
+
+pub const ROOT: &str = "/work";
+
+/// The URI of `file`, relative to /work
+pub fn uri(file: &str) -> FileUri {
+    FileUri::new(&format!("file:///work/{}", file))
+}
+
+/// An id that sorts by `n`
+pub const fn id(n: u8) -> SymbolId {
+    [n, 0, 0, 0, 0, 0, 0, 0]
+}
+
+/// `len` columns of a 0-based `line` of `file`
+pub fn location(file: &str, line: u32, column: u32, len: u32) -> SymbolLocation {
+    SymbolLocation {
+        start: SymbolPosition { line, column },
+        end: SymbolPosition { line, column: column + len },
+        file_uri: uri(file),
+    }
+}
+
+/// A symbol defined and declared at `loc`
+pub fn symbol(id: SymbolId, kind: SymbolKind, scope: &str, name: &str, loc: SymbolLocation) -> Symbol {
+    Symbol {
+        id,
+        syminfo: SymbolInfo { kind, ..Default::default() },
+        name: name.to_string(),
+        scope: scope.to_string(),
+        definition: loc.clone(),
+        canonical_declaration: loc,
+        ..Default::default()
+    }
+}
+
+/// A reference of the `kind` set, i.e. `RefKind::Reference as u8 | RefKind::Call as u8`, from inside `container`
+pub fn reference(kind: u8, location: SymbolLocation, container: SymbolId) -> RefReferences {
+    RefReferences { kind, location, container_id: container }
+}
+
+/// A database of `syms` without refs or shards
+pub fn database(syms: Vec<Symbol>) -> ClangdDatabase {
+    let mut db: ClangdDatabase = Default::default();
+    for s in syms {
+        db.id.insert(s.id, s);
+    }
+    db
+}
+
+/// A small loaded index with calls and a class hierarchy
+pub mod shapes {
+    use clangd_parser::clangd::{ClangdDatabase, ClangdFile};
+    use clangd_parser::refs::{RefKind, Refs};
+    use clangd_parser::rela::{Rela, RelationKind};
+    use clangd_parser::symbols::{SymbolId, SymbolKind};
+
+    use super::{id, location, reference, symbol};
+
+    pub const SHAPE: SymbolId = id(1);
+    pub const CIRCLE: SymbolId = id(2);
+    pub const DRAW: SymbolId = id(3);
+    pub const ON_DRAW: SymbolId = id(4);
+    pub const MAIN: SymbolId = id(5);
+
+    pub const CALL: u8 = RefKind::Reference as u8 | RefKind::Call as u8;
+    pub const REFERENCE: u8 = RefKind::Reference as u8;
+
+    // Shards where main() calls draw() and takes the address of on_draw(), and Circle derives from Shape; both carry it all
+    fn shards(call: u8) -> Vec<ClangdFile> {
+        let mut main: ClangdFile = Default::default();
+        main.symbols.data = vec![
+            symbol(SHAPE, SymbolKind::Class, "", "Shape", location("src/shape.h", 1, 6, 5)),
+            symbol(CIRCLE, SymbolKind::Class, "", "Circle", location("src/shape.h", 5, 6, 6)),
+            symbol(DRAW, SymbolKind::Function, "", "draw", location("src/main.c", 1, 5, 4)),
+            symbol(ON_DRAW, SymbolKind::Function, "", "on_draw", location("src/main.c", 4, 5, 7)),
+            symbol(MAIN, SymbolKind::Function, "", "main", location("src/main.c", 8, 4, 4)),
+        ];
+        main.references.data = vec![
+            Refs { id: DRAW, cnt: 1, refs: vec![reference(call, location("src/main.c", 9, 4, 4), MAIN)] },
+            Refs { id: ON_DRAW, cnt: 1, refs: vec![reference(REFERENCE, location("src/main.c", 10, 10, 7), MAIN)] },
+        ];
+        main.relations.data.push(Rela { subject: SHAPE, predicate: RelationKind::BaseOf, object: CIRCLE });
+        let mut header: ClangdFile = Default::default();
+        header.references.data = main.references.data.clone();
+        header.relations.data = main.relations.data.clone();
+        vec![main, header]
+    }
+
+    /// The database loaded from `shards`, with `call` as the kind of the draw() call
+    pub fn database(call: u8) -> ClangdDatabase {
+        let shards = shards(call).into_iter()
+            .enumerate()
+            .map(|(i, f)| (format!("main.c.000000000000000{}.idx", i), f))
+            .collect();
+        clangd_parser::build(shards, vec![])
+    }
+}
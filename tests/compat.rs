@@ -0,0 +1,116 @@
+//! Classify public API changes between two indexes of the same project.
+
+mod common;
+
+use std::path::Path;
+
+use clangd_parser::compat::{self, CompatChangeKind, CompatReport, Severity};
+use clangd_parser::symbols::{Symbol, SymbolFlags, SymbolKind, SymbolLocation};
+
+// declared in `file`, defined nowhere in the index
+fn symbol(id: u8, kind: SymbolKind, scope: &str, name: &str, file: &str) -> Symbol {
+    let decl = SymbolLocation { file_uri: common::uri(file), ..Default::default() };
+    Symbol {
+        definition: Default::default(),
+        flags: SymbolFlags::VisibleOutsideFile as u8,
+        ..common::symbol(common::id(id), kind, scope, name, decl)
+    }
+}
+
+fn function(id: u8, name: &str, signature: &str, return_t: &str) -> Symbol {
+    Symbol {
+        signature: signature.to_string(),
+        return_t: return_t.to_string(),
+        ..symbol(id, SymbolKind::Function, "", name, "include/motor.h")
+    }
+}
+
+fn check(old: Vec<Symbol>, new: Vec<Symbol>, public: &[&str]) -> CompatReport {
+    let public: Vec<String> = public.iter().map(|p| p.to_string()).collect();
+    let root = Path::new(common::ROOT);
+    compat::check(&common::database(old), root, &common::database(new), root, &public)
+}
+
+fn changes(r: &CompatReport) -> Vec<(Severity, CompatChangeKind, &str)> {
+    r.changes.iter().map(|c| (c.severity.clone(), c.change.clone(), c.name.as_str())).collect()
+}
+
+#[test]
+fn parameter_types() {
+    let types = |sig: &str| compat::parameter_types(&function(1, "f", sig, "void"));
+    assert_eq!(types("(const char *name, int len)"), vec!["const char*", "int"]);
+    assert_eq!(types("(int, unsigned long)"), vec!["int", "unsigned long"]);
+    assert_eq!(types("(const T &value)"), vec!["const T&"]);
+    assert_eq!(types("(struct point p, enum mode)"), vec!["struct point", "enum mode"]);
+    assert_eq!(types("(char buf[16], int m[2][3])"), vec!["char[16]", "int[2][3]"]);
+    assert_eq!(types("(std::map<int, int> m, int n = 3)"), vec!["std::map<int, int>", "int"]);
+    assert_eq!(types("(int (*cb)(int), void (*)(void))"), vec!["int (*)(int)", "void (*)(void)"]);
+    assert_eq!(types("(const char *fmt, ...)"), vec!["const char*", "..."]);
+    assert_eq!(types("(void)"), Vec::<String>::new());
+    assert_eq!(types("()"), Vec::<String>::new());
+}
+
+#[test]
+fn removed_and_changed_functions() {
+    let old = vec![
+        function(1, "motor_init", "(int channel)", "void"),
+        function(2, "motor_step", "(int steps)", "int"),
+        function(3, "motor_stop", "()", "void"),
+        function(4, "motor_speed", "(int rpm)", "void"),
+        function(5, "motor_on_fault", "(void (*cb)(int))", "void"),
+    ];
+    let new = vec![
+        // renamed parameter
+        function(1, "motor_init", "(int ch)", "void"),
+        function(2, "motor_step", "(long steps)", "int"),
+        function(4, "motor_speed", "(int rpm)", "bool"),
+        // renamed callback
+        function(5, "motor_on_fault", "(void (*handler)(int))", "void"),
+    ];
+    let r = check(old, new, &[]);
+    assert_eq!(changes(&r), vec![
+        (Severity::Breaking, CompatChangeKind::Removed, "motor_stop"),
+        (Severity::NonBreaking, CompatChangeKind::Signature, "motor_init"),
+        (Severity::Breaking, CompatChangeKind::ParameterTypes, "motor_step"),
+        (Severity::Breaking, CompatChangeKind::ReturnType, "motor_speed"),
+        (Severity::NonBreaking, CompatChangeKind::Signature, "motor_on_fault"),
+    ]);
+    assert_eq!((r.breaking, r.non_breaking), (3, 2));
+    assert!(r.is_breaking());
+}
+
+#[test]
+fn added_fields() {
+    let old = vec![symbol(1, SymbolKind::Struct, "", "motor", "include/motor.h")];
+    let new = vec![
+        symbol(1, SymbolKind::Struct, "", "motor", "include/motor.h"),
+        symbol(2, SymbolKind::Field, "motor::", "rpm", "include/motor.h"),
+        symbol(3, SymbolKind::Struct, "", "pwm", "include/motor.h"),
+        symbol(4, SymbolKind::Field, "pwm::", "duty", "include/motor.h"),
+        function(5, "pwm_init", "()", "void"),
+    ];
+    let r = check(old, new, &[]);
+    assert_eq!(changes(&r), vec![
+        // an existing struct grew
+        (Severity::Breaking, CompatChangeKind::Added, "motor::rpm"),
+        (Severity::NonBreaking, CompatChangeKind::Added, "pwm"),
+        // a brand-new struct's members are no break
+        (Severity::NonBreaking, CompatChangeKind::Added, "pwm::duty"),
+        (Severity::NonBreaking, CompatChangeKind::Added, "pwm_init"),
+    ]);
+}
+
+#[test]
+fn public_prefixes() {
+    let old = vec![
+        function(1, "motor_init", "(int channel)", "void"),
+        symbol(2, SymbolKind::Function, "", "motor_private", "src/internal.h"),
+    ];
+    // relative to the root, with or without a trailing slash, or absolute
+    for public in ["include/", "include", "/work/include"] {
+        let r = check(old.clone(), vec![], &[public]);
+        assert_eq!(changes(&r), vec![(Severity::Breaking, CompatChangeKind::Removed, "motor_init")], "{}", public);
+    }
+    assert!(check(old.clone(), vec![], &["inc"]).changes.is_empty());
+    assert_eq!(check(old, vec![], &[]).changes.len(), 2);
+}
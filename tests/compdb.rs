@@ -0,0 +1,87 @@
+//! Translation units are told apart by their URI, not their file name, and their commands normalised.
+
+use clangd_parser::clangd::{ClangdDatabase, ClangdFile};
+use clangd_parser::cmdl::Cmdl;
+use clangd_parser::compdb::{self, CompileCommandOptions, normalize_arguments};
+use clangd_parser::flags;
+use clangd_parser::health;
+use clangd_parser::srcs::{SourceFlags, Srcs};
+use clangd_parser::uri::FileUri;
+
+fn unit(file: &str, define: &str, flags: u8) -> ClangdFile {
+    let mut f: ClangdFile = Default::default();
+    f.sources.data.push(Srcs {
+        flags: SourceFlags::IsTU as u8 | flags,
+        uri: FileUri::new(&format!("file:///work/{}", file)),
+        ..Default::default()
+    });
+    f.cmdline.data.push(Cmdl {
+        directory: "/work/build".to_string(),
+        cmdl: vec!["cc".to_string(), format!("-D{}", define), "-c".to_string(), format!("/work/{}", file)],
+    });
+    f
+}
+
+// two programs with a main.c each, as the loader keys them
+fn database() -> ClangdDatabase {
+    let mut db: ClangdDatabase = Default::default();
+    db.file.insert("main.c.1A2B3C4D5E6F7A8B.idx".to_string(), unit("src/a/main.c", "APP_A", 0));
+    db.file.insert("main.c.8B7A6F5E4D3C2B1A.idx".to_string(), unit("src/b/main.c", "APP_B", SourceFlags::HadErrors as u8));
+    db
+}
+
+#[test]
+fn same_basename_keeps_both_commands() {
+    let db = database();
+    let cmds = compdb::compile_database(&db, &CompileCommandOptions::default());
+    let files: Vec<&str> = cmds.iter().map(|c| c.file.as_str()).collect();
+    assert_eq!(files, vec!["/work/src/a/main.c", "/work/src/b/main.c"]);
+    assert_eq!(cmds[1].arguments[1], "-DAPP_B");
+}
+
+#[test]
+fn same_basename_in_reports() {
+    let db = database();
+    let r = flags::report(&db);
+    assert_eq!(r.profiles.len(), 2);
+    assert_eq!(r.deviations.len(), 2);
+
+    let h = health::report(&db);
+    assert_eq!(h.units, 2);
+    assert_eq!(h.broken.len(), 1);
+    assert_eq!(h.broken[0].uri, FileUri::new("file:///work/src/b/main.c"));
+    assert_eq!(h.broken[0].shard, "main.c.8B7A6F5E4D3C2B1A.idx");
+}
+
+#[test]
+fn lookup_by_source() {
+    let db = database();
+    assert_eq!(db.files_named("main.c").count(), 2);
+    assert_eq!(db.files_named("main.h").count(), 0);
+    let b = FileUri::new("file:///work/src/b/main.c");
+    assert_eq!(db.file_of(&b).unwrap().cmdline.data[0].cmdl[1], "-DAPP_B");
+    assert!(db.file_of(&FileUri::new("file:///work/src/c/main.c")).is_none());
+    assert_eq!(db.compile_command(&b).unwrap().cmdl[1], "-DAPP_B");
+}
+
+fn normalize(args: &str) -> String {
+    let args: Vec<String> = args.split(' ').map(String::from).collect();
+    normalize_arguments(&args, "/work/build").join(" ")
+}
+
+#[test]
+fn normalize_joins_and_resolves() {
+    assert_eq!(normalize("cc -I inc -I/usr/include -isystem ../sys -c a.c"),
+        "cc -I/work/build/inc -I/usr/include -isystem/work/build/../sys -c a.c");
+    assert_eq!(normalize("cc -D X=1 -Xclang -foo -Xclang -foo"), "cc -DX=1 -Xclang -foo -Xclang -foo");
+}
+
+#[test]
+fn normalize_drops_only_redundant_flags() {
+    assert_eq!(normalize("cc -Iinc -I inc -iquote inc"), "cc -I/work/build/inc -iquote/work/build/inc");
+    assert_eq!(normalize("cc -DX -DX -DY=1 -DY=1"), "cc -DX -DY=1");
+    // each one undoes the last
+    assert_eq!(normalize("cc -DX -UX -DX"), "cc -DX -UX -DX");
+    assert_eq!(normalize("cc -DX=1 -DX=2 -DX=1"), "cc -DX=1 -DX=2 -DX=1");
+    assert_eq!(normalize("cc -UX -UX"), "cc -UX");
+}
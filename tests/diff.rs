@@ -0,0 +1,105 @@
+//! Compare two indexes of the same project.
+
+mod common;
+
+use clangd_parser::clangd::ClangdFile;
+use clangd_parser::diff::SymbolChange;
+use clangd_parser::rela::{Rela, RelationKind};
+use clangd_parser::srcs::Srcs;
+use clangd_parser::symbols::{Symbol, SymbolKind, SymbolLocation};
+use clangd_parser::uri::FileUri;
+
+use common::database;
+
+fn location(file: &str, line: u32) -> SymbolLocation {
+    common::location(file, line, 4, 8)
+}
+
+// defined in `file`, declared in include/motor.h
+fn symbol(id: u8, name: &str, file: &str, line: u32) -> Symbol {
+    Symbol {
+        signature: "(int n)".to_string(),
+        return_t: "int".to_string(),
+        canonical_declaration: location("include/motor.h", line),
+        ..common::symbol(common::id(id), SymbolKind::Function, "", name, location(file, line))
+    }
+}
+
+#[test]
+fn identical() {
+    let db = database(vec![symbol(1, "motor_init", "src/motor.c", 3)]);
+    assert!(db.diff(&db.clone()).is_empty());
+}
+
+#[test]
+fn added_removed_and_changed() {
+    let old = database(vec![
+        symbol(1, "motor_init", "src/motor.c", 3),
+        symbol(2, "motor_step", "src/motor.c", 10),
+    ]);
+    let mut step = symbol(2, "motor_step", "src/motor.c", 10);
+    step.signature = "(long n)".to_string();
+    step.return_t = "long".to_string();
+    let new = database(vec![step, symbol(3, "motor_stop", "src/motor.c", 20)]);
+
+    let d = old.diff(&new);
+    assert_eq!(d.added.iter().map(|s| s.name.as_str()).collect::<Vec<&str>>(), vec!["motor_stop"]);
+    assert_eq!(d.removed.iter().map(|s| s.name.as_str()).collect::<Vec<&str>>(), vec!["motor_init"]);
+    assert_eq!(d.changed.len(), 1);
+    assert_eq!(d.changed[0].changes, vec![
+        SymbolChange::Signature { old: "(int n)".to_string(), new: "(long n)".to_string() },
+        SymbolChange::ReturnType { old: "int".to_string(), new: "long".to_string() },
+    ]);
+    assert_eq!(d.to_string(), "\
++ 0300000000000000 motor_stop
+- 0100000000000000 motor_init
+~ 0200000000000000 motor_step
+    signature '(int n)' -> '(long n)'
+    return type 'int' -> 'long'
+");
+}
+
+#[test]
+fn line_shifts_are_not_moves() {
+    let old = database(vec![symbol(1, "motor_init", "src/motor.c", 3), symbol(2, "motor_step", "src/motor.c", 10)]);
+    // a line inserted at the top of both files
+    let new = database(vec![symbol(1, "motor_init", "src/motor.c", 4), symbol(2, "motor_step", "src/motor.c", 11)]);
+    assert!(old.diff(&new).is_empty());
+}
+
+#[test]
+fn file_changes_are_moves() {
+    let old = database(vec![symbol(1, "motor_init", "src/motor.c", 3)]);
+    let new = database(vec![symbol(1, "motor_init", "src/motor_init.c", 3)]);
+    let d = old.diff(&new);
+    assert_eq!(d.changed.len(), 1);
+    assert_eq!(d.changed[0].changes, vec![SymbolChange::DefinitionMoved {
+        old: location("src/motor.c", 3),
+        new: location("src/motor_init.c", 3),
+    }]);
+    assert_eq!(d.changed[0].changes[0].to_string(), "definition file:///work/src/motor.c:4 -> file:///work/src/motor_init.c:4");
+}
+
+#[test]
+fn relations_and_includes() {
+    let mut old = database(vec![]);
+    let mut f: ClangdFile = Default::default();
+    f.relations.data.push(Rela { subject: [1; 8], predicate: RelationKind::BaseOf, object: [2; 8] });
+    f.sources.data.push(Srcs {
+        uri: FileUri::new("file:///work/src/motor.c"),
+        direct_includes: vec![FileUri::new("file:///work/include/motor.h")],
+        ..Default::default()
+    });
+    old.file.insert("motor.c.0123456789ABCDEF.idx".to_string(), f.clone());
+
+    let mut new = database(vec![]);
+    f.relations.data.clear();
+    f.sources.data[0].direct_includes = vec![FileUri::new("file:///work/include/pwm.h")];
+    new.file.insert("motor.c.0123456789ABCDEF.idx".to_string(), f);
+
+    let d = old.diff(&new);
+    assert!(d.relations_added.is_empty());
+    assert_eq!(d.relations_removed.len(), 1);
+    assert_eq!(d.includes_added, vec![(FileUri::new("file:///work/src/motor.c"), FileUri::new("file:///work/include/pwm.h"))]);
+    assert_eq!(d.includes_removed, vec![(FileUri::new("file:///work/src/motor.c"), FileUri::new("file:///work/include/motor.h"))]);
+}
@@ -0,0 +1,76 @@
+//! Flag profiles of translation units and the deviations between them, one per flag.
+
+use clangd_parser::clangd::{ClangdDatabase, ClangdFile};
+use clangd_parser::cmdl::Cmdl;
+use clangd_parser::flags::{self, Deviation, DeviationKind, FlagCategory, FlagProfile};
+use clangd_parser::srcs::{SourceFlags, Srcs};
+use clangd_parser::uri::FileUri;
+
+fn uri(file: &str) -> FileUri {
+    FileUri::new(&format!("file:///work/{}", file))
+}
+
+fn args(s: &str) -> Vec<String> {
+    s.split(' ').map(String::from).collect()
+}
+
+// A unit compiled with `flags`
+fn unit(db: &mut ClangdDatabase, file: &str, flags: &str) {
+    let mut f: ClangdFile = Default::default();
+    f.sources.data.push(Srcs { flags: SourceFlags::IsTU as u8, uri: uri(file), ..Default::default() });
+    f.cmdline.data.push(Cmdl { directory: "/work".to_string(), cmdl: args(&format!("cc {} -c /work/{}", flags, file)) });
+    let name = file.rsplit('/').next().unwrap();
+    db.file.insert(format!("{}.{:016X}.idx", name, db.file.len()), f);
+}
+
+#[test]
+fn profile() {
+    let p = FlagProfile::from_arguments(&uri("a.c"),
+        &args("cc -DNDEBUG -DLEVEL=2 -Iinclude -std=c99 -std=c11 -O2 -Wall -Wl,--gc-sections -Wa,-adhln -Wextra"));
+    assert_eq!(p.defines.iter().collect::<Vec<&String>>(), vec!["LEVEL=2", "NDEBUG"]);
+    assert_eq!(p.include_paths.iter().collect::<Vec<&String>>(), vec!["include"]);
+    // the last -std wins
+    assert_eq!(p.standard.as_deref(), Some("c11"));
+    assert_eq!(p.optimization.as_deref(), Some("-O2"));
+    // linker and assembler options aren't warnings
+    assert_eq!(p.warnings.iter().collect::<Vec<&String>>(), vec!["-Wall", "-Wextra"]);
+}
+
+#[test]
+fn grouped_by_flag() {
+    let mut db: ClangdDatabase = Default::default();
+    unit(&mut db, "src/a.c", "-DNDEBUG -DTRACE -std=c11 -O2");
+    unit(&mut db, "src/b.c", "-DNDEBUG -DTRACE -std=c11 -O2");
+    unit(&mut db, "src/c.c", "-DNDEBUG -std=c11 -O2");
+    unit(&mut db, "src/d.c", "-DNDEBUG -std=c11 -O2");
+    unit(&mut db, "src/e.c", "-std=c99 -O0");
+    unit(&mut db, "src/f.c", "-DNDEBUG -std=c99");
+    let r = flags::report(&db);
+    assert_eq!(r.profiles.len(), 6);
+
+    let deviation = |category, flag: Option<&str>, kind, files: &[&str]| Deviation {
+        category,
+        flag: flag.map(String::from),
+        kind,
+        uris: files.iter().map(|f| uri(f)).collect(),
+    };
+    let different = |majority: &str| DeviationKind::Different { majority: Some(majority.to_string()) };
+    assert_eq!(r.deviations, vec![
+        deviation(FlagCategory::Define, Some("NDEBUG"), DeviationKind::Missing, &["src/e.c"]),
+        // held by two of six, so one deviation for both rather than one per unit
+        deviation(FlagCategory::Define, Some("TRACE"), DeviationKind::Extra, &["src/a.c", "src/b.c"]),
+        deviation(FlagCategory::Standard, Some("c99"), different("c11"), &["src/e.c", "src/f.c"]),
+        deviation(FlagCategory::Optimization, None, different("-O2"), &["src/f.c"]),
+        deviation(FlagCategory::Optimization, Some("-O0"), different("-O2"), &["src/e.c"]),
+    ]);
+    assert_eq!(r.deviations[1].to_string(), "Define TRACE only in 2 translation units\n    file:///work/src/a.c\n    file:///work/src/b.c");
+}
+
+#[test]
+fn consistent() {
+    let mut db: ClangdDatabase = Default::default();
+    unit(&mut db, "src/a.c", "-DNDEBUG -Iinclude -std=c11 -O2 -Wall");
+    unit(&mut db, "src/b.c", "-DNDEBUG -Iinclude -std=c11 -O2 -Wall");
+    let r = flags::report(&db);
+    assert!(r.deviations.is_empty());
+}
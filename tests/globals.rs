@@ -0,0 +1,67 @@
+//! Tell writes to a global from reads by the text around the reference.
+
+use clangd_parser::globals::is_write;
+
+// `line` with the reference to `g` marked as `@g`
+fn write(line: &str) -> bool {
+    let start = line.find("@g").expect("no @g in line") as u32;
+    is_write(&line.replace("@g", "g"), start, start + 1)
+}
+
+#[test]
+fn writes() {
+    for line in [
+        "    @g = 1;",
+        "    @g += 2;",
+        "    @g <<= 1;",
+        "    @g++;",
+        "    ++@g;",
+        "    --@g;",
+        "    @g--;",
+        "    @g[i] += 1;",
+        "    @g[buf[i]] = 0;",
+        "    @g->x++;",
+        "    @g.count = 3;",
+        "    f(&@g);",
+        "    p = & @g;",
+        "    if (x) @g = y;",
+        "    return &@g;",
+        "    n = sizeof &@g;",
+        "    case &@g:",
+        "    p = (char *)&@g;",
+        "    p = (struct motor *) &@g;",
+        "    return (T)&@g;",
+    ] {
+        assert!(write(line), "not a write: {:?}", line);
+    }
+}
+
+#[test]
+fn reads() {
+    for line in [
+        "    if (@g == 1)",
+        "    return @g;",
+        "    x = @g;",
+        "    x = a & @g;",
+        "    x = a && @g;",
+        "    x = f(i) & @g;",
+        "    x = returned & @g;",
+        "    x = (a + b) & @g;",
+        "    x = f(int) & @g;",
+        "    x = @g[i];",
+        "    x = @g->x + 1;",
+        "    if (@g >= 1 && @g <= 2)",
+        "    f(@g);",
+        // the assignment is on the next line
+        "    @g",
+    ] {
+        assert!(!write(line), "not a read: {:?}", line);
+    }
+}
+
+#[test]
+fn utf16_columns() {
+    // clangd counts the emoji as 2 columns, UTF-8 as 4 bytes
+    assert!(is_write("s = \"🔧\"; g = 1;", 10, 11));
+    assert!(!is_write("s = \"🔧\"; x = g;", 14, 15));
+}
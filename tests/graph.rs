@@ -0,0 +1,52 @@
+//! Cut a graph down to what a root reaches.
+
+mod common;
+
+use std::path::Path;
+
+use clangd_parser::clangd::ClangdDatabase;
+use clangd_parser::graph::{self, GraphKind, GraphOptions};
+use clangd_parser::refs::RefKind;
+use clangd_parser::symbols::{SymbolKind, symbol_id_str};
+
+use common::{id, location, reference, symbol};
+
+const MAIN: [u8; 8] = id(1);
+const INIT: [u8; 8] = id(2);
+const PWM: [u8; 8] = id(3);
+const LOG: [u8; 8] = id(4);
+
+// main -> motor_init -> pwm_set, and log_write on its own
+fn database() -> ClangdDatabase {
+    let mut db = common::database([(MAIN, "main"), (INIT, "motor_init"), (PWM, "pwm_set"), (LOG, "log_write")].into_iter()
+        .enumerate()
+        .map(|(i, (id, name))| symbol(id, SymbolKind::Function, "", name, location("src/app.c", i as u32 * 10, 4, name.len() as u32)))
+        .collect());
+    for (caller, callee) in [(MAIN, INIT), (INIT, PWM), (LOG, LOG)] {
+        db.refs.entry(callee).or_default().push(reference(RefKind::Reference as u8, Default::default(), caller));
+    }
+    db
+}
+
+fn calls(root: Option<&str>, depth: Option<usize>) -> Result<Vec<String>, String> {
+    let opts = GraphOptions { root: root.map(String::from), depth, cluster: false };
+    let g = graph::build(&database(), GraphKind::Calls, Path::new(common::ROOT), &opts)?;
+    let mut labels: Vec<String> = g.nodes.values().map(|n| n.label.clone()).collect();
+    labels.sort();
+    Ok(labels)
+}
+
+#[test]
+fn reachable_from_root() {
+    assert_eq!(calls(None, None).unwrap(), vec!["log_write", "main", "motor_init", "pwm_set"]);
+    assert_eq!(calls(Some("main"), None).unwrap(), vec!["main", "motor_init", "pwm_set"]);
+    assert_eq!(calls(Some("main"), Some(1)).unwrap(), vec!["main", "motor_init"]);
+    assert_eq!(calls(Some(&symbol_id_str(&INIT)), None).unwrap(), vec!["motor_init", "pwm_set"]);
+    assert_eq!(calls(Some("log_write"), None).unwrap(), vec!["log_write"]);
+}
+
+#[test]
+fn unknown_root_is_an_error() {
+    assert_eq!(calls(Some("motor_stop"), None), Err("No node 'motor_stop' in the calls graph".to_string()));
+    assert!(calls(Some("0500000000000000"), None).is_err());
+}
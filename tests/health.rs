@@ -0,0 +1,77 @@
+//! Broken translation units, the header shards they leave errors in, and what the rest of the index still covers.
+
+mod common;
+
+use clangd_parser::clangd::{ClangdDatabase, ClangdFile};
+use clangd_parser::health;
+use clangd_parser::srcs::{SourceFlags, Srcs};
+use clangd_parser::symbols::SymbolKind;
+
+use common::{id, location, symbol, uri};
+
+const TU: u8 = SourceFlags::IsTU as u8;
+const ERRORS: u8 = SourceFlags::HadErrors as u8;
+
+// A shard of `file` with its flags, direct includes and the symbols numbered `syms`
+fn shard(file: &str, flags: u8, includes: &[&str], syms: &[u8]) -> (String, ClangdFile) {
+    let mut f: ClangdFile = Default::default();
+    f.sources.data.push(Srcs {
+        flags,
+        uri: uri(file),
+        direct_includes: includes.iter().map(|i| uri(i)).collect(),
+        ..Default::default()
+    });
+    // clangd adds the includes as stubs without flags
+    for i in includes {
+        f.sources.data.push(Srcs { uri: uri(i), ..Default::default() });
+    }
+    for n in syms {
+        f.symbols.data.push(symbol(id(*n), SymbolKind::Function, "", &format!("f{}", n), location(file, *n as u32, 0, 2)));
+    }
+    let name = file.rsplit('/').next().unwrap();
+    (format!("{}.00000000000000{:02X}.idx", name, syms[0]), f)
+}
+
+// a.c failed to compile, and so did motor.h through it; b.c and util.h are clean, and c.c failed on its own
+fn database() -> ClangdDatabase {
+    let shards = [
+        shard("src/a.c", TU | ERRORS, &["src/motor.h"], &[1]),
+        shard("src/motor.h", ERRORS, &["src/util.h"], &[2, 5]),
+        shard("src/util.h", 0, &[], &[3]),
+        shard("src/b.c", TU, &["src/util.h"], &[4, 5]),
+        shard("src/c.c", TU | ERRORS, &[], &[6]),
+    ];
+    clangd_parser::build(shards.into_iter().collect(), vec![])
+}
+
+#[test]
+fn header_shards_count_against_their_unit() {
+    let r = health::report(&database());
+    assert_eq!(r.units, 3);
+    let broken: Vec<(String, usize, usize)> = r.broken.iter().map(|b| (b.uri.path(), b.symbols, b.only_here)).collect();
+    assert_eq!(broken, vec![
+        // its own f1 and motor.h's f2 and f5, which b.c also has
+        ("/work/src/a.c".to_string(), 3, 2),
+        ("/work/src/c.c".to_string(), 1, 1),
+    ]);
+    assert_eq!((r.affected_symbols, r.total_symbols), (3, 6));
+    assert_eq!(r.incomplete_ratio(), 0.5);
+}
+
+#[test]
+fn findings() {
+    let db = database();
+    let errors: Vec<String> = health::sources_with_errors(&db).iter().map(|s| s.uri.path()).collect();
+    assert_eq!(errors, vec!["/work/src/a.c", "/work/src/c.c", "/work/src/motor.h"]);
+    let findings = health::findings(&db);
+    assert_eq!(findings.len(), 3);
+    assert!(findings.iter().all(|f| f.rule == health::INDEX_ERROR_RULE.id));
+}
+
+#[test]
+fn clean_index() {
+    let db = clangd_parser::build([shard("src/b.c", TU, &[], &[4])].into_iter().collect(), vec![]);
+    let r = health::report(&db);
+    assert_eq!((r.units, r.broken.len(), r.affected_symbols), (1, 0, 0));
+    assert_eq!(r.incomplete_ratio(), 0.0);
+}
@@ -0,0 +1,119 @@
+//! From changed lines, as given on the command line or by `git diff -U0`, to the symbols they touch.
+
+mod common;
+
+use std::path::Path;
+
+use clangd_parser::clangd::ClangdDatabase;
+use clangd_parser::impact::{self, ChangedRange};
+use clangd_parser::refs::RefKind;
+use clangd_parser::symbols::{Symbol, SymbolKind, SymbolLocation};
+use clangd_parser::uri::FileUri;
+
+use common::{id, reference, uri};
+
+const A: [u8; 8] = id(1);
+const B: [u8; 8] = id(2);
+const C: [u8; 8] = id(3);
+const MOTOR_MAX: [u8; 8] = id(4);
+
+// `git diff -U0` after changing a(), deleting two lines of b(), adding c(), adding added.txt and removing gone.txt
+const DIFF: &str = "\
+diff --git a/added.txt b/added.txt
+new file mode 100644
+index 0000000..3e75765
+--- /dev/null
++++ b/added.txt
+@@ -0,0 +1 @@
++new
+diff --git a/gone.txt b/gone.txt
+deleted file mode 100644
+index 3367afd..0000000
+--- a/gone.txt
++++ /dev/null
+@@ -1 +0,0 @@
+-old
+diff --git a/src/motor.c b/src/motor.c
+index 142d276..f294602 100644
+--- a/src/motor.c
++++ b/src/motor.c
+@@ -3 +3 @@ int a(void)
+-    return 1;
++    return MOTOR_MAX;
+@@ -8,2 +7,0 @@ int b(void)
+-    int x = 2;
+-    int y = 3;
+@@ -11,0 +10,5 @@ int b(void)
++
++int c(void)
++{
++    return 0;
++}
+";
+
+fn root() -> &'static Path {
+    Path::new(common::ROOT)
+}
+
+fn range(file: &str, start: u32, end: u32) -> ChangedRange {
+    ChangedRange { file: uri(file), start, end }
+}
+
+// 0-based line, as in the index
+fn location(line: u32) -> SymbolLocation {
+    common::location("src/motor.c", line, 4, 1)
+}
+
+fn function(id: [u8; 8], name: &str, line: u32) -> Symbol {
+    common::symbol(id, SymbolKind::Function, "", name, location(line))
+}
+
+// src/motor.c after the change: a() on line 1, b() on line 6, c() on line 11
+fn database() -> ClangdDatabase {
+    let mut db = common::database(vec![function(A, "a", 0), function(B, "b", 5), function(C, "c", 10)]);
+    db.refs.insert(MOTOR_MAX, vec![reference(RefKind::Reference as u8, location(2), A)]);
+    db
+}
+
+#[test]
+fn parse_ranges() {
+    assert_eq!(ChangedRange::parse("src/motor.c:12", root()), Ok(range("src/motor.c", 12, 12)));
+    assert_eq!(ChangedRange::parse("src/motor.c:3-9", root()), Ok(range("src/motor.c", 3, 9)));
+    assert_eq!(ChangedRange::parse("/other/motor.c:3", root()), Ok(ChangedRange { file: FileUri::new("file:///other/motor.c"), start: 3, end: 3 }));
+    for bad in ["src/motor.c", "src/motor.c:", "src/motor.c:x", "src/motor.c:3-"] {
+        assert!(ChangedRange::parse(bad, root()).is_err(), "{}", bad);
+    }
+}
+
+#[test]
+fn parse_git_diff() {
+    assert_eq!(impact::parse_unified_diff(DIFF, root()), vec![
+        range("added.txt", 1, 1),
+        range("src/motor.c", 3, 3),
+        // the deletion, numbered 7 by git, sits before line 8
+        range("src/motor.c", 8, 8),
+        range("src/motor.c", 10, 14),
+    ]);
+}
+
+#[test]
+fn deletion_at_the_top() {
+    let diff = "\
+--- a/src/motor.c
++++ b/src/motor.c
+@@ -1,2 +0,0 @@
+-// motor driver
+-
+";
+    assert_eq!(impact::parse_unified_diff(diff, root()), vec![range("src/motor.c", 1, 1)]);
+}
+
+#[test]
+fn changed_symbols_from_git_diff() {
+    let db = database();
+    let ranges = impact::parse_unified_diff(DIFF, root());
+    // a() through its reference, b() as the closest definition above the deletion, c() through its definition
+    assert_eq!(impact::changed_symbols(&db, &ranges), [A, B, C].into_iter().collect());
+    assert_eq!(impact::changed_symbols(&db, &[range("src/motor.c", 3, 3)]), [A].into_iter().collect());
+    assert!(impact::changed_symbols(&db, &[range("src/pwm.c", 1, 100)]).is_empty());
+}
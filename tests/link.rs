@@ -0,0 +1,93 @@
+//! Link sets of a test, following prototypes in headers to the sources defining them.
+
+mod common;
+
+use clangd_parser::clangd::ClangdDatabase;
+use clangd_parser::link::link_set;
+use clangd_parser::refs::RefKind;
+use clangd_parser::symbols::{Symbol, SymbolId, SymbolKind};
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use common::{id, location, reference, symbol, uri};
+
+const TEST: &str = "test/shape_test.c";
+
+// A function declared in `decl`, if given, and defined in `def`, if given
+fn function(n: u8, name: &str, decl: Option<&str>, def: Option<&str>) -> Symbol {
+    let mut s = symbol(id(n), SymbolKind::Function, "", name, location(def.or(decl).unwrap(), n as u32, 0, 4));
+    if let Some(d) = decl {
+        s.canonical_declaration = location(d, n as u32, 0, 4);
+    }
+    if def.is_none() {
+        s.definition = Default::default();
+    }
+    s
+}
+
+// The test calls area() from shape.h, defined in shape.c, which calls root() from math.c;
+// the test also uses square(), inline in shape.h, which calls clamp() from util.c, and printf(), defined nowhere
+fn database() -> ClangdDatabase {
+    let mut db = common::database(vec![
+        function(1, "area", Some("src/shape.h"), Some("src/shape.c")),
+        function(2, "root", Some("src/math.h"), Some("src/math.c")),
+        function(3, "square", None, Some("src/shape.h")),
+        function(4, "clamp", Some("src/util.h"), Some("src/util.c")),
+        function(5, "printf", Some("/usr/include/stdio.h"), None),
+        function(6, "helper", None, Some(TEST)),
+        function(7, "scale", None, Some("src/shape.c")),
+        function(8, "unused", Some("src/util.h"), Some("src/unused.c")),
+        symbol(id(9), SymbolKind::Macro, "", "SIDES", location("src/shape.h", 9, 8, 5)),
+    ]);
+    let uses: [(SymbolId, &str); 9] = [
+        (id(1), TEST),
+        (id(3), TEST),
+        (id(5), TEST),
+        (id(6), TEST),
+        (id(9), TEST),
+        (id(2), "src/shape.c"),
+        // shape.c's own static
+        (id(7), "src/shape.c"),
+        (id(4), "src/shape.h"),
+        (id(5), "src/math.c"),
+    ];
+    let mut refs: BTreeMap<SymbolId, Vec<_>> = BTreeMap::new();
+    for (i, (sym, file)) in uses.into_iter().enumerate() {
+        refs.entry(sym).or_default().push(reference(RefKind::Reference as u8, location(file, 20 + i as u32, 4, 4), id(6)));
+    }
+    // a declaration alone needs nothing
+    refs.entry(id(8)).or_default().push(reference(RefKind::Declaration as u8, location(TEST, 40, 0, 6), id(6)));
+    db.refs = refs;
+    db
+}
+
+#[test]
+fn through_headers() {
+    let report = link_set(&database(), &uri(TEST));
+    let sources: Vec<(String, Vec<&str>)> = report.sources.iter()
+        .map(|(u, names)| (u.path(), names.iter().map(String::as_str).collect()))
+        .collect();
+    assert_eq!(sources, vec![
+        ("/work/src/math.c".to_string(), vec!["root"]),
+        ("/work/src/shape.c".to_string(), vec!["area"]),
+        // square() has no source of its own, but what it calls does
+        ("/work/src/util.c".to_string(), vec!["clamp"]),
+    ]);
+    // reached from the test and from math.c, reported once
+    assert_eq!(report.unresolved, vec!["printf"]);
+}
+
+#[test]
+fn cmake() {
+    let report = link_set(&database(), &uri(TEST));
+    assert_eq!(report.cmake(Path::new(common::ROOT)),
+        "add_executable(shape_test\n    test/shape_test.c\n    src/math.c\n    src/shape.c\n    src/util.c\n)\n");
+}
+
+#[test]
+fn nothing_needed() {
+    let report = link_set(&database(), &uri("src/math.c"));
+    assert!(report.sources.is_empty());
+    assert_eq!(report.unresolved, vec!["printf"]);
+}
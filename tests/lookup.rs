@@ -0,0 +1,63 @@
+//! Symbol at a position: nesting, ranges over several lines and clangd's UTF-16 columns.
+
+mod common;
+
+use clangd_parser::clangd::ClangdFile;
+use clangd_parser::refs::{RefKind, Refs};
+use clangd_parser::symbols::{SymbolKind, SymbolLocation, SymbolPosition};
+
+use common::{id, location, reference, symbol, uri};
+
+fn span(file: &str, start: (u32, u32), end: (u32, u32)) -> SymbolLocation {
+    SymbolLocation {
+        start: SymbolPosition { line: start.0, column: start.1 },
+        end: SymbolPosition { line: end.0, column: end.1 },
+        file_uri: uri(file),
+    }
+}
+
+fn names(db: &clangd_parser::clangd::ClangdDatabase, file: &str, positions: &[(u32, u32)]) -> Vec<Option<String>> {
+    positions.iter()
+        .map(|(line, column)| db.symbol_at_uri(&uri(file), *line, *column).map(|s| s.name.clone()))
+        .collect()
+}
+
+#[test]
+fn innermost_range() {
+    // struct outer { int inner; }; spanning lines 0-2, with `inner` on line 1 and a reference to it overlapping outer's end
+    let mut f: ClangdFile = Default::default();
+    f.symbols.data = vec![
+        symbol(id(1), SymbolKind::Struct, "", "outer", span("a.c", (0, 0), (2, 1))),
+        symbol(id(2), SymbolKind::Field, "outer::", "inner", location("a.c", 1, 8, 5)),
+        symbol(id(3), SymbolKind::Variable, "", "other", location("a.c", 4, 4, 5)),
+    ];
+    f.references.data = vec![Refs { id: id(2), cnt: 1, refs: vec![reference(RefKind::Reference as u8, span("a.c", (2, 0), (2, 6)), id(3))] }];
+    let db = clangd_parser::build([("a.c.0000000000000000.idx".to_string(), f)].into(), vec![]);
+
+    assert_eq!(names(&db, "a.c", &[(0, 0), (1, 2), (1, 10), (1, 13), (1, 14), (2, 0), (2, 6), (3, 0), (4, 9)]), vec![
+        Some("outer".to_string()),
+        Some("outer".to_string()),
+        Some("inner".to_string()),
+        // the end is inclusive
+        Some("inner".to_string()),
+        Some("outer".to_string()),
+        // both start at 2:0, the shorter wins
+        Some("inner".to_string()),
+        Some("inner".to_string()),
+        None,
+        Some("other".to_string()),
+    ]);
+    assert_eq!(names(&db, "b.c", &[(1, 10)]), vec![None]);
+}
+
+#[test]
+fn utf16_columns() {
+    // `s = "😀"; x = 1;`, where the emoji is 2 UTF-16 units and 4 bytes, so x is at column 10, byte 12
+    let mut f: ClangdFile = Default::default();
+    f.symbols.data = vec![
+        symbol(id(1), SymbolKind::Variable, "", "s", location("a.c", 0, 0, 1)),
+        symbol(id(2), SymbolKind::Variable, "", "x", location("a.c", 0, 10, 1)),
+    ];
+    let db = clangd_parser::build([("a.c.0000000000000000.idx".to_string(), f)].into(), vec![]);
+    assert_eq!(names(&db, "a.c", &[(0, 10), (0, 12)]), vec![Some("x".to_string()), None]);
+}
@@ -0,0 +1,115 @@
+//! Export a small index to LSIF: each symbol's definition, references and hover, and ranges owned by one symbol.
+
+mod common;
+
+use serde_json::Value;
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use clangd_parser::clangd::ClangdDatabase;
+use clangd_parser::lsif;
+use clangd_parser::refs::RefKind;
+use clangd_parser::symbols::{SymbolKind, SymbolLocation};
+
+use common::{id, reference, symbol};
+
+const MAX: [u8; 8] = id(1);
+const LIMIT: [u8; 8] = id(2);
+
+fn location(line: u32) -> SymbolLocation {
+    common::location("src/motor.c", line, 8, 3)
+}
+
+// the macro MAX is defined on line 1 and expands to LIMIT on line 5, so both are referenced at the same spot
+fn database() -> ClangdDatabase {
+    let mut db = common::database(vec![
+        symbol(MAX, SymbolKind::Macro, "", "MAX", location(0)),
+        symbol(LIMIT, SymbolKind::Macro, "", "LIMIT", location(2)),
+    ]);
+    db.refs.insert(MAX, vec![reference(RefKind::Definition as u8, location(0), [0; 8]), reference(RefKind::Reference as u8, location(4), [0; 8])]);
+    db.refs.insert(LIMIT, vec![reference(RefKind::Definition as u8, location(2), [0; 8]), reference(RefKind::Reference as u8, location(4), [0; 8])]);
+    db
+}
+
+fn export() -> Vec<Value> {
+    let mut out: Vec<u8> = vec![];
+    lsif::export(&database(), Path::new(common::ROOT), &mut out).unwrap();
+    String::from_utf8(out).unwrap().lines().map(|l| serde_json::from_str(l).unwrap()).collect()
+}
+
+#[test]
+fn shared_locations_keep_one_owner() {
+    let lines = export();
+    let edges = |label: &str| lines.iter().filter(|v| v["type"] == "edge" && v["label"] == label).cloned().collect::<Vec<Value>>();
+
+    let documents = lines.iter().filter(|v| v["label"] == "document").count();
+    assert_eq!(documents, 1);
+    // one range per location: lines 1, 3 and 5
+    let ranges = lines.iter().filter(|v| v["label"] == "range").count();
+    assert_eq!(ranges, 3);
+
+    // range -> its result set, and result -> the result set asking for it
+    let mut next: BTreeMap<u64, u64> = BTreeMap::new();
+    for e in edges("next") {
+        assert!(next.insert(e["outV"].as_u64().unwrap(), e["inV"].as_u64().unwrap()).is_none());
+    }
+    let mut owner: BTreeMap<u64, u64> = BTreeMap::new();
+    for label in ["textDocument/definition", "textDocument/references"] {
+        for e in edges(label) {
+            owner.insert(e["inV"].as_u64().unwrap(), e["outV"].as_u64().unwrap());
+        }
+    }
+    let items = edges("item");
+    assert!(!items.is_empty());
+    for e in items {
+        let result_set = owner[&e["outV"].as_u64().unwrap()];
+        for r in e["inVs"].as_array().unwrap() {
+            assert_eq!(next[&r.as_u64().unwrap()], result_set, "{}", e);
+        }
+    }
+}
+
+// The vertex `label` leads to from `out_v`, i.e. a result set's hoverResult
+fn follow<'a>(lines: &'a [Value], label: &str, out_v: &Value) -> &'a Value {
+    let e = lines.iter().find(|v| v["label"] == label && v["outV"] == *out_v).unwrap_or_else(|| panic!("no {} from {}", label, out_v));
+    lines.iter().find(|v| v["id"] == e["inV"]).unwrap()
+}
+
+// Ranges in `label` item edges from `out_v` with `property`, as (line, character)
+fn items(lines: &[Value], out_v: &Value, property: Option<&str>) -> Vec<(u64, u64)> {
+    lines.iter()
+        .filter(|v| v["label"] == "item" && v["outV"] == *out_v && property.is_none_or(|p| v["property"] == p))
+        .flat_map(|e| e["inVs"].as_array().unwrap().iter())
+        .map(|r| {
+            let range = lines.iter().find(|v| v["id"] == *r).unwrap();
+            (range["start"]["line"].as_u64().unwrap(), range["start"]["character"].as_u64().unwrap())
+        })
+        .collect()
+}
+
+#[test]
+fn definition_references_and_hover() {
+    let lines = export();
+    let result_set = |name: &str| {
+        let hover = lines.iter()
+            .find(|v| v["label"] == "hoverResult" && v["result"]["contents"][0]["value"] == format!("#define {}", name))
+            .unwrap();
+        let edge = lines.iter().find(|v| v["label"] == "textDocument/hover" && v["inV"] == hover["id"]).unwrap();
+        edge["outV"].clone()
+    };
+
+    let max = result_set("MAX");
+    assert_eq!(follow(&lines, "textDocument/hover", &max)["result"]["contents"][0]["language"], "c");
+    let def = follow(&lines, "textDocument/definition", &max);
+    assert_eq!(items(&lines, &def["id"], None), vec![(0, 8)]);
+    let refs = follow(&lines, "textDocument/references", &max);
+    assert_eq!(items(&lines, &refs["id"], Some("definitions")), vec![(0, 8)]);
+    assert_eq!(items(&lines, &refs["id"], Some("references")), vec![(4, 8)]);
+
+    // the expansion on line 5 went to MAX
+    let limit = result_set("LIMIT");
+    let refs = follow(&lines, "textDocument/references", &limit);
+    assert_eq!(items(&lines, &refs["id"], Some("definitions")), vec![(2, 8)]);
+    assert!(items(&lines, &refs["id"], Some("references")).is_empty());
+}
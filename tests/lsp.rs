@@ -0,0 +1,83 @@
+//! The language server over an in-memory connection.
+
+mod common;
+
+use lsp_server::{Connection, Message, Notification, Request, RequestId, ResponseError};
+use serde_json::{Value, json};
+
+use clangd_parser::lsp;
+
+use common::shapes::{CALL, database};
+
+// Send `method` with `params` and wait for its response
+fn request(client: &Connection, id: i32, method: &str, params: Value) -> Result<Value, ResponseError> {
+    client.sender.send(Message::Request(Request::new(RequestId::from(id), method.to_string(), params))).unwrap();
+    loop {
+        if let Message::Response(r) = client.receiver.recv().unwrap() {
+            assert_eq!(r.id, RequestId::from(id));
+            return r.response_result;
+        }
+    }
+}
+
+fn notify(client: &Connection, method: &str, params: Value) {
+    client.sender.send(Message::Notification(Notification::new(method.to_string(), params))).unwrap();
+}
+
+fn position(file: &str, line: u32, character: u32) -> Value {
+    json!({
+        "textDocument": { "uri": common::uri(file).as_str() },
+        "position": { "line": line, "character": character },
+    })
+}
+
+// Run `f` against a server on `database(CALL)`, initialized and shut down around it
+fn with_server(f: impl FnOnce(&Connection)) {
+    let db = database(CALL);
+    let (server, client) = Connection::memory();
+    std::thread::scope(|s| {
+        let handle = s.spawn(|| lsp::serve_connection(&db, &server).unwrap());
+        let init = request(&client, 0, "initialize", json!({ "capabilities": {} }));
+        assert_eq!(init.unwrap()["capabilities"]["typeHierarchyProvider"], json!(true));
+        notify(&client, "initialized", json!({}));
+        f(&client);
+        assert!(request(&client, 99, "shutdown", Value::Null).is_ok());
+        notify(&client, "exit", Value::Null);
+        handle.join().unwrap();
+    });
+}
+
+#[test]
+fn type_hierarchy() {
+    with_server(|client| {
+        let items = request(client, 1, "textDocument/prepareTypeHierarchy", position("src/shape.h", 5, 8)).unwrap();
+        assert_eq!(items[0]["name"], json!("Circle"));
+        let supers = request(client, 2, "typeHierarchy/supertypes", json!({ "item": items[0] })).unwrap();
+        assert_eq!(supers.as_array().unwrap().len(), 1);
+        assert_eq!(supers[0]["name"], json!("Shape"));
+        // functions have no type hierarchy
+        let none = request(client, 3, "textDocument/prepareTypeHierarchy", position("src/main.c", 1, 6)).unwrap();
+        assert_eq!(none, Value::Null);
+    });
+}
+
+#[test]
+fn call_hierarchy() {
+    with_server(|client| {
+        let items = request(client, 1, "textDocument/prepareCallHierarchy", position("src/main.c", 8, 5)).unwrap();
+        assert_eq!(items[0]["name"], json!("main"));
+        let calls = request(client, 2, "callHierarchy/outgoingCalls", json!({ "item": items[0] })).unwrap();
+        let calls = calls.as_array().unwrap();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0]["to"]["name"], json!("draw"));
+        assert_eq!(calls[0]["fromRanges"][0]["start"], json!({ "line": 9, "character": 4 }));
+    });
+}
+
+#[test]
+fn unsupported_method() {
+    with_server(|client| {
+        let e = request(client, 1, "textDocument/hover", position("src/main.c", 1, 6)).unwrap_err();
+        assert!(e.message.contains("textDocument/hover"));
+    });
+}
@@ -0,0 +1,73 @@
+//! Pick out a file's external calls and stub them.
+
+mod common;
+
+use clangd_parser::clangd::ClangdDatabase;
+use clangd_parser::mocks::{self, MockStyle};
+use clangd_parser::refs::RefKind;
+use clangd_parser::symbols::{Symbol, SymbolKind, SymbolLocation};
+
+use common::{id, reference, uri};
+
+const RUN: [u8; 8] = id(1);
+const A_RESET: [u8; 8] = id(2);
+const B_RESET: [u8; 8] = id(3);
+const CLAMP: [u8; 8] = id(4);
+const HELPER: [u8; 8] = id(5);
+
+// declared in include/app.h
+fn function(id: [u8; 8], scope: &str, name: &str, defined_in: &str) -> Symbol {
+    Symbol {
+        canonical_declaration: SymbolLocation { file_uri: uri("include/app.h"), ..Default::default() },
+        signature: "(int n)".to_string(),
+        return_t: "int".to_string(),
+        ..common::symbol(id, SymbolKind::Function, scope, name, SymbolLocation { file_uri: uri(defined_in), ..Default::default() })
+    }
+}
+
+// app.c's run() calls a::reset(), b::reset(), the inline clamp() and its own helper()
+fn database() -> ClangdDatabase {
+    let mut db = common::database(vec![
+        function(RUN, "", "run", "src/app.c"),
+        function(A_RESET, "a::", "reset", "src/a.c"),
+        function(B_RESET, "b::", "reset", "src/b.c"),
+        function(CLAMP, "", "clamp", "include/util.h"),
+        function(HELPER, "", "helper", "src/app.c"),
+    ]);
+    let call = reference(
+        RefKind::Reference as u8 | RefKind::Call as u8,
+        SymbolLocation { file_uri: uri("src/app.c"), ..Default::default() },
+        RUN,
+    );
+    for id in [A_RESET, B_RESET, CLAMP, HELPER] {
+        db.refs.insert(id, vec![call.clone()]);
+    }
+    db
+}
+
+#[test]
+fn external_calls_skip_own_and_header_definitions() {
+    let db = database();
+    let names: Vec<String> = mocks::external_calls(&db, &uri("src/app.c")).iter().map(|s| s.qualified_name()).collect();
+    assert_eq!(names, vec!["a::reset", "b::reset"]);
+}
+
+#[test]
+fn stub_state_is_named_after_the_qualified_name() {
+    let db = database();
+    let file = uri("src/app.c");
+    let deps = mocks::external_calls(&db, &file);
+    let out = mocks::generate(&file, &deps, MockStyle::Stub, &[]);
+    for line in [
+        "int a_reset_call_count;",
+        "int a_reset_return_value;",
+        "int b_reset_call_count;",
+        "int b_reset_return_value;",
+        "int a::reset(int n)",
+        "    b_reset_call_count++;",
+        "    return b_reset_return_value;",
+    ] {
+        assert!(out.lines().any(|l| l == line), "missing {:?} in\n{}", line, out);
+    }
+    assert!(!out.contains("\nint reset_call_count;"));
+}
@@ -0,0 +1,51 @@
+//! Outlines of a header and the source defining what it declares.
+
+mod common;
+
+use clangd_parser::outline::{OutlineKind, OutlineSymbol};
+use clangd_parser::symbols::{Symbol, SymbolKind};
+
+use common::{database, id, location, symbol, uri};
+
+fn symbols() -> Vec<Symbol> {
+    // shape.h declares struct shape { int sides; } and area(); shape.c defines area() and a static helper
+    let mut area = symbol(id(3), SymbolKind::Function, "", "area", location("src/shape.c", 3, 4, 4));
+    area.canonical_declaration = location("src/shape.h", 4, 4, 4);
+    area.signature = "(struct shape *)".to_string();
+    vec![
+        symbol(id(1), SymbolKind::Struct, "", "shape", location("src/shape.h", 0, 7, 5)),
+        symbol(id(2), SymbolKind::Field, "shape::", "sides", location("src/shape.h", 1, 8, 5)),
+        area,
+        symbol(id(4), SymbolKind::Function, "", "helper", location("src/shape.c", 0, 11, 6)),
+    ]
+}
+
+fn names(outline: &[OutlineSymbol]) -> Vec<(&str, OutlineKind, u32, Vec<&str>)> {
+    outline.iter()
+        .map(|s| (s.name.as_str(), s.kind, s.location.start.line, s.children.iter().map(|c| c.name.as_str()).collect()))
+        .collect()
+}
+
+#[test]
+fn header() {
+    let db = database(symbols());
+    let outline = db.outline_uri(&uri("src/shape.h"));
+    assert_eq!(names(&outline), vec![
+        ("shape", OutlineKind::Struct, 0, vec!["sides"]),
+        // declared here, defined in shape.c
+        ("area", OutlineKind::Function, 4, vec![]),
+    ]);
+    assert_eq!(outline[0].children[0].kind, OutlineKind::Field);
+}
+
+#[test]
+fn source() {
+    let db = database(symbols());
+    let outline = db.outline_uri(&uri("src/shape.c"));
+    assert_eq!(names(&outline), vec![
+        ("helper", OutlineKind::Function, 0, vec![]),
+        ("area", OutlineKind::Function, 3, vec![]),
+    ]);
+    assert_eq!(outline[1].detail, "(struct shape *)");
+    assert!(db.outline_uri(&uri("src/other.c")).is_empty());
+}
@@ -0,0 +1,120 @@
+//! Path mappings, as given to --path-mappings, applied to paths, URIs, compile arguments and whole shards.
+
+use clangd_parser::clangd::ClangdFile;
+use clangd_parser::cmdl::Cmdl;
+use clangd_parser::paths::{self, PathMapping};
+use clangd_parser::refs::{RefReferences, Refs};
+use clangd_parser::srcs::Srcs;
+use clangd_parser::symbols::{Symbol, SymbolIncludedHeader, SymbolLocation};
+use clangd_parser::uri::FileUri;
+
+fn mapping(from: &str, to: &str) -> PathMapping {
+    PathMapping { from: from.to_string(), to: to.to_string() }
+}
+
+fn mappings() -> Vec<PathMapping> {
+    PathMapping::parse_list("/ci/work=/home/me/work,/ci=/opt/ci").unwrap()
+}
+
+#[test]
+fn parse_list() {
+    assert_eq!(PathMapping::parse_list("/ci/work=/home/me/work").unwrap(), vec![mapping("/ci/work", "/home/me/work")]);
+    // trailing slashes, whitespace and empty entries
+    assert_eq!(PathMapping::parse_list(" /ci/work/ = /home/me/work/ ,, /ci=/opt/ci,").unwrap(), vec![
+        mapping("/ci/work", "/home/me/work"),
+        mapping("/ci", "/opt/ci"),
+    ]);
+    assert_eq!(PathMapping::parse_list("").unwrap(), vec![]);
+    for bad in ["/ci/work", "=/home/me", "/ci/work=", " = /home/me", "/ci= ", "/a=/b,/c"] {
+        assert!(PathMapping::parse_list(bad).is_err(), "{}", bad);
+    }
+    assert_eq!(PathMapping::parse_list("/a").unwrap_err(), "Invalid path mapping '/a', expected from=to");
+}
+
+#[test]
+fn apply_on_component_boundaries() {
+    let m = mapping("/work", "/home/me/work");
+    assert_eq!(m.apply("/work"), Some("/home/me/work".to_string()));
+    assert_eq!(m.apply("/work/src/a.c"), Some("/home/me/work/src/a.c".to_string()));
+    assert_eq!(m.apply("/workspace/a.c"), None);
+    assert_eq!(m.apply("/other/work/a.c"), None);
+    assert_eq!(m.apply("work/a.c"), None);
+}
+
+#[test]
+fn first_matching_mapping_wins() {
+    let m = mappings();
+    assert_eq!(paths::remap_path("/ci/work/src/a.c", &m), "/home/me/work/src/a.c");
+    assert_eq!(paths::remap_path("/ci/build/a.o", &m), "/opt/ci/build/a.o");
+    assert_eq!(paths::remap_path("/cid/a.c", &m), "/cid/a.c");
+}
+
+#[test]
+fn remap_uri() {
+    let m = mappings();
+    assert_eq!(paths::remap_uri(&FileUri::new("file:///ci/work/my%20file.c"), &m).as_str(), "file:///home/me/work/my%20file.c");
+    assert_eq!(paths::remap_uri(&FileUri::new("file:///elsewhere/a.c"), &m).as_str(), "file:///elsewhere/a.c");
+    assert_eq!(paths::remap_uri(&FileUri::new("test:///ci/work/a.c"), &m).as_str(), "test:///ci/work/a.c");
+    assert_eq!(paths::remap_uri(&FileUri::new("<stdio.h>"), &m).as_str(), "<stdio.h>");
+}
+
+#[test]
+fn remap_argument() {
+    let m = mappings();
+    for (arg, remapped) in [
+        ("-I/ci/work/include", "-I/home/me/work/include"),
+        // `-I /path` comes as two arguments
+        ("-I", "-I"),
+        ("/ci/work/include", "/home/me/work/include"),
+        ("-isystem/ci/sys", "-isystem/opt/ci/sys"),
+        ("-isysroot/ci/sdk", "-isysroot/opt/ci/sdk"),
+        ("--sysroot=/ci/sdk", "--sysroot=/opt/ci/sdk"),
+        ("-include/ci/work/pch.h", "-include/home/me/work/pch.h"),
+        ("-o/ci/work/a.o", "-o/home/me/work/a.o"),
+        // relative paths and other flags stay
+        ("-Iinclude", "-Iinclude"),
+        ("-DROOT=/ci/work", "-DROOT=/ci/work"),
+        ("-I/cid/include", "-I/cid/include"),
+        ("-O2", "-O2"),
+    ] {
+        assert_eq!(paths::remap_argument(arg, &m), remapped, "{}", arg);
+    }
+}
+
+#[test]
+fn remap_shard() {
+    let at = |p: &str| SymbolLocation { file_uri: FileUri::new(&format!("file://{}", p)), ..Default::default() };
+    let mut f: ClangdFile = Default::default();
+    f.symbols.data.push(Symbol {
+        definition: at("/ci/work/src/a.c"),
+        canonical_declaration: at("/ci/work/include/a.h"),
+        headers: vec![SymbolIncludedHeader { name: FileUri::new("file:///ci/work/include/a.h"), refs: 1, supported_directives: 1 }],
+        ..Default::default()
+    });
+    f.references.data.push(Refs { id: [1; 8], cnt: 1, refs: vec![RefReferences { kind: 0, location: at("/ci/work/src/b.c"), container_id: [0; 8] }] });
+    f.sources.data.push(Srcs {
+        uri: FileUri::new("file:///ci/work/src/a.c"),
+        direct_includes: vec![FileUri::new("file:///ci/work/include/a.h"), FileUri::new("<stdio.h>")],
+        ..Default::default()
+    });
+    f.cmdline.data.push(Cmdl {
+        directory: "/ci/build".to_string(),
+        cmdl: ["cc", "-I", "/ci/work/include", "-c", "/ci/work/src/a.c"].iter().map(|s| s.to_string()).collect(),
+    });
+
+    let unchanged = f.clone();
+    f.remap_paths(&[]);
+    assert_eq!(f.symbols.data, unchanged.symbols.data);
+
+    f.remap_paths(&mappings());
+    let s = &f.symbols.data[0];
+    assert_eq!(s.definition.file_uri.as_str(), "file:///home/me/work/src/a.c");
+    assert_eq!(s.canonical_declaration.file_uri.as_str(), "file:///home/me/work/include/a.h");
+    assert_eq!(s.headers[0].name.as_str(), "file:///home/me/work/include/a.h");
+    assert_eq!(f.references.data[0].refs[0].location.file_uri.as_str(), "file:///home/me/work/src/b.c");
+    assert_eq!(f.sources.data[0].uri.as_str(), "file:///home/me/work/src/a.c");
+    assert_eq!(f.sources.data[0].direct_includes[0].as_str(), "file:///home/me/work/include/a.h");
+    assert_eq!(f.sources.data[0].direct_includes[1].as_str(), "<stdio.h>");
+    assert_eq!(f.cmdline.data[0].directory, "/opt/ci/build");
+    assert_eq!(f.cmdline.data[0].cmdl, vec!["cc", "-I", "/home/me/work/include", "-c", "/home/me/work/src/a.c"]);
+}
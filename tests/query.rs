@@ -0,0 +1,50 @@
+//! Callers, callees and the class hierarchy, from a database built the way the loader builds it.
+
+mod common;
+
+use clangd_parser::symbols::{Symbol, SymbolId};
+
+use common::location;
+use common::shapes::{CALL, CIRCLE, DRAW, MAIN, ON_DRAW, REFERENCE, SHAPE, database};
+
+fn names(syms: Vec<&Symbol>) -> Vec<&str> {
+    syms.iter().map(|s| s.name.as_str()).collect()
+}
+
+#[test]
+fn calls_marked_by_the_index() {
+    let db = database(CALL);
+    // both shards have the call, the database keeps one
+    assert_eq!(db.references(&DRAW).len(), 1);
+    assert_eq!(db.callers(&DRAW).keys().copied().collect::<Vec<SymbolId>>(), vec![MAIN]);
+    // taking the address is no call
+    assert!(db.callers(&ON_DRAW).is_empty());
+    let callees = db.callees(&MAIN);
+    assert_eq!(callees.keys().copied().collect::<Vec<SymbolId>>(), vec![DRAW]);
+    assert_eq!(callees[&DRAW], vec![location("src/main.c", 9, 4, 4)]);
+    assert!(db.callees(&DRAW).is_empty());
+}
+
+#[test]
+fn calls_in_older_indexes() {
+    // without RefKind::Call anywhere, every reference from a function counts
+    let db = database(REFERENCE);
+    assert_eq!(db.callers(&ON_DRAW).keys().copied().collect::<Vec<SymbolId>>(), vec![MAIN]);
+    assert_eq!(db.callees(&MAIN).keys().copied().collect::<Vec<SymbolId>>(), vec![DRAW, ON_DRAW]);
+}
+
+#[test]
+fn type_hierarchy() {
+    let db = database(CALL);
+    assert_eq!(names(db.supertypes(&CIRCLE)), vec!["Shape"]);
+    assert_eq!(names(db.subtypes(&SHAPE)), vec!["Circle"]);
+    assert!(db.supertypes(&SHAPE).is_empty());
+    assert!(db.subtypes(&DRAW).is_empty());
+}
+
+#[test]
+fn search() {
+    let db = database(CALL);
+    assert_eq!(names(db.search("DRAW")), vec!["draw", "on_draw"]);
+    assert!(db.search("square").is_empty());
+}
@@ -0,0 +1,232 @@
+//! Drive the remote-index gRPC server over localhost with a stub client.
+
+mod common;
+
+use tonic::codec::ProstCodec;
+use tonic::codegen::http::uri::PathAndQuery;
+use tonic::transport::Channel;
+use tonic::{Code, Status};
+
+use std::net::SocketAddr;
+use std::path::Path;
+
+use clangd_parser::clangd::{ClangdDatabase, ClangdFile};
+use clangd_parser::refs::RefKind;
+use clangd_parser::rela::{Rela, RelationKind};
+use clangd_parser::remote::proto::*;
+use clangd_parser::remote::{self, RemoteIndex, SERVICE_NAME};
+use clangd_parser::symbols::{Symbol, SymbolKind, SymbolLocation};
+use clangd_parser::uri::FileUri;
+
+use common::{id, location, reference};
+
+const BASE: [u8; 8] = id(1);
+const DERIVED: [u8; 8] = id(2);
+const RUN: [u8; 8] = id(3);
+const PRINTF: [u8; 8] = id(4);
+
+// everything lives in namespace app
+fn symbol(id: [u8; 8], kind: SymbolKind, name: &str, loc: SymbolLocation) -> Symbol {
+    common::symbol(id, kind, "app::", name, loc)
+}
+
+fn database() -> ClangdDatabase {
+    let mut db = common::database(vec![
+        symbol(BASE, SymbolKind::Class, "Base", location("src/shape.h", 2, 6, 4)),
+        symbol(DERIVED, SymbolKind::Class, "Derived", location("src/shape.h", 8, 6, 7)),
+        symbol(RUN, SymbolKind::Function, "run", location("src/main.cpp", 4, 5, 3)),
+    ]);
+    // outside the project root, so never sent
+    let mut printf = symbol(PRINTF, SymbolKind::Function, "printf", Default::default());
+    printf.canonical_declaration.file_uri = FileUri::new("file:///usr/include/stdio.h");
+    db.id.insert(PRINTF, printf);
+
+    db.refs.insert(BASE, vec![
+        reference(RefKind::Definition as u8, location("src/shape.h", 2, 6, 4), [0; 8]),
+        reference(RefKind::Reference as u8, location("src/main.cpp", 6, 4, 4), RUN),
+    ]);
+    db.refs.insert(DERIVED, vec![
+        reference(RefKind::Reference as u8, location("src/main.cpp", 7, 4, 7), RUN),
+    ]);
+
+    let mut file: ClangdFile = Default::default();
+    file.relations.data.push(Rela { subject: BASE, predicate: RelationKind::BaseOf, object: DERIVED });
+    db.file.insert("main.cpp.0123456789ABCDEF.idx".to_string(), file);
+    db
+}
+
+async fn start() -> SocketAddr {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let index = RemoteIndex::new(database(), Path::new(common::ROOT));
+    tokio::spawn(remote::serve(index, listener));
+    addr
+}
+
+// A stub client, calling `method` the way clangd does and collecting the stream
+async fn call<Req, Reply>(addr: SocketAddr, method: &str, req: Req) -> Result<Vec<Reply>, Status>
+where
+    Req: prost::Message + Send + Sync + 'static,
+    Reply: prost::Message + Default + Send + Sync + 'static,
+{
+    let channel = Channel::from_shared(format!("http://{}", addr)).unwrap().connect().await.unwrap();
+    let mut client = tonic::client::Grpc::new(channel);
+    client.ready().await.unwrap();
+    let path = PathAndQuery::try_from(format!("/{}/{}", SERVICE_NAME, method)).unwrap();
+    let mut stream = client.server_streaming(tonic::Request::new(req), path, ProstCodec::default()).await?.into_inner();
+    let mut replies: Vec<Reply> = vec![];
+    while let Some(r) = stream.message().await? {
+        replies.push(r);
+    }
+    Ok(replies)
+}
+
+#[tokio::test]
+async fn lookup() {
+    let addr = start().await;
+    let req = LookupRequest { ids: vec!["0100000000000000".to_string(), "0400000000000000".to_string()] };
+    let replies: Vec<LookupReply> = call(addr, "Lookup", req).await.unwrap();
+
+    assert_eq!(replies.len(), 2);
+    let Some(LookupReplyKind::StreamResult(s)) = &replies[0].kind else { panic!("expected a symbol") };
+    assert_eq!(s.id.as_deref(), Some("0100000000000000"));
+    assert_eq!(s.name.as_deref(), Some("Base"));
+    assert_eq!(s.scope.as_deref(), Some("app::"));
+    let def = s.definition.as_ref().unwrap();
+    assert_eq!(def.file_path.as_deref(), Some("src/shape.h"));
+    assert_eq!(def.start, Some(Position { line: Some(2), column: Some(6) }));
+    assert_eq!(replies[1].kind, Some(LookupReplyKind::FinalResult(FinalResult { has_more: Some(false) })));
+}
+
+#[tokio::test]
+async fn fuzzy_find() {
+    let addr = start().await;
+    let req = FuzzyFindRequest { query: Some("drv".to_string()), any_scope: Some(true), ..Default::default() };
+    let replies: Vec<FuzzyFindReply> = call(addr, "FuzzyFind", req).await.unwrap();
+    let names: Vec<String> = replies.iter()
+        .filter_map(|r| match &r.kind {
+            Some(FuzzyFindReplyKind::StreamResult(s)) => s.name.clone(),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(names, vec!["Derived"]);
+
+    let req = FuzzyFindRequest { scopes: vec!["other::".to_string()], ..Default::default() };
+    let replies: Vec<FuzzyFindReply> = call(addr, "FuzzyFind", req).await.unwrap();
+    assert_eq!(replies.len(), 1);
+
+    let req = FuzzyFindRequest { scopes: vec!["app::".to_string()], limit: Some(1), ..Default::default() };
+    let replies: Vec<FuzzyFindReply> = call(addr, "FuzzyFind", req).await.unwrap();
+    assert_eq!(replies.len(), 2);
+    assert_eq!(replies[1].kind, Some(FuzzyFindReplyKind::FinalResult(FinalResult { has_more: Some(true) })));
+}
+
+#[tokio::test]
+async fn refs() {
+    let addr = start().await;
+    let req = RefsRequest {
+        ids: vec!["0100000000000000".to_string()],
+        filter: Some(RefKind::Reference as u32),
+        want_container: Some(true),
+        ..Default::default()
+    };
+    let replies: Vec<RefsReply> = call(addr, "Refs", req).await.unwrap();
+
+    assert_eq!(replies.len(), 2);
+    let Some(RefsReplyKind::StreamResult(r)) = &replies[0].kind else { panic!("expected a ref") };
+    assert_eq!(r.kind, Some(RefKind::Reference as u32));
+    assert_eq!(r.container.as_deref(), Some("0300000000000000"));
+    assert_eq!(r.location.as_ref().unwrap().file_path.as_deref(), Some("src/main.cpp"));
+}
+
+#[tokio::test]
+async fn contained_refs() {
+    let addr = start().await;
+    let req = ContainedRefsRequest { id: "0300000000000000".to_string(), limit: None };
+    let replies: Vec<ContainedRefsReply> = call(addr, "ContainedRefs", req).await.unwrap();
+    let symbols: Vec<String> = replies.iter()
+        .filter_map(|r| match &r.kind {
+            Some(ContainedRefsReplyKind::StreamResult(c)) => Some(c.symbol.clone()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(symbols, vec!["0100000000000000", "0200000000000000"]);
+}
+
+#[tokio::test]
+async fn relations() {
+    let addr = start().await;
+    let req = RelationsRequest {
+        subjects: vec!["0100000000000000".to_string()],
+        predicate: Some(RelationKind::BaseOf as u32),
+        limit: None,
+    };
+    let replies: Vec<RelationsReply> = call(addr, "Relations", req).await.unwrap();
+
+    assert_eq!(replies.len(), 2);
+    let Some(RelationsReplyKind::StreamResult(r)) = &replies[0].kind else { panic!("expected a relation") };
+    assert_eq!(r.subject_id.as_deref(), Some("0100000000000000"));
+    assert_eq!(r.object.as_ref().unwrap().name.as_deref(), Some("Derived"));
+}
+
+#[tokio::test]
+async fn invalid_requests() {
+    let addr = start().await;
+    let req = LookupRequest { ids: vec!["not-an-id".to_string()] };
+    let err = call::<_, LookupReply>(addr, "Lookup", req).await.unwrap_err();
+    assert_eq!(err.code(), Code::InvalidArgument);
+
+    let err = call::<_, LookupReply>(addr, "Monitor", LookupRequest::default()).await.unwrap_err();
+    assert_eq!(err.code(), Code::Unimplemented);
+}
+
+fn has_more<T>(replies: &[T], last: impl Fn(&T) -> Option<bool>) -> Option<bool> {
+    replies.last().and_then(last)
+}
+
+#[test]
+fn refs_limit_across_ids() {
+    let index = RemoteIndex::new(database(), Path::new(common::ROOT));
+    let request = |limit: u32| RefsRequest {
+        ids: vec!["0100000000000000".to_string(), "0200000000000000".to_string(), "not-an-id".to_string()],
+        limit: Some(limit),
+        ..Default::default()
+    };
+    let more = |r: &RefsReply| match &r.kind {
+        Some(RefsReplyKind::FinalResult(f)) => f.has_more,
+        _ => None,
+    };
+
+    // full within the first id's refs: the remaining ids, the invalid one included, are never parsed
+    let replies = index.refs(request(1)).unwrap();
+    assert_eq!(replies.len(), 2);
+    assert_eq!(has_more(&replies, more), Some(true));
+    // full after the first id, the second one has more
+    let replies = index.refs(request(2)).unwrap();
+    assert_eq!(replies.len(), 3);
+    assert_eq!(has_more(&replies, more), Some(true));
+    // room to spare, so the invalid id is reached
+    assert_eq!(index.refs(request(3)).unwrap_err().code(), Code::InvalidArgument);
+
+    let req = RefsRequest { ids: vec!["0100000000000000".to_string(), "0200000000000000".to_string()], limit: Some(3), ..Default::default() };
+    let replies = index.refs(req).unwrap();
+    assert_eq!(replies.len(), 4);
+    assert_eq!(has_more(&replies, more), Some(false));
+}
+
+#[test]
+fn contained_refs_limit_across_targets() {
+    let index = RemoteIndex::new(database(), Path::new(common::ROOT));
+    let request = |limit: u32| ContainedRefsRequest { id: "0300000000000000".to_string(), limit: Some(limit) };
+    let more = |r: &ContainedRefsReply| match &r.kind {
+        Some(ContainedRefsReplyKind::FinalResult(f)) => f.has_more,
+        _ => None,
+    };
+
+    let replies = index.contained_refs(request(1)).unwrap();
+    assert_eq!(replies.len(), 2);
+    assert_eq!(has_more(&replies, more), Some(true));
+    let replies = index.contained_refs(request(2)).unwrap();
+    assert_eq!(replies.len(), 3);
+    assert_eq!(has_more(&replies, more), Some(false));
+}
@@ -0,0 +1,69 @@
+//! SARIF logs: rule ids and indices, and where results point.
+
+mod common;
+
+use serde_json::{Value, json};
+
+use clangd_parser::sarif::{Finding, Level, Rule, Sarif};
+use clangd_parser::symbols::SymbolLocation;
+
+use common::{location, uri};
+
+const RULES: [Rule; 2] = [
+    Rule { id: "unused-function", description: "Function is never referenced" },
+    Rule { id: "unused-include", description: "Include is never used" },
+];
+
+fn finding(rule: &'static str, location: SymbolLocation) -> Finding {
+    Finding { rule, level: Level::Warning, message: format!("{} here", rule), location }
+}
+
+fn log(findings: &[Finding]) -> Value {
+    serde_json::to_value(Sarif::new(&RULES, findings)).unwrap()
+}
+
+#[test]
+fn rules() {
+    let v = log(&[]);
+    assert_eq!(v["version"], json!("2.1.0"));
+    let driver = &v["runs"][0]["tool"]["driver"];
+    assert_eq!(driver["name"], json!("clangd-parser"));
+    assert_eq!(driver["rules"], json!([
+        { "id": "unused-function", "shortDescription": { "text": "Function is never referenced" } },
+        { "id": "unused-include", "shortDescription": { "text": "Include is never used" } },
+    ]));
+}
+
+#[test]
+fn results() {
+    let v = log(&[
+        finding("unused-include", location("src/motor.c", 2, 0, 19)),
+        finding("unused-function", location("src/motor.c", 10, 4, 6)),
+        // a rule the run doesn't describe gets no index rather than another rule's
+        finding("unknown", location("src/motor.c", 12, 0, 1)),
+    ]);
+    let results = v["runs"][0]["results"].as_array().unwrap();
+    let rules: Vec<(&Value, Option<&Value>)> = results.iter().map(|r| (&r["ruleId"], r.get("ruleIndex"))).collect();
+    assert_eq!(rules, vec![
+        (&json!("unused-include"), Some(&json!(1))),
+        (&json!("unused-function"), Some(&json!(0))),
+        (&json!("unknown"), None),
+    ]);
+    assert_eq!(results[1]["level"], json!("warning"));
+    assert_eq!(results[1]["message"]["text"], json!("unused-function here"));
+    // regions are 1-based
+    assert_eq!(results[1]["locations"], json!([{
+        "physicalLocation": {
+            "artifactLocation": { "uri": "file:///work/src/motor.c" },
+            "region": { "startLine": 11, "startColumn": 5, "endLine": 11, "endColumn": 11 },
+        },
+    }]));
+}
+
+#[test]
+fn whole_file() {
+    let v = log(&[finding("unused-include", SymbolLocation { file_uri: uri("src/motor.h"), ..Default::default() })]);
+    assert_eq!(v["runs"][0]["results"][0]["locations"][0]["physicalLocation"], json!({
+        "artifactLocation": { "uri": "file:///work/src/motor.h" },
+    }));
+}
@@ -0,0 +1,169 @@
+//! Export a small index to SCIP and decode the protobuf back.
+
+mod common;
+
+use prost::Message;
+
+use std::path::Path;
+
+use clangd_parser::clangd::{ClangdDatabase, ClangdFile};
+use clangd_parser::refs::RefKind;
+use clangd_parser::rela::{Rela, RelationKind};
+use clangd_parser::scip::{self, proto::*};
+use clangd_parser::symbols::{SymbolFlags, SymbolKind, SymbolLocation};
+use clangd_parser::uri::FileUri;
+
+use common::{id, location, reference, symbol};
+
+const SHAPE: [u8; 8] = id(1);
+const CIRCLE: [u8; 8] = id(2);
+const AREA: [u8; 8] = id(3);
+const CIRCLE_AREA: [u8; 8] = id(4);
+const PRINTF: [u8; 8] = id(5);
+const HELPER: [u8; 8] = id(6);
+const SIZE: [u8; 8] = id(7);
+const INTERNAL: [u8; 8] = id(8);
+
+fn database() -> ClangdDatabase {
+    let mut db = common::database(vec![
+        symbol(SHAPE, SymbolKind::Class, "geo::", "Shape", location("src/shape.h", 2, 6, 5)),
+        symbol(CIRCLE, SymbolKind::Class, "geo::", "Circle", location("src/shape.h", 8, 6, 6)),
+    ]);
+    let mut area = symbol(AREA, SymbolKind::InstanceMethod, "geo::Shape::", "area", location("src/shape.h", 3, 19, 4));
+    area.return_t = "double".to_string();
+    area.signature = "() const".to_string();
+    area.documentation = "Area in square units.".to_string();
+    db.id.insert(AREA, area);
+    db.id.insert(CIRCLE_AREA, symbol(CIRCLE_AREA, SymbolKind::InstanceMethod, "geo::Circle::", "area", location("src/shape.h", 9, 11, 4)));
+    // outside the project root
+    let mut printf = symbol(PRINTF, SymbolKind::Function, "", "printf", Default::default());
+    printf.canonical_declaration = SymbolLocation { file_uri: FileUri::new("file:///usr/include/stdio.h"), ..Default::default() };
+    db.id.insert(PRINTF, printf);
+    db.id.insert(SIZE, symbol(SIZE, SymbolKind::Variable, "geo::", "größe", location("src/shape.h", 12, 4, 5)));
+    for s in db.id.values_mut() {
+        s.flags = SymbolFlags::VisibleOutsideFile as u8;
+    }
+    // static, in the project and outside it
+    db.id.insert(HELPER, symbol(HELPER, SymbolKind::Function, "", "helper", location("src/util.cpp", 1, 12, 6)));
+    let mut internal = symbol(INTERNAL, SymbolKind::Function, "", "internal", Default::default());
+    internal.canonical_declaration = SymbolLocation { file_uri: FileUri::new("file:///usr/include/stdio.h"), ..Default::default() };
+    db.id.insert(INTERNAL, internal);
+
+    db.refs.insert(SHAPE, vec![
+        reference(RefKind::Definition as u8 | RefKind::Spelled as u8, location("src/shape.h", 2, 6, 5), [0; 8]),
+        reference(RefKind::Reference as u8, location("src/shape.h", 8, 23, 5), CIRCLE),
+    ]);
+    db.refs.insert(HELPER, vec![reference(RefKind::Reference as u8, location("src/util.cpp", 4, 4, 6), [0; 8])]);
+    db.refs.insert(AREA, vec![
+        reference(RefKind::Declaration as u8, location("src/shape.h", 3, 19, 4), SHAPE),
+        reference(RefKind::Reference as u8, location("src/main.cpp", 5, 10, 4), [0; 8]),
+    ]);
+
+    let mut file: ClangdFile = Default::default();
+    file.relations.data.push(Rela { subject: SHAPE, predicate: RelationKind::BaseOf, object: CIRCLE });
+    file.relations.data.push(Rela { subject: AREA, predicate: RelationKind::OverriddenBy, object: CIRCLE_AREA });
+    db.file.insert("shape.h.0123456789ABCDEF.idx".to_string(), file);
+    db
+}
+
+fn decode() -> Index {
+    let mut buf: Vec<u8> = vec![];
+    scip::export(&database(), Path::new("/work"), &mut buf).unwrap();
+    Index::decode(buf.as_slice()).unwrap()
+}
+
+fn document<'a>(index: &'a Index, path: &str) -> &'a Document {
+    index.documents.iter().find(|d| d.relative_path == path).unwrap()
+}
+
+fn info<'a>(doc: &'a Document, display_name: &str, enclosing: &str) -> &'a SymbolInformation {
+    doc.symbols.iter().find(|s| s.display_name == display_name && s.enclosing_symbol.ends_with(enclosing)).unwrap()
+}
+
+#[test]
+fn metadata() {
+    let index = decode();
+    let metadata = index.metadata.unwrap();
+    assert_eq!(metadata.project_root, "file:///work");
+    assert_eq!(metadata.tool_info.unwrap().name, "clangd-parser");
+
+    let paths: Vec<&str> = index.documents.iter().map(|d| d.relative_path.as_str()).collect();
+    assert_eq!(paths, vec!["src/main.cpp", "src/shape.h", "src/util.cpp"]);
+    assert_eq!(index.documents[0].language, "CPP");
+    assert_eq!(index.documents[0].position_encoding, PositionEncoding::Utf16CodeUnitOffsetFromLineStart as i32);
+}
+
+#[test]
+fn symbol_strings() {
+    let index = decode();
+    let shape = document(&index, "src/shape.h");
+    assert_eq!(info(shape, "Shape", "").symbol, "cxx . . . geo/Shape#");
+    let area = info(shape, "area", "Shape#");
+    assert_eq!(area.symbol, "cxx . . . geo/Shape#area(0300000000000000).");
+    assert_eq!(area.kind, Kind::Method as i32);
+    assert_eq!(area.enclosing_symbol, "cxx . . . geo/Shape#");
+    assert_eq!(area.documentation, vec!["```c\ndouble geo::Shape::area() const\n```", "Area in square units."]);
+
+    // not a plain identifier
+    assert_eq!(info(shape, "größe", "").symbol, "cxx . . . geo/`größe`.");
+
+    // the static outside the root is dropped
+    assert_eq!(index.external_symbols.len(), 1);
+    assert_eq!(index.external_symbols[0].symbol, "cxx . . . printf(0500000000000000).");
+}
+
+#[test]
+fn occurrences() {
+    let index = decode();
+    let main = document(&index, "src/main.cpp");
+    assert_eq!(main.occurrences, vec![Occurrence {
+        range: vec![5, 10, 14],
+        symbol: "cxx . . . geo/Shape#area(0300000000000000).".to_string(),
+        symbol_roles: 0,
+    }]);
+
+    let shape = document(&index, "src/shape.h");
+    let roles = |symbol: &str| -> Vec<(Vec<i32>, i32)> {
+        shape.occurrences.iter().filter(|o| o.symbol == symbol).map(|o| (o.range.clone(), o.symbol_roles)).collect()
+    };
+    assert_eq!(roles("cxx . . . geo/Shape#"), vec![
+        (vec![2, 6, 11], SymbolRole::Definition as i32),
+        (vec![8, 23, 28], 0),
+    ]);
+    assert_eq!(roles("cxx . . . geo/Shape#area(0300000000000000)."), vec![(vec![3, 19, 23], SymbolRole::ForwardDefinition as i32)]);
+    // no refs, so the definition stands in
+    assert_eq!(roles("cxx . . . geo/Circle#"), vec![(vec![8, 6, 12], SymbolRole::Definition as i32)]);
+}
+
+#[test]
+fn relationships() {
+    let index = decode();
+    let shape = document(&index, "src/shape.h");
+    assert_eq!(info(shape, "Circle", "").relationships, vec![Relationship {
+        symbol: "cxx . . . geo/Shape#".to_string(),
+        is_implementation: true,
+        ..Default::default()
+    }]);
+    assert_eq!(info(shape, "area", "Circle#").relationships, vec![Relationship {
+        symbol: "cxx . . . geo/Shape#area(0300000000000000).".to_string(),
+        is_reference: true,
+        is_implementation: true,
+        ..Default::default()
+    }]);
+    assert!(info(shape, "Shape", "").relationships.is_empty());
+}
+
+#[test]
+fn local_symbols() {
+    let index = decode();
+    let util = document(&index, "src/util.cpp");
+    let helper = info(util, "helper", "");
+    assert_eq!(helper.symbol, "local 0600000000000000");
+    assert_eq!(helper.kind, Kind::Function as i32);
+    let occurrences: Vec<(&str, Vec<i32>, i32)> = util.occurrences.iter().map(|o| (o.symbol.as_str(), o.range.clone(), o.symbol_roles)).collect();
+    assert_eq!(occurrences, vec![
+        ("local 0600000000000000", vec![1, 12, 18], SymbolRole::Definition as i32),
+        ("local 0600000000000000", vec![4, 4, 10], 0),
+    ]);
+    assert!(index.external_symbols.iter().all(|s| !s.symbol.starts_with("local ")));
+}
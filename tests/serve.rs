@@ -0,0 +1,90 @@
+//! Routing and JSON of the HTTP queries, answered without a server.
+
+mod common;
+
+use serde_json::{Value, json};
+
+use clangd_parser::serve::handle;
+use clangd_parser::symbols::{SymbolId, symbol_id_str};
+
+use common::shapes::{CALL, CIRCLE, DRAW, MAIN, SHAPE, database};
+
+fn names(v: &Value) -> Vec<&str> {
+    v.as_array().unwrap().iter().map(|s| s["name"].as_str().unwrap()).collect()
+}
+
+fn url(id: &SymbolId, rest: &str) -> String {
+    format!("/symbols/{}{}", symbol_id_str(id), rest)
+}
+
+#[test]
+fn search() {
+    let db = database(CALL);
+    assert_eq!(names(&handle(&db, "/symbols?q=draw").unwrap()), vec!["draw", "on_draw"]);
+    assert_eq!(names(&handle(&db, "/symbols?q=draw&limit=1").unwrap()), vec!["draw"]);
+    assert_eq!(handle(&db, "/symbols?limit=x").unwrap_err().0, 400);
+}
+
+#[test]
+fn symbol() {
+    let db = database(CALL);
+    let v = handle(&db, &url(&DRAW, "")).unwrap();
+    assert_eq!(v["id"], json!(symbol_id_str(&DRAW)));
+    assert_eq!(v["name"], json!("draw"));
+    assert_eq!(v["kind"], json!("Function"));
+    assert_eq!(v["definition"]["file_uri"], json!("file:///work/src/main.c"));
+    assert_eq!(v["definition"]["start"], json!({ "line": 1, "column": 5 }));
+    // trailing slashes are ignored
+    assert_eq!(handle(&db, &url(&DRAW, "/")).unwrap(), v);
+}
+
+#[test]
+fn refs_and_calls() {
+    let db = database(CALL);
+    let refs = handle(&db, &url(&DRAW, "/refs")).unwrap();
+    assert_eq!(refs[0]["kinds"], json!(["reference", "call"]));
+    assert_eq!(refs[0]["container"], json!(symbol_id_str(&MAIN)));
+
+    let callers = handle(&db, &url(&DRAW, "/callers")).unwrap();
+    assert_eq!(callers[0]["symbol"]["name"], json!("main"));
+    assert_eq!(callers[0]["sites"][0]["start"], json!({ "line": 9, "column": 4 }));
+    let callees = handle(&db, &url(&MAIN, "/callees")).unwrap();
+    assert_eq!(callees.as_array().unwrap().len(), 1);
+    assert_eq!(callees[0]["symbol"]["name"], json!("draw"));
+}
+
+#[test]
+fn type_hierarchy() {
+    let db = database(CALL);
+    assert_eq!(names(&handle(&db, &url(&CIRCLE, "/supertypes")).unwrap()), vec!["Shape"]);
+    assert_eq!(names(&handle(&db, &url(&SHAPE, "/subtypes")).unwrap()), vec!["Circle"]);
+}
+
+#[test]
+fn symbol_at() {
+    let db = database(CALL);
+    let v = handle(&db, "/symbol-at?file=file%3A%2F%2F%2Fwork%2Fsrc%2Fmain.c&line=9&column=5").unwrap();
+    assert_eq!(v["name"], json!("draw"));
+    assert_eq!(handle(&db, "/symbol-at?file=file:///work/src/main.c&line=0&column=0").unwrap_err().0, 404);
+    assert_eq!(handle(&db, "/symbol-at?file=file:///work/src/main.c&line=x&column=0").unwrap_err().0, 400);
+    assert_eq!(handle(&db, "/symbol-at?line=9&column=5").unwrap_err().0, 400);
+}
+
+#[test]
+fn includes() {
+    let db = database(CALL);
+    let v = handle(&db, "/includes?file=file:///work/src/main.c").unwrap();
+    assert_eq!(v, json!({ "file": "file:///work/src/main.c", "includes": [], "included_by": [] }));
+}
+
+#[test]
+fn not_found() {
+    let db = database(CALL);
+    assert_eq!(handle(&db, "/").unwrap_err().0, 404);
+    assert_eq!(handle(&db, "/nothing").unwrap_err().0, 404);
+    assert_eq!(handle(&db, "/symbols/0011223344556677").unwrap_err().0, 404);
+    assert_eq!(handle(&db, "/symbols/not-an-id").unwrap_err().0, 404);
+    let (status, message) = handle(&db, &url(&DRAW, "/nothing")).unwrap_err();
+    assert_eq!(status, 404);
+    assert!(message.contains("/nothing"));
+}
@@ -0,0 +1,238 @@
+//! Symbol helpers that work on the parsed index rather than the IDX bytes.
+
+use clangd_parser::clangd::{ClangdDatabase, ClangdIdMap};
+use clangd_parser::symbols::{self, Symbol, SymbolIncludedHeader, SymbolInfo, SymbolKind, SymbolLocation, SymbolPosition, SymbolProperty};
+use clangd_parser::uri::FileUri;
+
+fn location(file: &str, line: u32) -> SymbolLocation {
+    SymbolLocation {
+        start: SymbolPosition { line, column: 4 },
+        end: SymbolPosition { line, column: 10 },
+        file_uri: FileUri::new(&format!("file:///work/{}", file)),
+    }
+}
+
+fn header(name: &str, refs: usize, supported_directives: usize) -> SymbolIncludedHeader {
+    SymbolIncludedHeader { name: FileUri::new(name), refs, supported_directives }
+}
+
+// motor_init() as seen by the header's shard and by the source's shard
+fn copies() -> (Symbol, Symbol) {
+    let declared = Symbol {
+        id: [1, 0, 0, 0, 0, 0, 0, 0],
+        name: "motor_init".to_string(),
+        canonical_declaration: location("include/motor.h", 3),
+        references: 2,
+        documentation: "Power up the driver.".to_string(),
+        headers: vec![header("\"motor.h\"", 2, 1)],
+        ..Default::default()
+    };
+    let defined = Symbol {
+        definition: location("src/motor.c", 12),
+        references: 3,
+        signature: "(int channel)".to_string(),
+        documentation: String::new(),
+        headers: vec![header("\"motor.h\"", 1, 2), header("<motor/all.h>", 1, 1)],
+        ..declared.clone()
+    };
+    (declared, defined)
+}
+
+#[test]
+fn merge_prefers_the_definition() {
+    let (declared, defined) = copies();
+    let mut merged = declared.clone();
+    merged.merge(&defined);
+
+    assert_eq!(merged.definition, location("src/motor.c", 12));
+    assert_eq!(merged.canonical_declaration, location("include/motor.h", 3));
+    assert_eq!(merged.references, 5);
+    assert_eq!(merged.signature, "(int channel)");
+    // filled in from the copy without a definition
+    assert_eq!(merged.documentation, "Power up the driver.");
+    assert_eq!(merged.headers, vec![header("\"motor.h\"", 3, 3), header("<motor/all.h>", 1, 1)]);
+}
+
+#[test]
+fn merge_is_order_independent() {
+    let (declared, defined) = copies();
+    let mut a = declared.clone();
+    a.merge(&defined);
+    let mut b = defined.clone();
+    b.merge(&declared);
+    assert_eq!(a, b);
+}
+
+fn params(signature: &str) -> Vec<(String, String, String)> {
+    let s = Symbol { signature: signature.to_string(), ..Default::default() };
+    s.params().into_iter().map(|p| (p.name, p.t, p.decl)).collect()
+}
+
+fn param(name: &str, t: &str, decl: &str) -> (String, String, String) {
+    (name.to_string(), t.to_string(), decl.to_string())
+}
+
+#[test]
+fn params_named_and_unnamed() {
+    assert_eq!(params("(const char *name, int)"), vec![
+        param("name", "const char *", "const char *name"),
+        param("arg2", "int", "int arg2"),
+    ]);
+    assert_eq!(params("(unsigned long, T *, const T &)"), vec![
+        param("arg1", "unsigned long", "unsigned long arg1"),
+        param("arg2", "T *", "T *arg2"),
+        param("arg3", "const T &", "const T &arg3"),
+    ]);
+    assert_eq!(params("(char *const p, const Config)"), vec![
+        param("p", "char *const", "char *const p"),
+        param("arg2", "const Config", "const Config arg2"),
+    ]);
+    assert!(params("(void)").is_empty());
+    assert!(params("()").is_empty());
+}
+
+#[test]
+fn params_struct_tags() {
+    assert_eq!(params("(struct point p, enum mode, union value *v)"), vec![
+        param("p", "struct point", "struct point p"),
+        param("arg2", "enum mode", "enum mode arg2"),
+        param("v", "union value *", "union value *v"),
+    ]);
+}
+
+#[test]
+fn params_arrays() {
+    assert_eq!(params("(char buf[16], int m[2][3], int[])"), vec![
+        param("buf", "char[16]", "char buf[16]"),
+        param("m", "int[2][3]", "int m[2][3]"),
+        param("arg3", "int[]", "int arg3[]"),
+    ]);
+}
+
+#[test]
+fn params_function_pointers() {
+    assert_eq!(params("(int (*cb)(int, void *), void (*)(void))"), vec![
+        param("cb", "int (*)(int, void *)", "int (*cb)(int, void *)"),
+        param("arg2", "void (*)(void)", "void (*arg2)(void)"),
+    ]);
+}
+
+#[test]
+fn params_defaults() {
+    assert_eq!(params("(int n = 3, std::map<int, int> m = {}, bool strict=false, ...)"), vec![
+        param("n", "int", "int n"),
+        param("m", "std::map<int, int>", "std::map<int, int> m"),
+        param("strict", "bool", "bool strict"),
+    ]);
+}
+
+#[test]
+fn only_googletest_bodies_are_unit_tests() {
+    let mut syms: ClangdIdMap = Default::default();
+    for (id, kind, scope, name) in [
+        (1, SymbolKind::InstanceMethod, "MotorTest_Init_Test::", "TestBody"),
+        (2, SymbolKind::InstanceMethod, "Motor::", "TestBody"),
+        // Unity and plain C tests are found by name in `impact`
+        (3, SymbolKind::Function, "", "test_motor_init"),
+    ] {
+        let s = Symbol {
+            id: [id, 0, 0, 0, 0, 0, 0, 0],
+            syminfo: SymbolInfo { kind, ..Default::default() },
+            scope: scope.to_string(),
+            name: name.to_string(),
+            ..Default::default()
+        };
+        syms.insert(s.id, s);
+    }
+    symbols::derive_properties(&mut syms);
+    let tests: Vec<u8> = syms.values().filter(|s| s.has_property(SymbolProperty::UnitTest)).map(|s| s.id[0]).collect();
+    assert_eq!(tests, vec![1]);
+}
+
+// Symbols as parsed, i.e. marked TemplateSpecialization when they have arguments, with their properties derived
+fn derived(syms: &[(u8, SymbolKind, &str, &str, &str, &str)]) -> ClangdIdMap {
+    let mut m: ClangdIdMap = Default::default();
+    for (id, kind, scope, name, args, snippet) in syms {
+        let mut s = Symbol {
+            id: [*id, 0, 0, 0, 0, 0, 0, 0],
+            syminfo: SymbolInfo { kind: kind.clone(), ..Default::default() },
+            scope: scope.to_string(),
+            name: name.to_string(),
+            template_specialization_args: args.to_string(),
+            completion_snippet_suffix: snippet.to_string(),
+            ..Default::default()
+        };
+        if !args.is_empty() {
+            s.syminfo.set_property(SymbolProperty::TemplateSpecialization);
+        }
+        m.insert(s.id, s);
+    }
+    symbols::derive_properties(&mut m);
+    m
+}
+
+fn with_property(syms: &ClangdIdMap, p: SymbolProperty) -> Vec<u8> {
+    syms.values().filter(|s| s.has_property(p.clone())).map(|s| s.id[0]).collect()
+}
+
+// template<class T, std::size_t N> class Buffer, a full and several partial specializations, and Widget
+fn buffers() -> ClangdIdMap {
+    derived(&[
+        (1, SymbolKind::Class, "", "Buffer", "", "<${1:class T}, ${2:std::size_t N}>"),
+        (2, SymbolKind::Class, "", "Buffer", "<int, 4>", ""),
+        (3, SymbolKind::Class, "", "Buffer", "<T *, 3>", ""),
+        (4, SymbolKind::Class, "", "Buffer", "<Widget, N>", ""),
+        (5, SymbolKind::Class, "", "Buffer", "<std::size_t, 8>", ""),
+        (6, SymbolKind::Class, "", "Buffer", "<const unsigned long, sizeof(Widget)>", ""),
+        (7, SymbolKind::Class, "", "Widget", "", ""),
+    ])
+}
+
+#[test]
+fn partial_specializations() {
+    let syms = buffers();
+    assert_eq!(with_property(&syms, SymbolProperty::TemplatePartialSpecialization), vec![3, 4]);
+    assert_eq!(with_property(&syms, SymbolProperty::TemplateSpecialization), vec![2, 5, 6]);
+    // the primary template and the partial specializations
+    assert_eq!(with_property(&syms, SymbolProperty::Generic), vec![1, 3, 4]);
+}
+
+#[test]
+fn generic() {
+    let syms = derived(&[
+        // a template without a snippet, known only by having a specialization
+        (1, SymbolKind::Struct, "", "traits", "", ""),
+        (2, SymbolKind::Struct, "", "traits", "<char>", ""),
+        (3, SymbolKind::Function, "", "max", "", "<${1:typename T}>(${2:T a}, ${3:T b})"),
+        // arguments a function specialization deduced are never a partial specialization
+        (4, SymbolKind::Function, "", "max", "<U>", ""),
+        (5, SymbolKind::Function, "", "min", "", "(${1:int a}, ${2:int b})"),
+    ]);
+    assert_eq!(with_property(&syms, SymbolProperty::Generic), vec![1, 3]);
+    assert_eq!(with_property(&syms, SymbolProperty::TemplateSpecialization), vec![2, 4]);
+    assert!(with_property(&syms, SymbolProperty::TemplatePartialSpecialization).is_empty());
+}
+
+#[test]
+fn local() {
+    let syms = derived(&[
+        (1, SymbolKind::Function, "motor::", "init", "", ""),
+        (2, SymbolKind::Variable, "motor::init::", "retries", "", ""),
+        (3, SymbolKind::Class, "motor::init::", "Guard", "", ""),
+        (4, SymbolKind::Variable, "motor::", "speed", "", ""),
+        (5, SymbolKind::Class, "motor::", "Config", "", ""),
+        (6, SymbolKind::Field, "motor::Config::", "speed", "", ""),
+    ]);
+    assert_eq!(with_property(&syms, SymbolProperty::Local), vec![2, 3]);
+}
+
+#[test]
+fn specializations() {
+    let db = ClangdDatabase { id: buffers(), ..Default::default() };
+    let args: Vec<&str> = db.specializations(&[1, 0, 0, 0, 0, 0, 0, 0]).iter()
+        .map(|s| s.template_specialization_args.as_str())
+        .collect();
+    assert_eq!(args, vec!["<T *, 3>", "<Widget, N>", "<const unsigned long, sizeof(Widget)>", "<int, 4>", "<std::size_t, 8>"]);
+    assert!(db.specializations(&[7, 0, 0, 0, 0, 0, 0, 0]).is_empty());
+    assert!(db.specializations(&[9, 0, 0, 0, 0, 0, 0, 0]).is_empty());
+}
@@ -0,0 +1,129 @@
+//! ctags, etags and cscope output for a header and its source, read back from a scratch project.
+
+mod common;
+
+use clangd_parser::clangd::ClangdDatabase;
+use clangd_parser::refs::RefKind;
+use clangd_parser::symbols::{SymbolFlags, SymbolKind, SymbolLocation, SymbolPosition};
+use clangd_parser::tags;
+use clangd_parser::uri::FileUri;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use common::{id, reference, symbol};
+
+const HEADER: &str = "struct shape {\n    int sides;\n};\nint area(struct shape *s);\n";
+const SOURCE: &str = "#include \"shape.h\"\nstatic int helper(void) { return 0; }\nint area(struct shape *s) {\n    return helper() * s->sides;\n}\n";
+
+fn at(root: &Path, file: &str, line: u32, column: u32, len: u32) -> SymbolLocation {
+    SymbolLocation {
+        start: SymbolPosition { line, column },
+        end: SymbolPosition { line, column: column + len },
+        file_uri: FileUri::from_path(&root.join(file)),
+    }
+}
+
+// shape.h and shape.c under a fresh directory, and their index
+fn project(name: &str) -> (PathBuf, ClangdDatabase) {
+    let root = std::env::temp_dir().join(format!("clangd-parser-tags-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+    fs::write(root.join("shape.h"), HEADER).unwrap();
+    fs::write(root.join("shape.c"), SOURCE).unwrap();
+
+    let visible = SymbolFlags::VisibleOutsideFile as u8;
+    let mut shape = symbol(id(1), SymbolKind::Struct, "", "shape", at(&root, "shape.h", 0, 7, 5));
+    shape.flags = visible;
+    let mut sides = symbol(id(2), SymbolKind::Field, "shape::", "sides", at(&root, "shape.h", 1, 8, 5));
+    sides.flags = visible;
+    let mut area = symbol(id(3), SymbolKind::Function, "", "area", at(&root, "shape.c", 2, 4, 4));
+    area.canonical_declaration = at(&root, "shape.h", 3, 4, 4);
+    area.signature = "(struct shape *s)".to_string();
+    area.return_t = "int".to_string();
+    area.flags = visible;
+    let mut helper = symbol(id(4), SymbolKind::Function, "", "helper", at(&root, "shape.c", 1, 11, 6));
+    helper.signature = "(void)".to_string();
+    helper.return_t = "int".to_string();
+
+    let mut db = common::database(vec![shape, sides, area, helper]);
+    let call = RefKind::Reference as u8 | RefKind::Call as u8;
+    db.refs.insert(id(4), vec![reference(call, at(&root, "shape.c", 3, 11, 6), id(3))]);
+    db.refs.insert(id(2), vec![reference(RefKind::Reference as u8, at(&root, "shape.c", 3, 25, 5), id(3))]);
+    (root, db)
+}
+
+#[test]
+fn ctags() {
+    let (root, db) = project("ctags");
+    let mut out: Vec<u8> = vec![];
+    tags::ctags(&db, &root, &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    let lines: Vec<&str> = out.lines().filter(|l| !l.starts_with("!_TAG_")).collect();
+    assert_eq!(lines, vec![
+        "area\tshape.c\t3;\"\tf\ttyperef:typename:int\tsignature:(struct shape *s)",
+        "area\tshape.h\t4;\"\tp\ttyperef:typename:int\tsignature:(struct shape *s)",
+        "helper\tshape.c\t2;\"\tf\ttyperef:typename:int\tsignature:(void)\tfile:",
+        "shape\tshape.h\t1;\"\ts",
+        "sides\tshape.h\t2;\"\tm\tstruct:shape",
+    ]);
+    // vim binary searches the file, so the pseudo tags have to sort first too
+    let all: Vec<&str> = out.lines().collect();
+    let mut sorted = all.clone();
+    sorted.sort();
+    assert_eq!(all, sorted);
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn etags() {
+    let (root, db) = project("etags");
+    let mut out: Vec<u8> = vec![];
+    tags::etags(&db, &root, &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+
+    let mut sections: Vec<(String, usize, String)> = vec![];
+    let mut rest = out.as_str();
+    while let Some(s) = rest.strip_prefix("\x0c\n") {
+        let (header, body) = s.split_once('\n').unwrap();
+        let (file, len) = header.rsplit_once(',').unwrap();
+        let len: usize = len.parse().unwrap();
+        // the length in the header is exactly the section's, in bytes
+        sections.push((file.to_string(), len, body[..len].to_string()));
+        rest = &body[len..];
+    }
+    assert!(rest.is_empty());
+
+    let line_offset = |text: &str, line: usize| text.split_inclusive('\n').take(line).map(|l| l.len()).sum::<usize>();
+    assert_eq!(sections, vec![
+        ("shape.c".to_string(), 49, format!("static int helper\x7fhelper\x012,{}\nint area\x7farea\x013,{}\n",
+            line_offset(SOURCE, 1), line_offset(SOURCE, 2))),
+        ("shape.h".to_string(), 67, format!("struct shape\x7fshape\x011,0\n    int sides\x7fsides\x012,{}\nint area\x7farea\x014,{}\n",
+            line_offset(HEADER, 1), line_offset(HEADER, 3))),
+    ]);
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn cscope() {
+    let (root, db) = project("cscope");
+    let mut out: Vec<u8> = vec![];
+    tags::cscope(&db, &root, &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+
+    let (header, _) = out.split_once('\n').unwrap();
+    let fields: Vec<&str> = header.split(' ').collect();
+    assert_eq!(fields[..2], ["cscope", "15"]);
+    assert_eq!(fields[2], root.to_string_lossy());
+    assert_eq!(fields[3], "-c");
+    // the offset in the header points at the trailer, just past the terminating empty file name
+    let trailer = &out[fields[4].parse::<usize>().unwrap()..];
+    assert_eq!(trailer, "1\n.\n0\n2\n16\nshape.c\nshape.h\n");
+    assert!(out[..out.len() - trailer.len()].ends_with("\t@\n"));
+
+    // helper() is defined and called in shape.c, with the source text around each mark
+    assert!(out.contains("\t@shape.c\n\n2 static int\n\t$helper\n(void) { return 0; }\n\n"));
+    assert!(out.contains("4 return\n\t`helper\n() * s->\nsides\n;\n\n"));
+    assert!(out.contains("\t@shape.h\n\n1 struct\n\tsshape\n{\n\n2 int\n\tmsides\n;\n\n"));
+    fs::remove_dir_all(root).unwrap();
+}
@@ -0,0 +1,67 @@
+//! Spell includes the way the code under test is built.
+
+use std::path::PathBuf;
+
+use clangd_parser::clangd::{ClangdDatabase, ClangdFile};
+use clangd_parser::cmdl::Cmdl;
+use clangd_parser::srcs::{SourceFlags, Srcs};
+use clangd_parser::symbols::{Symbol, SymbolIncludedHeader, SymbolLocation};
+use clangd_parser::testgen;
+use clangd_parser::uri::FileUri;
+
+fn uri(file: &str) -> FileUri {
+    FileUri::new(&format!("file:///work/{}", file))
+}
+
+fn declared_in(header: &str) -> Symbol {
+    Symbol {
+        name: "foo_init".to_string(),
+        canonical_declaration: SymbolLocation { file_uri: uri(header), ..Default::default() },
+        definition: SymbolLocation { file_uri: uri("src/foo.c"), ..Default::default() },
+        ..Default::default()
+    }
+}
+
+#[test]
+fn include_relative_to_include_dirs_then_root() {
+    let s = declared_in("include/mylib/foo.h");
+    let root = PathBuf::from("/work");
+    assert_eq!(testgen::include_for(&s, &[PathBuf::from("/work/include"), root.clone()]).unwrap(), "#include \"mylib/foo.h\"");
+    assert_eq!(testgen::include_for(&s, &[root]).unwrap(), "#include \"include/mylib/foo.h\"");
+    // nothing to go on
+    assert_eq!(testgen::include_for(&s, &[]).unwrap(), "#include \"foo.h\"");
+}
+
+#[test]
+fn include_spelled_by_clangd_wins() {
+    let mut s = declared_in("include/mylib/foo.h");
+    s.headers.push(SymbolIncludedHeader { name: FileUri::new("<mylib/foo.h>"), refs: 3, supported_directives: 1 });
+    assert_eq!(testgen::include_for(&s, &[]).unwrap(), "#include <mylib/foo.h>");
+
+    // a URI is spelled like the declaring header
+    s.headers[0].name = uri("include/mylib/foo.h");
+    assert_eq!(testgen::include_for(&s, &[PathBuf::from("/work/include")]).unwrap(), "#include \"mylib/foo.h\"");
+}
+
+#[test]
+fn include_dirs_from_the_compile_command() {
+    let mut f: ClangdFile = Default::default();
+    f.sources.data.push(Srcs { flags: SourceFlags::IsTU as u8, uri: uri("src/foo.c"), ..Default::default() });
+    f.cmdline.data.push(Cmdl {
+        directory: "/work/build".to_string(),
+        cmdl: ["cc", "-I", "../include", "-iquote/work/src", "-DNDEBUG", "-c", "/work/src/foo.c"].map(String::from).to_vec(),
+    });
+    let mut db: ClangdDatabase = Default::default();
+    db.file.insert("foo.c.0123456789ABCDEF.idx".to_string(), f);
+
+    let root = PathBuf::from("/work");
+    assert_eq!(testgen::include_dirs(&db, &uri("src/foo.c"), &root), vec![
+        PathBuf::from("/work/build/../include"),
+        PathBuf::from("/work/src"),
+        root.clone(),
+    ]);
+    let dirs = testgen::include_dirs(&db, &uri("src/foo.c"), &root);
+    assert_eq!(testgen::include_for(&declared_in("include/mylib/foo.h"), &dirs).unwrap(), "#include \"mylib/foo.h\"");
+    // headers have no command of their own
+    assert_eq!(testgen::include_dirs(&db, &uri("include/mylib/foo.h"), &root), vec![root]);
+}
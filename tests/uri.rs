@@ -0,0 +1,136 @@
+//! File URIs as clangd writes them: escapes, schemes, Windows drives and relative paths.
+
+use std::path::{Path, PathBuf};
+
+use clangd_parser::uri::{self, FileUri};
+
+#[test]
+fn decode() {
+    assert_eq!(uri::decode("/work/my%20file.c"), "/work/my file.c");
+    assert_eq!(uri::decode("src%2Fmotor.c"), "src/motor.c");
+    assert_eq!(uri::decode("src%2fmotor.c"), "src/motor.c");
+    assert_eq!(uri::decode("/work/caf%C3%A9.c"), "/work/café.c");
+    assert_eq!(uri::decode("/c%3A/src"), "/c:/src");
+    // not escapes
+    for s in ["100%", "%2", "%zz.c"] {
+        assert_eq!(uri::decode(s), s);
+    }
+    assert_eq!(uri::decode("a%%20"), "a% ");
+}
+
+#[test]
+fn encode() {
+    assert_eq!(uri::encode("/work/my file.c"), "/work/my%20file.c");
+    assert_eq!(uri::encode("C:/src/a+b.c"), "C:/src/a%2Bb.c");
+    assert_eq!(uri::encode("/work/café.c"), "/work/caf%C3%A9.c");
+    assert_eq!(uri::encode("/work/a-b_c.~1/d.h"), "/work/a-b_c.~1/d.h");
+}
+
+#[test]
+fn encode_round_trip() {
+    for s in ["/work/my file.c", "C:/src/a+b.c", "/work/café.c", "/work/100%/x#1?.h", "/work/a%20b.c"] {
+        assert_eq!(uri::decode(&uri::encode(s)), s);
+    }
+}
+
+#[test]
+fn scheme_and_path() {
+    let cases = [
+        ("file:///work/src/a.c", Some("file"), "/work/src/a.c"),
+        ("test:///foo.h", Some("test"), "/foo.h"),
+        ("unittest:/foo.h", Some("unittest"), "/foo.h"),
+        // the authority is dropped
+        ("file://server/share/a.c", Some("file"), "/share/a.c"),
+        // verbatim headers and drive letters have no scheme
+        ("<stdio.h>", None, "<stdio.h>"),
+        ("C:/src/a.c", None, "C:/src/a.c"),
+    ];
+    for (raw, scheme, path) in cases {
+        let u = FileUri::new(raw);
+        assert_eq!((u.scheme(), u.path().as_str()), (scheme, path), "{}", raw);
+        assert_eq!(u.as_str(), raw);
+    }
+}
+
+#[test]
+fn normalized_comparison() {
+    let a = FileUri::new("file:///work/include/motor.h");
+    for same in [
+        "file:///work/src/../include/motor.h",
+        "file:///work//include/./motor.h",
+        "file:///work%2Finclude%2Fmotor.h",
+        "file://localhost/work/include/motor.h",
+        "file:///work/include/motor%2Eh",
+        "file:///../work/include/motor.h",
+    ] {
+        assert_eq!(FileUri::new(same), a, "{}", same);
+    }
+    assert_ne!(FileUri::new("test:///work/include/motor.h"), a);
+    assert_ne!(FileUri::new("file:///work/include/Motor.h"), a);
+    // the raw text is kept for display
+    assert_eq!(FileUri::new("file:///work%2Finclude%2Fmotor.h").to_string(), "file:///work%2Finclude%2Fmotor.h");
+}
+
+#[test]
+fn windows_drives() {
+    for raw in ["file:///C:/src/a.c", "file:///C%3A/src/a.c"] {
+        assert_eq!(FileUri::new(raw).to_path(), Some(PathBuf::from("C:/src/a.c")), "{}", raw);
+    }
+    let u = FileUri::from_path(Path::new("C:\\src\\a b.c"));
+    assert_eq!(u.as_str(), "file:///C:/src/a%20b.c");
+    assert_eq!(u.to_path(), Some(PathBuf::from("C:/src/a b.c")));
+    assert_eq!(u, FileUri::new("file:///C%3A/src/a%20b.c"));
+    // the drive letter's case doesn't matter, the rest of the path's does
+    assert_eq!(FileUri::new("file:///C:/src/a.c"), FileUri::new("file:///c:/src/a.c"));
+    assert_eq!(FileUri::new("file:///c%3a/src/a.c"), FileUri::new("file:///C:/src/a.c"));
+    assert_ne!(FileUri::new("file:///C:/Src/a.c"), FileUri::new("file:///c:/src/a.c"));
+    assert_eq!(FileUri::new("file:///c:/src/a.c").relative_to(Path::new("C:\\src")), Some(PathBuf::from("a.c")));
+    // and the raw text keeps it
+    assert_eq!(FileUri::new("file:///C:/src/a.c").as_str(), "file:///C:/src/a.c");
+}
+
+#[test]
+fn file_names() {
+    let cases = [
+        ("file:///work/src/motor.c", Some("motor.c")),
+        ("<stdio.h>", Some("stdio.h")),
+        ("<sys/types.h>", Some("types.h")),
+        ("\"config.h\"", Some("config.h")),
+        ("\"gen/version.h\"", Some("version.h")),
+        ("file:///work/src/", None),
+    ];
+    for (raw, name) in cases {
+        assert_eq!(FileUri::new(raw).file_name().as_deref(), name, "{}", raw);
+    }
+    assert_eq!(FileUri::new("<stdio.h>").extension().as_deref(), Some("h"));
+    assert!(FileUri::new("<sys/types.h>").is_header());
+}
+
+#[test]
+fn to_path() {
+    assert_eq!(FileUri::new("file:///work/my%20file.c").to_path(), Some(PathBuf::from("/work/my file.c")));
+    assert_eq!(FileUri::new("test:///foo.h").to_path(), None);
+    assert_eq!(FileUri::new("<stdio.h>").to_path(), None);
+}
+
+#[test]
+fn path_round_trip() {
+    for p in ["/work/src/a.c", "/work/my file.c", "/work/café/a+b.c"] {
+        let u = FileUri::from_path(Path::new(p));
+        assert_eq!(u.to_path(), Some(PathBuf::from(p)));
+        assert_eq!(FileUri::new(u.as_str()), u);
+    }
+}
+
+#[test]
+fn relative_to() {
+    let u = FileUri::new("file:///work/src/../include/motor.h");
+    assert_eq!(u.relative_to(Path::new("/work")), Some(PathBuf::from("include/motor.h")));
+    assert_eq!(u.relative_to(Path::new("/work/")), Some(PathBuf::from("include/motor.h")));
+    assert_eq!(u.relative_to(Path::new("/work/build/../include")), Some(PathBuf::from("motor.h")));
+    assert_eq!(u.relative_to(Path::new("/wor")), None);
+    assert_eq!(u.relative_to(Path::new("/other")), None);
+    assert_eq!(u.relative_to(Path::new("")), None);
+    assert_eq!(FileUri::new("file:///C:/src/a.c").relative_to(Path::new("C:\\src")), Some(PathBuf::from("a.c")));
+    assert_eq!(FileUri::new("test:///work/motor.h").relative_to(Path::new("/work")), None);
+}
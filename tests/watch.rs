@@ -0,0 +1,178 @@
+//! Compare two versions of a shard the way the watcher does on each poll, and load shards caught mid-write.
+
+use clangd_parser::clangd::{ClangdFile, LoadError};
+use clangd_parser::refs::{RefKind, RefReferences, Refs};
+use clangd_parser::srcs::Srcs;
+use clangd_parser::symbols::{Symbol, SymbolInfo, SymbolKind, SymbolLocation, SymbolPosition};
+use clangd_parser::uri::FileUri;
+use clangd_parser::watch::{diff_shards, IndexEvent, IndexWatcher};
+
+const INIT: [u8; 8] = [1, 0, 0, 0, 0, 0, 0, 0];
+const STEP: [u8; 8] = [2, 0, 0, 0, 0, 0, 0, 0];
+const STOP: [u8; 8] = [3, 0, 0, 0, 0, 0, 0, 0];
+
+fn uri(file: &str) -> FileUri {
+    FileUri::new(&format!("file:///work/{}", file))
+}
+
+fn location(line: u32) -> SymbolLocation {
+    SymbolLocation {
+        start: SymbolPosition { line, column: 4 },
+        end: SymbolPosition { line, column: 8 },
+        file_uri: uri("src/motor.c"),
+    }
+}
+
+fn symbol(id: [u8; 8], name: &str, line: u32) -> Symbol {
+    Symbol {
+        id,
+        syminfo: SymbolInfo { kind: SymbolKind::Function, ..Default::default() },
+        name: name.to_string(),
+        definition: location(line),
+        canonical_declaration: location(line),
+        ..Default::default()
+    }
+}
+
+fn shard() -> ClangdFile {
+    let mut file: ClangdFile = Default::default();
+    file.symbols.data.push(symbol(INIT, "motor_init", 3));
+    file.symbols.data.push(symbol(STEP, "motor_step", 10));
+    file.references.data.push(Refs {
+        id: INIT,
+        cnt: 1,
+        refs: vec![RefReferences { kind: RefKind::Definition as u8, location: location(3), container_id: [0; 8] }],
+    });
+    file.sources.data.push(Srcs {
+        uri: uri("src/motor.c"),
+        direct_includes: vec![uri("src/motor.h")],
+        ..Default::default()
+    });
+    file
+}
+
+#[test]
+fn unchanged() {
+    assert!(diff_shards("motor.c", &shard(), &shard()).is_empty());
+}
+
+#[test]
+fn new_shard_adds_everything() {
+    let events = diff_shards("motor.c", &Default::default(), &shard());
+    let names: Vec<String> = events.iter().map(|e| e.to_string()).collect();
+    assert_eq!(names, vec![
+        "[motor.c] + motor_init",
+        "[motor.c] + motor_step",
+        "[motor.c] refs 0100000000000000 (1 refs)",
+        "[motor.c] includes file:///work/src/motor.c (+1 -0)",
+    ]);
+}
+
+#[test]
+fn removed_shard_removes_everything() {
+    let events = diff_shards("motor.c", &shard(), &Default::default());
+    assert_eq!(events.len(), 4);
+    assert!(matches!(&events[0], IndexEvent::SymbolRemoved { symbol, .. } if symbol.id == INIT));
+    assert!(matches!(&events[1], IndexEvent::SymbolRemoved { symbol, .. } if symbol.id == STEP));
+    assert_eq!(events[2], IndexEvent::RefsChanged { shard: "motor.c".to_string(), id: INIT, refs: vec![] });
+    assert_eq!(events[3], IndexEvent::IncludesChanged {
+        shard: "motor.c".to_string(),
+        uri: uri("src/motor.c"),
+        added: vec![],
+        removed: vec![uri("src/motor.h")],
+    });
+}
+
+#[test]
+fn symbols_added_changed_and_removed() {
+    let mut new = shard();
+    new.symbols.data.retain(|s| s.id != INIT);
+    new.symbols.data[0].signature = "(int speed)".to_string();
+    new.symbols.data.push(symbol(STOP, "motor_stop", 20));
+
+    let events: Vec<IndexEvent> = diff_shards("motor.c", &shard(), &new).into_iter()
+        .filter(|e| !matches!(e, IndexEvent::RefsChanged { .. }))
+        .collect();
+    assert_eq!(events.len(), 3);
+    match &events[0] {
+        IndexEvent::SymbolChanged { shard, old, new } => {
+            assert_eq!(shard, "motor.c");
+            assert_eq!(old.signature, "");
+            assert_eq!(new.signature, "(int speed)");
+        },
+        e => panic!("unexpected {:?}", e),
+    }
+    assert!(matches!(&events[1], IndexEvent::SymbolAdded { symbol, .. } if symbol.name == "motor_stop"));
+    assert!(matches!(&events[2], IndexEvent::SymbolRemoved { symbol, .. } if symbol.name == "motor_init"));
+}
+
+#[test]
+fn refs_and_includes_changed() {
+    let mut new = shard();
+    new.references.data[0].refs.push(RefReferences { kind: RefKind::Reference as u8, location: location(12), container_id: STEP });
+    new.sources.data[0].direct_includes = vec![uri("src/pwm.h")];
+
+    let events = diff_shards("motor.c", &shard(), &new);
+    assert_eq!(events.len(), 2);
+    assert!(matches!(&events[0], IndexEvent::RefsChanged { id, refs, .. } if *id == INIT && refs.len() == 2));
+    assert_eq!(events[1], IndexEvent::IncludesChanged {
+        shard: "motor.c".to_string(),
+        uri: uri("src/motor.c"),
+        added: vec![uri("src/pwm.h")],
+        removed: vec![uri("src/motor.h")],
+    });
+}
+
+#[test]
+fn load_without_index_is_an_error() {
+    let root = std::env::temp_dir().join(format!("clangd-parser-empty-{}", std::process::id()));
+    std::fs::create_dir_all(&root).unwrap();
+    assert!(matches!(clangd_parser::load(&root, &[]), Err(LoadError::NoCache(_))));
+    assert!(IndexWatcher::new(&root).is_err());
+    std::fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn half_written_shard_is_a_failure() {
+    // a shard clangd is still writing: the header promises more than is there
+    let root = std::env::temp_dir().join(format!("clangd-parser-partial-{}", std::process::id()));
+    let index = root.join(".cache").join("clangd").join("index");
+    std::fs::create_dir_all(&index).unwrap();
+    std::fs::write(index.join("motor.c.0123456789ABCDEF.idx"), b"RIFF\x40\x00\x00\x00CdIxmeta").unwrap();
+
+    let db = clangd_parser::load(&root, &[]).unwrap();
+    assert!(db.file.is_empty());
+    assert_eq!(db.failures.len(), 1);
+
+    let w = IndexWatcher::new(&root).unwrap();
+    assert_eq!(w.failures().len(), 1);
+    assert_eq!(w.database().failures.len(), 1);
+    std::fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn shard_names() {
+    assert_eq!(clangd_parser::shard_name("motor.c.0123456789ABCDEF.idx"), Some("motor.c"));
+    assert_eq!(clangd_parser::shard_name("motor.pb.cc.0123456789ABCDEF.idx"), Some("motor.pb.cc"));
+    assert_eq!(clangd_parser::shard_name("Makefile.0123456789ABCDEF.idx"), Some("Makefile"));
+    for bad in ["motor.c", "motor.idx", ".0123456789ABCDEF.idx", ""] {
+        assert_eq!(clangd_parser::shard_name(bad), None, "{}", bad);
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn non_utf8_names_are_skipped() {
+    use std::os::unix::ffi::OsStrExt;
+
+    let root = std::env::temp_dir().join(format!("clangd-parser-names-{}", std::process::id()));
+    let index = root.join(".cache").join("clangd").join("index");
+    std::fs::create_dir_all(&index).unwrap();
+    std::fs::write(index.join(std::ffi::OsStr::from_bytes(b"mot\xffor.c.0123456789ABCDEF.idx")), b"RIFF").unwrap();
+    std::fs::write(index.join("README"), b"not a shard").unwrap();
+
+    let mut w = IndexWatcher::new(&root).unwrap();
+    assert!(w.poll().is_empty());
+    assert!(w.failures().is_empty());
+    std::fs::remove_dir_all(root).unwrap();
+}